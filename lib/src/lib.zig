@@ -23,14 +23,21 @@ export fn memset(dest: ?[*]u8, c: u8, n: usize) callconv(.C) ?[*]u8 {
 
 // The actual stuff
 
-export fn PutString(s: [*]i64) i64 {
+// ez chars are unicode code points. this writes one of them to stdout as UTF-8
+fn writeCodePoint(c: i64) i64 {
     const stdout = std.io.getStdOut().writer();
+    if (c < 0 or c > 0x10FFFF) return -1;
+    var buf: [4]u8 = undefined;
+    const len = std.unicode.utf8Encode(@intCast(u21, c), &buf) catch return -1;
+    stdout.writeAll(buf[0..len]) catch return -1;
+    return 0;
+}
+
+export fn PutString(s: [*]i64) i64 {
     const len: i64 = s[1];
     var were_on_rn: u32 = 2;
-    var char: u8 = 0;
     while (were_on_rn < len + 2) : (were_on_rn += 1) { // we do len + 2 because the offset in the beg of array is 2
-        char = @intCast(u8, s[were_on_rn]);
-        stdout.print("{c}", .{char}) catch return -1;
+        if (writeCodePoint(s[were_on_rn]) != 0) return -1;
     }
     return 0;
 }
@@ -46,10 +53,8 @@ export fn PutNewLine() i64 {
     return 0;
 }
 
-export fn PutChar(u: u8) i64 {
-    const stdout = std.io.getStdOut().writer();
-    stdout.print("{c}", .{u}) catch return -1;
-    return 0;
+export fn PutChar(c: i64) i64 {
+    return writeCodePoint(c);
 }
 export fn PutNum(n: i64) i64 {
     const stdout = std.io.getStdOut().writer();
@@ -69,20 +74,77 @@ export fn PutNumBin(n: i64) i64 {
     return 0;
 }
 
+const replacement_character: u21 = 0xFFFD;
+
+// a code point from the start of some UTF-8 and how many bytes it took
+const Decoded = struct {
+    code_point: u21,
+    len: usize,
+};
+
+// decode the code point at the start of `bytes`. a bad sequence becomes U+FFFD, one for each start of a sequence that
+// could have been good, like rust's from_utf8_lossy. that way a line reads the same here and in ezc
+fn decodeLossy(bytes: []const u8) Decoded {
+    const first = bytes[0];
+    if (first < 0x80) return Decoded{ .code_point = first, .len = 1 };
+    // how many bytes it has, and what the second byte can be so that overlong forms and surrogates are bad
+    var len: usize = 0;
+    var low: u8 = 0x80;
+    var high: u8 = 0xBF;
+    switch (first) {
+        0xC2...0xDF => len = 2,
+        0xE0 => {
+            len = 3;
+            low = 0xA0;
+        },
+        0xE1...0xEC, 0xEE...0xEF => len = 3,
+        0xED => {
+            len = 3;
+            high = 0x9F;
+        },
+        0xF0 => {
+            len = 4;
+            low = 0x90;
+        },
+        0xF1...0xF3 => len = 4,
+        0xF4 => {
+            len = 4;
+            high = 0x8F;
+        },
+        else => return Decoded{ .code_point = replacement_character, .len = 1 },
+    }
+    var code_point: u21 = first & (@as(u8, 0x7F) >> @intCast(u3, len));
+    var i: usize = 1;
+    while (i < len) : (i += 1) {
+        if (i >= bytes.len) return Decoded{ .code_point = replacement_character, .len = i };
+        const b = bytes[i];
+        const b_low: u8 = if (i == 1) low else 0x80;
+        const b_high: u8 = if (i == 1) high else 0xBF;
+        if (b < b_low or b > b_high) return Decoded{ .code_point = replacement_character, .len = i };
+        code_point = (code_point << 6) | @as(u21, b & 0x3F);
+    }
+    return Decoded{ .code_point = code_point, .len = len };
+}
+
 export fn InputLine() [*]i64 {
     var general_purpose_allocator = std.heap.GeneralPurposeAllocator(.{}){};
     const gpa = &general_purpose_allocator.allocator;
     const stdin = std.io.getStdIn().reader();
     const output = stdin.readUntilDelimiterAlloc(gpa, '\n', 10000) catch unreachable;
     defer gpa.free(output);
+    // the line is decoded from UTF-8 so that every element is one code point, just like a string literal. stdin can
+    // have anything in it, so it can't be assumed to be good UTF-8. there are never more code points than bytes
     var mem = gpa.alloc(i64, output.len + 2) catch unreachable;
     {
         var index: usize = 0;
-        while (index < output.len) : (index += 1) {
-            mem[index + 2] = @intCast(i64, output[index]);
+        var at: usize = 0;
+        while (at < output.len) : (index += 1) {
+            const decoded = decodeLossy(output[at..]);
+            mem[index + 2] = @intCast(i64, decoded.code_point);
+            at += decoded.len;
         }
         mem[0] = @bitCast(i64, @ptrToInt(&mem));
-        mem[1] = @bitCast(i64, output.len);
+        mem[1] = @bitCast(i64, index);
     }
     return mem.ptr;
 }
@@ -1,34 +1,86 @@
 //! analisis on the ast
 
+use crate::codegen::MAX_ARGS;
+use crate::errors::{Warning, WarningKind};
+use crate::lexer::Span;
 use crate::{ast, ast::AstNode, ast::Expr, ast::TypeOfSetOrChange, ast::Val};
 use std::collections::HashMap;
 use std::collections::HashSet;
 
 /// error for a code analyze
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AnalysisError {
     /// set two times same var
-    DoubleSet(String),
+    DoubleSet(String, Span),
     /// A variable does not exist
-    VarNotExist(String),
+    VarNotExist(String, Span),
     /// bigger than 2^64 num
-    NumberTooBig(String),
+    NumberTooBig(String, Span),
     /// try to set a var in loop. doesn't work for technical reasons
-    SetInLoop,
+    SetInLoop(Span),
     /// a break without a loop
-    BreakWithoutLoop,
+    BreakWithoutLoop(Span),
     /// Return outside of func
-    ReturnOutSideOfFunc,
+    ReturnOutSideOfFunc(Span),
     /// the function already exists
-    FuncAlreadyExists(String),
+    FuncAlreadyExists(String, Span),
     /// same arg for function
-    SameArgForFunction(ast::Type),
+    SameArgForFunction(ast::Type, Span),
     /// function called with wrong number of args
-    FuncCalledWithWrongArgsType(String, Vec<Type>, Vec<Type>),
+    FuncCalledWithWrongArgsType(String, Vec<Type>, Vec<Type>, Span),
     /// funccalledbutnoexist
-    FuncCalledButNoExist(String),
+    FuncCalledButNoExist(String, Span),
     /// cannot change something to an array
-    CannotChangeSomethingToArray(String, TypeOfSetOrChange),
+    CannotChangeSomethingToArray(String, TypeOfSetOrChange, Span),
+    /// `return x.` in a function that `returns nothing`
+    ReturnValueFromNothingFunc(String, Span),
+    /// `return.` in a function that returns a value
+    ReturnWithoutValue(String, Span),
+    /// indexing something that is not an array. has the whole expression and the type of the thing
+    NotAnArray(String, Type, Span),
+    /// `@` on something that is not a pointer. has the whole expression and the type of the thing
+    NotAPointer(String, Type, Span),
+    /// an array in a binop. has the array and the whole binop
+    ArrayInArithmetic(String, String, Span),
+    /// the result of a function that `returns nothing` is used
+    NothingUsedAsValue(String, Span),
+    /// an expression with the wrong type. has the expression, what it should be and what it is
+    MismatchedTypes(String, Type, Type, Span),
+    /// a variadic function called without all of the args that come before the `...`. has how many it needs and how many it got
+    VariadicFuncCalledWithTooFewArgs(String, usize, usize, Span),
+    /// a function called with more args than fit in registers. has how many it got
+    TooManyArgs(String, usize, Span),
+    /// a function that takes more args than fit in registers. has how many it takes
+    TooManyParams(String, usize, Span),
+}
+
+impl AnalysisError {
+    /// where the error happened
+    pub fn span(&self) -> Span {
+        match self {
+            AnalysisError::DoubleSet(_, span)
+            | AnalysisError::VarNotExist(_, span)
+            | AnalysisError::NumberTooBig(_, span)
+            | AnalysisError::SetInLoop(span)
+            | AnalysisError::BreakWithoutLoop(span)
+            | AnalysisError::ReturnOutSideOfFunc(span)
+            | AnalysisError::FuncAlreadyExists(_, span)
+            | AnalysisError::SameArgForFunction(_, span)
+            | AnalysisError::FuncCalledWithWrongArgsType(_, _, _, span)
+            | AnalysisError::FuncCalledButNoExist(_, span)
+            | AnalysisError::CannotChangeSomethingToArray(_, _, span)
+            | AnalysisError::ReturnValueFromNothingFunc(_, span)
+            | AnalysisError::ReturnWithoutValue(_, span)
+            | AnalysisError::NotAnArray(_, _, span)
+            | AnalysisError::NotAPointer(_, _, span)
+            | AnalysisError::ArrayInArithmetic(_, _, span)
+            | AnalysisError::NothingUsedAsValue(_, span)
+            | AnalysisError::MismatchedTypes(_, _, _, span)
+            | AnalysisError::VariadicFuncCalledWithTooFewArgs(_, _, _, span)
+            | AnalysisError::TooManyArgs(_, _, span)
+            | AnalysisError::TooManyParams(_, _, span) => *span,
+        }
+    }
 }
 
 /// a way to see what ur in
@@ -39,12 +91,73 @@ struct Scope {
     in_func: bool,
 }
 
-/// a wrapper function to analize the ast
-pub fn analize(ast: &mut ast::AstRoot) -> Result<(), AnalysisError> {
+/// a wrapper function to analize the ast. all of the errors in the ast are returned, not just the first one.
+/// if there are no errors, the warnings are returned
+pub fn analize(ast: &mut ast::AstRoot) -> Result<Vec<Warning>, Vec<AnalysisError>> {
     let mut analizer = Analyser::new();
-    analizer.analyze(&mut ast.tree)?;
+    analizer.analyze(&mut ast.tree);
+    if !analizer.errors.is_empty() {
+        return Err(analizer.errors);
+    }
+    for (name, usage) in std::mem::take(&mut analizer.initialized_static_vars) {
+        analizer.warn_if_unused(&name, usage);
+    }
+    for (name, span) in std::mem::take(&mut analizer.unexported_functions) {
+        if !analizer.called_functions.contains(&name) {
+            analizer.warnings.push(Warning {
+                kind: WarningKind::UnusedFunction,
+                message: format!("the function `{}` is never called", name),
+                span,
+            });
+        }
+    }
     ast.static_vars = Some(get_all_var_decls(&ast.tree));
-    Ok(())
+    Ok(analizer.warnings)
+}
+
+/// analyses top level code a piece at a time, like the inputs of the repl. what the pieces before set and declared
+/// stays known, so nothing is analysed twice. code with errors can leave some of its names behind, so keep a clone
+/// from before it to go back to
+#[derive(Debug, Clone)]
+pub struct Session {
+    analyser: Analyser,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Session::new()
+    }
+}
+
+impl Session {
+    /// a session that knows no names yet
+    pub fn new() -> Self {
+        Session {
+            analyser: Analyser::new(),
+        }
+    }
+    /// analyse more top level code. gives back its warnings, or all of its errors
+    pub fn analyze(&mut self, tree: &mut Vec<AstNode>) -> Result<Vec<Warning>, Vec<AnalysisError>> {
+        self.analyser.analyze(tree);
+        self.result()
+    }
+    /// analyse an expression at the top level. a call on its own can be to a function that `returns nothing`
+    pub fn analyze_expr(&mut self, expr: &mut Expr) -> Result<Vec<Warning>, Vec<AnalysisError>> {
+        if let Expr::FuncCall { .. } = expr {
+            self.analyser.check_expr(expr);
+        } else {
+            self.analyser.check_value_expr(expr);
+        }
+        self.result()
+    }
+    fn result(&mut self) -> Result<Vec<Warning>, Vec<AnalysisError>> {
+        let warnings = std::mem::take(&mut self.analyser.warnings);
+        if self.analyser.errors.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(std::mem::take(&mut self.analyser.errors))
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -52,92 +165,169 @@ pub fn analize(ast: &mut ast::AstRoot) -> Result<(), AnalysisError> {
 pub enum Type {
     /// a number
     Number,
-    /// an array type like [5]n
+    /// an array type like [5]n. an array is also a pointer to its first element
     Arr(i64),
+    /// could be anything. this is what external functions give back and what function arguments are, since they don't say their types
+    Any,
+    /// what a function that `returns nothing` gives back. it can't be used for anything
+    Nothing,
+}
+
+impl Type {
+    /// can this be used where a number goes
+    fn is_number(self) -> bool {
+        matches!(self, Type::Number | Type::Any)
+    }
+    /// can this be indexed or used with `@`
+    fn is_array(self) -> bool {
+        matches!(self, Type::Arr(_) | Type::Any)
+    }
+}
+
+/// what is known about a variable: its type, where it was made and if it was ever read (for the unused warnings)
+#[derive(Debug, Copy, Clone)]
+struct Var {
+    ty: Type,
+    span: Span,
+    read: bool,
+    /// it gets set or changed to a function call. these are not warned about since the result of a call has to go somewhere
+    binds_call: bool,
+    is_param: bool,
+}
+
+impl Var {
+    fn new(ty: Type, span: Span, setor: &Val) -> Self {
+        Var {
+            ty,
+            span,
+            read: false,
+            binds_call: is_call(setor),
+            is_param: false,
+        }
+    }
+}
+
+/// what is known about a function from the way it was declared
+#[derive(Debug, Clone)]
+struct Signature {
+    /// the types of the arguments it takes
+    args: Vec<Type>,
+    /// it takes any number of args after `args`
+    variadic: bool,
+    /// what it gives back. this is `Type::Any` for ez functions that haven't been analysed yet
+    returns: Type,
+    /// it is called by its real name and not a mangled one. true for external and exported functions
+    external: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Analyser {
     /// the initialized_static_vars
-    initialized_static_vars: HashSet<String>,
+    initialized_static_vars: HashMap<String, Var>,
     /// the initialized_local_vars
-    initialized_local_vars: HashMap<String, (u32, bool)>,
-    /// the initialized_function_names
-    initialized_functions: HashMap<String, Vec<Type>>,
-    /// the initialized_external_functions
-    initialized_external_functions: HashMap<String, u32>,
+    initialized_local_vars: HashMap<String, (u32, bool, Var)>,
+    /// the signatures of all the functions, external ones too. the ones from used files are also under `module's Name`
+    initialized_functions: HashMap<String, Signature>,
+    /// the functions from used files that can be called without their module. a function in this file with the same name hides them
+    imported_functions: HashSet<String>,
     /// the initialized_function_vars
-    initialized_function_vars: HashMap<String, Type>,
+    initialized_function_vars: HashMap<String, Var>,
+    /// the functions that are not exported, with where they are declared
+    unexported_functions: Vec<(String, Span)>,
+    /// the functions that get called from somewhere other than themselves
+    called_functions: HashSet<String>,
+    /// the function that is being analysed
+    cur_func: Option<String>,
+    /// the function that is being analysed was declared with `returns nothing`
+    cur_func_returns_nothing: bool,
+    /// the type of the values returned so far from the function that is being analysed
+    cur_func_returns: Option<Type>,
     /// scope that the analizer is in rn
     scope: Scope,
+    /// the errors found so far
+    errors: Vec<AnalysisError>,
+    /// the warnings found so far
+    warnings: Vec<Warning>,
 }
 
 impl Analyser {
     /// create an Analyser
     pub fn new() -> Self {
         Self {
-            initialized_static_vars: HashSet::new(),
-            initialized_external_functions: HashMap::new(),
+            initialized_static_vars: HashMap::new(),
             initialized_local_vars: HashMap::new(),
             initialized_functions: HashMap::new(),
+            imported_functions: HashSet::new(),
             initialized_function_vars: HashMap::new(),
+            unexported_functions: Vec::new(),
+            called_functions: HashSet::new(),
+            cur_func: None,
+            cur_func_returns_nothing: false,
+            cur_func_returns: None,
             scope: Scope {
                 in_func: false,
                 in_if: false,
                 in_loop: false,
             },
+            errors: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 
-    /// analize a tree to see if works
+    /// analize a tree to see if works. errors go in `self.errors` and the analysis keeps going after them
     pub fn analyze(
         self: &mut Self,
         tree: &mut Vec<ast::AstNode>,
-    ) -> Result<HashMap<String, (u32, bool, u8)>, AnalysisError> {
+    ) -> HashMap<String, (u32, bool, u8)> {
         let mut new_locals: HashMap<String, (u32, bool, u8)> = HashMap::new(); // the third thing is for the ordering of the variables in this map
         let mut order: u8 = 0;
+        // everything after a statement that never gets to the next one can't run
+        let unreachable_from = tree
+            .iter()
+            .position(never_falls_through)
+            .map(|i| i + 1)
+            .filter(|i| *i < tree.len());
         for node in tree.iter_mut() {
             match node {
                 ast::AstNode::SetOrChange {
                     sete,
                     setor,
                     type_of,
+                    span,
                 } => {
+                    let span = *span;
                     if *type_of == ast::TypeOfSetOrChange::SetIden {
+                        // the var still gets declared after an error so its uses don't error too
                         if self.scope.in_loop {
-                            return Err(AnalysisError::SetInLoop);
+                            self.errors.push(AnalysisError::SetInLoop(span));
                         }
                         if !self.scope.in_func {
-                            if !self.initialized_static_vars.contains(sete)
+                            if !self.initialized_static_vars.contains_key(sete)
                                 && !self.initialized_local_vars.contains_key(sete)
                             {
-                                let is_array = self.check_val(setor)?;
+                                let ty = self.check_set_val(setor);
+                                let var = Var::new(ty, span, setor);
                                 match self.scope {
                                     Scope {
                                         in_loop: false,
                                         in_func: false,
                                         in_if: false,
                                     } => {
-                                        self.initialized_static_vars.insert(sete.to_owned());
+                                        self.initialized_static_vars.insert(sete.to_owned(), var);
                                     }
-                                    Scope {
-                                        in_if: true,
-                                        in_func: false,
-                                        ..
-                                    } => {
+                                    Scope { in_func: false, .. } => {
                                         let var_mem_space: u32;
-                                        if let Some(n) = is_array {
-                                            var_mem_space = n + 2; // plus two because arrays are actually slices: first element is their ptr, second is len.
+                                        let is_array_bool: bool;
+                                        if let Val::Array(items) = setor {
+                                            var_mem_space = items.len() as u32 + 2; // plus two because arrays are actually slices: first element is their ptr, second is len.
+                                            is_array_bool = true;
                                         } else {
                                             var_mem_space = 1;
+                                            is_array_bool = false;
                                         }
-                                        let is_array_bool = match is_array {
-                                            Some(_) => true,
-                                            None => false,
-                                        };
                                         self.initialized_local_vars.insert(
                                             sete.to_owned(),
-                                            (var_mem_space, is_array_bool),
+                                            (var_mem_space, is_array_bool, var),
                                         );
                                         new_locals.insert(
                                             sete.to_owned(),
@@ -145,64 +335,89 @@ impl Analyser {
                                         );
                                         order += 1;
                                     }
-                                    Scope { in_loop: true, .. } => {
-                                        return Err(AnalysisError::SetInLoop)
-                                    }
                                     Scope { in_func: true, .. } => unreachable!(),
                                 }
                             } else {
-                                return Err(AnalysisError::DoubleSet(sete.to_owned()));
+                                self.errors
+                                    .push(AnalysisError::DoubleSet(sete.to_owned(), span));
                             }
                         } else {
                             ////////// WE must be in function scope
-                            if self.scope.in_loop {
-                                return Err(AnalysisError::SetInLoop);
-                            }
                             if !self.initialized_function_vars.contains_key(sete) {
+                                let ty = self.check_set_val(setor);
                                 let is_array: bool;
                                 let mut mem_len = 1;
                                 match setor {
-                                    Val::Expr(_) => {
-                                        is_array = false;
-                                        self.initialized_function_vars
-                                            .insert(sete.clone(), Type::Number);
-                                    }
+                                    Val::Expr(_) => is_array = false,
                                     Val::Array(n) => {
                                         mem_len = n.len() as u32 + 2;
                                         is_array = true;
-                                        self.initialized_function_vars
-                                            .insert(sete.clone(), Type::Number);
                                     }
                                 }
+                                self.initialized_function_vars
+                                    .insert(sete.clone(), Var::new(ty, span, setor));
                                 new_locals.insert(sete.to_owned(), (mem_len, is_array, order));
                                 order += 1;
                             } else {
-                                return Err(AnalysisError::DoubleSet(sete.clone()));
+                                self.errors
+                                    .push(AnalysisError::DoubleSet(sete.clone(), span));
                             }
                         }
                     } else {
-                        self.make_sure_var_exists(sete)?;
-                        if let TypeOfSetOrChange::ChangeArrIndex(e) = type_of {
-                            self.check_expr(e)?;
+                        let var_ty = self.var_type(sete, span);
+                        // what the thing being changed is. `x`, `@x` or `x[n]`
+                        let sete_ty = match type_of {
+                            TypeOfSetOrChange::ChangeArrIndex(e) => {
+                                if !var_ty.is_array() {
+                                    self.errors.push(AnalysisError::NotAnArray(
+                                        format!("{}[{}]", sete, e),
+                                        var_ty,
+                                        span,
+                                    ));
+                                }
+                                self.check_expr_is(e, Type::Number);
+                                Type::Number
+                            }
+                            // the pointer itself is read to know where to write
+                            TypeOfSetOrChange::ChangePtrDeref => {
+                                self.mark_read(sete);
+                                if !var_ty.is_array() {
+                                    self.errors.push(AnalysisError::NotAPointer(
+                                        format!("@{}", sete),
+                                        var_ty,
+                                        span,
+                                    ));
+                                }
+                                Type::Number
+                            }
+                            _ => var_ty,
+                        };
+                        if is_call(setor) {
+                            if let Some(var) = self.var_mut(sete) {
+                                var.binds_call = true;
+                            }
                         }
                         match setor {
                             Val::Array(_) => {
-                                return Err(AnalysisError::CannotChangeSomethingToArray(
-                                    sete.clone(),
-                                    type_of.clone(),
-                                ))
+                                self.errors
+                                    .push(AnalysisError::CannotChangeSomethingToArray(
+                                        sete.clone(),
+                                        type_of.clone(),
+                                        span,
+                                    ));
+                                self.check_val(setor);
                             }
-                            _ => {}
+                            Val::Expr(e) => self.check_expr_is(e, sete_ty),
                         }
-                        self.check_val(setor)?;
                     }
                 }
                 ast::AstNode::If {
                     guard,
                     body,
                     vars_declared,
+                    ..
                 } => {
-                    self.check_expr(guard)?;
+                    self.check_expr_is(guard, Type::Number);
                     let tmp_scope = self.scope;
                     match self.scope {
                         Scope {
@@ -215,7 +430,7 @@ impl Analyser {
                                 in_func: false,
                                 in_if: true,
                             };
-                            *vars_declared = Some(self.analyze(body)?);
+                            *vars_declared = Some(self.analyze(body));
                             // return scope to what it was after changing it
                             self.scope = tmp_scope;
                         }
@@ -229,32 +444,61 @@ impl Analyser {
                                 in_func: true,
                                 in_if: true,
                             };
-                            *vars_declared = Some(self.analyze(body)?);
+                            *vars_declared = Some(self.analyze(body));
                             self.scope = tmp_scope;
                         }
                     }
                 }
-                ast::AstNode::Loop { body } => {
+                ast::AstNode::Loop { body, .. } => {
                     let tmp_scope = self.scope;
                     self.scope = Scope {
                         in_loop: true,
                         in_if: false,
                         ..self.scope
                     };
-                    self.analyze(body)?;
+                    self.analyze(body);
                     self.scope = tmp_scope;
                 }
-                ast::AstNode::Extern { name, args } => {
-                    if let Some(_) = self.initialized_functions.insert(
-                        name.clone(),
-                        args.iter()
-                            .map(|x| convert_ast_type_to_analyse_type(x))
+                // these are replaced with the functions in the file by `modules::load`
+                ast::AstNode::Use { .. } => {}
+                ast::AstNode::Extern {
+                    module,
+                    name,
+                    args,
+                    variadic,
+                    returns_nothing,
+                    span,
+                } => {
+                    let signature = Signature {
+                        args: args
+                            .iter()
+                            .map(|x| convert_ast_type_to_analyse_type(x, *span))
                             .collect(),
-                    ) {
-                        return Err(AnalysisError::FuncAlreadyExists(name.clone()));
+                        variadic: *variadic,
+                        returns: if *returns_nothing {
+                            Type::Nothing
+                        } else {
+                            Type::Any
+                        },
+                        external: true,
+                    };
+                    if let Some(module) = module {
+                        let qualified = format!("{}'s {}", module, name);
+                        self.initialized_functions
+                            .insert(qualified, signature.clone());
+                        // `modules::load` makes sure two used files don't export the same name
+                        if !self.initialized_functions.contains_key(name) {
+                            self.imported_functions.insert(name.clone());
+                            self.initialized_functions.insert(name.clone(), signature);
+                        }
+                    } else if self
+                        .initialized_functions
+                        .insert(name.clone(), signature)
+                        .is_some()
+                    {
+                        self.errors
+                            .push(AnalysisError::FuncAlreadyExists(name.clone(), *span));
                     }
-                    self.initialized_external_functions
-                        .insert(name.clone(), args.len() as u32);
                 }
                 ast::AstNode::Func {
                     name,
@@ -262,32 +506,71 @@ impl Analyser {
                     body,
                     vars_declared,
                     export,
+                    returns_nothing,
+                    span,
                 } => {
                     /////////////// Making sure function name doesn't exist
-                    if let Some(_) = self.initialized_functions.insert(
-                        name.clone(),
-                        args.iter()
-                            .map(|x| convert_ast_type_to_analyse_type(x))
+                    let signature = Signature {
+                        args: args
+                            .iter()
+                            .map(|x| convert_ast_type_to_analyse_type(x, *span))
                             .collect(),
-                    ) {
-                        return Err(AnalysisError::FuncAlreadyExists(name.clone()));
+                        variadic: false,
+                        // it isn't known until the body is analysed, so recursive calls could return anything
+                        returns: if *returns_nothing {
+                            Type::Nothing
+                        } else {
+                            Type::Any
+                        },
+                        external: *export,
+                    };
+                    // a function that isn't exported can have the name of a used one, which is still there as `module's Name`
+                    let hides_import = !*export && self.imported_functions.remove(name);
+                    if self
+                        .initialized_functions
+                        .insert(name.clone(), signature)
+                        .is_some()
+                        && !hides_import
+                    {
+                        self.errors
+                            .push(AnalysisError::FuncAlreadyExists(name.clone(), *span));
                     }
-                    if *export {
-                        self.initialized_external_functions.insert(name.clone(), 0);
+                    if !*export {
+                        self.unexported_functions.push((name.clone(), *span));
+                    }
+                    if args.len() > MAX_ARGS {
+                        self.errors.push(AnalysisError::TooManyParams(
+                            name.clone(),
+                            args.len(),
+                            *span,
+                        ));
                     }
                     ////////////////////// Making sure there no duplicate args
                     let mut args_map = HashSet::new();
                     for n in args.clone() {
                         if !args_map.insert(n.clone()) {
-                            return Err(AnalysisError::SameArgForFunction(n.to_owned()));
+                            self.errors
+                                .push(AnalysisError::SameArgForFunction(n.to_owned(), *span));
+                            continue;
                         }
+                        let param = |ty| Var {
+                            ty,
+                            span: *span,
+                            read: false,
+                            binds_call: false,
+                            is_param: true,
+                        };
                         match n {
+                            // the type of an argument isn't said so it could be anything
                             ast::Type::Num(name) => {
-                                self.initialized_function_vars.insert(name, Type::Number);
+                                self.initialized_function_vars
+                                    .insert(name, param(Type::Any));
                             }
                             ast::Type::ArrNum(name, num) => {
-                                self.initialized_function_vars
-                                    .insert(name, Type::Arr(check_num(&num).unwrap()));
+                                self.initialized_function_vars.insert(
+                                    name,
+                                    param(Type::Arr(check_num(&num, *span).unwrap())),
+                                );
                             }
                         }
                     }
@@ -300,7 +583,27 @@ impl Analyser {
                     };
                     /////////////////// Clean up:
                     // we now remove all of the arguments from the variables declared to help out in codegen
-                    let mut tmp_res = self.analyze(body)?;
+                    self.cur_func = Some(name.clone());
+                    self.cur_func_returns_nothing = *returns_nothing;
+                    self.cur_func_returns = None;
+                    let mut tmp_res = self.analyze(body);
+                    self.cur_func = None;
+                    let returns = match self.cur_func_returns.take() {
+                        _ if *returns_nothing => Type::Nothing,
+                        Some(ty) => ty,
+                        // it just gets to the end, which returns 0
+                        None => Type::Number,
+                    };
+                    if let Some(signature) = self.initialized_functions.get_mut(name) {
+                        signature.returns = returns;
+                    }
+                    if !*returns_nothing && !always_returns(body) {
+                        self.warnings.push(Warning {
+                            kind: WarningKind::MissingReturn,
+                            message: format!("the function `{}` can get to its end without returning a value, so it will return 0 there\nHint: add a `return` at the end, or say that it `returns nothing`: Ex `function F(x) returns nothing,`", name),
+                            span: *span,
+                        });
+                    }
                     tmp_res.retain(|x, _| {
                         !args.contains(&ast::Type::Num(x.clone())) && {
                             for i in args.clone() {
@@ -315,130 +618,369 @@ impl Analyser {
                     });
                     *vars_declared = Some(tmp_res);
                     self.scope = tmp_scope;
-                    // clear the function vars since we may wanna do another function
-                    self.initialized_function_vars.clear();
+                    // clear the function vars since we may wanna do another function. all that is left are the args
+                    for (arg, var) in std::mem::take(&mut self.initialized_function_vars) {
+                        self.warn_if_unused(&arg, var);
+                    }
                 }
-                ast::AstNode::Break => {
+                ast::AstNode::Call { func_call, .. } => {
+                    self.check_expr(func_call);
+                }
+                ast::AstNode::Break { span } => {
                     if let Scope { in_loop: true, .. } = self.scope {
                     } else {
-                        return Err(AnalysisError::BreakWithoutLoop);
+                        self.errors.push(AnalysisError::BreakWithoutLoop(*span));
                     }
                 }
-                ast::AstNode::Return { val } => {
-                    if self.scope.in_func {
-                        self.check_expr(val)?;
-                    } else {
-                        return Err(AnalysisError::ReturnOutSideOfFunc);
+                ast::AstNode::Return { val, span } => {
+                    if !self.scope.in_func {
+                        self.errors.push(AnalysisError::ReturnOutSideOfFunc(*span));
+                        continue;
+                    }
+                    let name = self.cur_func.clone().unwrap_or_default();
+                    match val {
+                        Some(val) => {
+                            let ty = self.check_value_expr(val);
+                            self.cur_func_returns = match self.cur_func_returns {
+                                None => Some(ty),
+                                Some(before) => match unify(before, ty) {
+                                    Some(ty) => Some(ty),
+                                    None => {
+                                        self.errors.push(AnalysisError::MismatchedTypes(
+                                            val.to_string(),
+                                            before,
+                                            ty,
+                                            val.span(),
+                                        ));
+                                        Some(before)
+                                    }
+                                },
+                            };
+                            if self.cur_func_returns_nothing {
+                                self.errors
+                                    .push(AnalysisError::ReturnValueFromNothingFunc(name, *span));
+                            }
+                        }
+                        None if !self.cur_func_returns_nothing => self
+                            .errors
+                            .push(AnalysisError::ReturnWithoutValue(name, *span)),
+                        None => {}
                     }
                 }
             }
         }
+        // the dead code is still checked for errors above but it doesn't get compiled
+        if let Some(i) = unreachable_from {
+            self.warnings.push(Warning {
+                kind: WarningKind::UnreachableCode,
+                message: String::from("this code can never run, because it comes after a `return`, a `break` or a loop that never stops"),
+                span: tree[i].span().to(tree[tree.len() - 1].span()),
+            });
+            tree.truncate(i);
+        }
         // drop all the local vars.
         for (key, _) in new_locals.iter() {
-            if self.scope.in_func {
-                self.initialized_function_vars.remove(key);
+            let var = if self.scope.in_func {
+                self.initialized_function_vars.remove(key)
             } else {
-                self.initialized_local_vars.remove(key);
+                self.initialized_local_vars.remove(key).map(|v| v.2)
+            };
+            if let Some(var) = var {
+                self.warn_if_unused(key, var);
             }
         }
-        Ok(new_locals)
-    }
-    /// a helper function to make sure a variable exists
-    fn make_sure_var_exists(&self, var: &String) -> Result<(), AnalysisError> {
-        if !self.scope.in_func {
-            if !self.initialized_local_vars.contains_key(var)
-                && !self.initialized_static_vars.contains(var)
-            {
-                return Err(AnalysisError::VarNotExist(var.to_owned()));
+        new_locals
+    }
+    /// a variable in the scope we are in
+    fn var_mut(&mut self, var: &str) -> Option<&mut Var> {
+        if self.scope.in_func {
+            self.initialized_function_vars.get_mut(var)
+        } else if let Some(v) = self.initialized_local_vars.get_mut(var) {
+            Some(&mut v.2)
+        } else {
+            self.initialized_static_vars.get_mut(var)
+        }
+    }
+    /// remember that a variable was read
+    fn mark_read(&mut self, var: &str) {
+        if let Some(var) = self.var_mut(var) {
+            var.read = true;
+        }
+    }
+    /// warn about a variable that is going out of scope without ever being read. names starting with `_` are never warned about
+    fn warn_if_unused(&mut self, name: &str, var: Var) {
+        if var.read || var.binds_call || name.starts_with('_') {
+            return;
+        }
+        self.warnings.push(if var.is_param {
+            Warning {
+                kind: WarningKind::UnusedParameter,
+                message: format!("the parameter `{}` is never used", name),
+                span: var.span,
             }
         } else {
-            if !self.initialized_function_vars.contains_key(var) {
-                return Err(AnalysisError::VarNotExist(var.to_owned()));
+            Warning {
+                kind: WarningKind::UnusedVariable,
+                message: format!("the variable `{}` is set but never read", name),
+                span: var.span,
+            }
+        });
+    }
+    /// the type of a variable. it is an error if it doesn't exist, and then it could be anything
+    fn var_type(&mut self, var: &str, span: Span) -> Type {
+        match self.var_mut(var) {
+            Some(var) => var.ty,
+            None => {
+                self.errors
+                    .push(AnalysisError::VarNotExist(var.to_owned(), span));
+                Type::Any
             }
         }
-        Ok(())
     }
-    /// analyze an expression
-    fn check_expr(&self, expr: &mut Expr) -> Result<(), AnalysisError> {
+    /// analyze an expression and work out its type
+    fn check_expr(&mut self, expr: &mut Expr) -> Type {
         match expr {
-            Expr::Number(n) => {
-                check_num(n)?;
+            Expr::Number(n, span) => {
+                if let Err(e) = check_num(n, *span) {
+                    self.errors.push(e);
+                }
+                Type::Number
+            }
+            Expr::Iden(s, span) => {
+                let ty = self.var_type(s, *span);
+                self.mark_read(s);
+                ty
             }
-            Expr::Iden(s) => self.make_sure_var_exists(&s)?,
-            Expr::BinOp { lhs, rhs, .. } => {
-                self.check_expr(lhs)?;
-                self.check_expr(rhs)?;
+            Expr::BinOp { .. } => {
+                let whole = expr.to_string();
+                if let Expr::BinOp { lhs, rhs, .. } = expr {
+                    for side in [lhs, rhs].iter_mut() {
+                        let ty = self.check_value_expr(side);
+                        if let Type::Arr(_) = ty {
+                            self.errors.push(AnalysisError::ArrayInArithmetic(
+                                side.to_string(),
+                                whole.clone(),
+                                side.span(),
+                            ));
+                        }
+                    }
+                }
+                Type::Number
             }
             Expr::FuncCall {
+                module,
                 func_name,
                 args,
                 external,
+                span,
             } => {
-                self.check_funcall(func_name, args, external)?;
+                let returns = self.check_funcall(module, func_name, args, external, *span);
                 assert!(external.is_some());
+                returns
             }
-            Expr::AccessArray(a, e) => {
-                self.make_sure_var_exists(a)?;
-                self.check_expr(e)?;
+            Expr::AccessArray(a, e, span) => {
+                let ty = self.var_type(a, *span);
+                self.mark_read(a);
+                if !ty.is_array() {
+                    let whole = format!("{}[{}]", a, e);
+                    self.errors
+                        .push(AnalysisError::NotAnArray(whole, ty, *span));
+                }
+                self.check_expr_is(e, Type::Number);
+                Type::Number
             }
-            Expr::DerefPtr(p) => self.make_sure_var_exists(p)?,
-        }
-        Ok(())
-    }
-    /// analyse an immediate val. returns Some(n) if it is an array None if not
-    fn check_val(&self, val: &mut Val) -> Result<Option<u32>, AnalysisError> {
-        Ok(match val {
-            Val::Expr(a) => {
-                self.check_expr(a)?;
-                None
+            Expr::DerefPtr(p, span) => {
+                let ty = self.var_type(p, *span);
+                self.mark_read(p);
+                if !ty.is_array() {
+                    self.errors
+                        .push(AnalysisError::NotAPointer(format!("@{}", p), ty, *span));
+                }
+                Type::Any
             }
+        }
+    }
+    /// analyze an expression that is used as a value, so it can't be the result of a function that returns nothing
+    fn check_value_expr(&mut self, expr: &mut Expr) -> Type {
+        let ty = self.check_expr(expr);
+        if ty == Type::Nothing {
+            self.errors.push(AnalysisError::NothingUsedAsValue(
+                expr.to_string(),
+                expr.span(),
+            ));
+            return Type::Any;
+        }
+        ty
+    }
+    /// analyze an expression that has to be a number (`Type::Number`) or an array (`Type::Arr`). `Type::Any` takes anything
+    fn check_expr_is(&mut self, expr: &mut Expr, should: Type) {
+        let ty = self.check_value_expr(expr);
+        let ok = match should {
+            Type::Number => ty.is_number(),
+            Type::Arr(_) => ty.is_array(),
+            Type::Any | Type::Nothing => true,
+        };
+        if !ok {
+            self.errors.push(AnalysisError::MismatchedTypes(
+                expr.to_string(),
+                should,
+                ty,
+                expr.span(),
+            ));
+        }
+    }
+    /// analyse an immediate val and work out its type. the items of an array have to be numbers
+    fn check_val(&mut self, val: &mut Val) -> Type {
+        match val {
+            Val::Expr(a) => self.check_value_expr(a),
             Val::Array(items) => {
                 for item in items.iter_mut() {
-                    self.check_expr(item)?;
+                    self.check_expr_is(item, Type::Number);
                 }
-                Some(items.len() as u32)
+                Type::Arr(items.len() as i64)
             }
-        })
+        }
     }
-    /// check a function called
+    /// the type of the val that a variable is set to. after an error it could be anything
+    fn check_set_val(&mut self, val: &mut Val) -> Type {
+        let errors_before = self.errors.len();
+        let ty = self.check_val(val);
+        if self.errors.len() != errors_before {
+            Type::Any
+        } else {
+            ty
+        }
+    }
+    /// check a function call against the signature of the function, external or not. gives back what the call returns.
+    /// `module` is the one in `Math's Square(x)`
     fn check_funcall(
-        &self,
+        &mut self,
+        module: &Option<String>,
         func_name: &str,
         args: &mut Vec<ast::Val>,
         external: &mut Option<bool>,
-    ) -> Result<(), AnalysisError> {
-        let converted_args = args
+        span: Span,
+    ) -> Type {
+        let converted_args: Vec<Type> = args
             .iter()
             .map(|x| convert_ast_val_to_analyse_type(x))
             .collect();
-        if let Some(should_args) = self.initialized_functions.get(func_name) {
-            if &converted_args != should_args {
-                return Err(AnalysisError::FuncCalledWithWrongArgsType(
-                    func_name.to_string(),
-                    should_args.clone(),
-                    converted_args,
-                ));
-            }
-        } else {
-            return Err(AnalysisError::FuncCalledButNoExist(func_name.to_string()));
+        // functions from used files are under `module's Name`, with the module in lowercase
+        let (key, name) = match module {
+            Some(module) => (
+                format!("{}'s {}", module.to_lowercase(), func_name),
+                format!("{}'s {}", module, func_name),
+            ),
+            None => (func_name.to_string(), func_name.to_string()),
+        };
+        if module.is_none() && self.cur_func.as_deref() != Some(func_name) {
+            self.called_functions.insert(func_name.to_string());
         }
-        if let Some(_) = self.initialized_external_functions.get(func_name) {
-            *external = Some(true);
-        } else {
-            *external = Some(false);
+        // the args are all passed in registers, so there can only be as many as there are registers
+        if converted_args.len() > MAX_ARGS {
+            self.errors.push(AnalysisError::TooManyArgs(
+                name.clone(),
+                converted_args.len(),
+                span,
+            ));
         }
+        let returns = match self.initialized_functions.get(&key) {
+            Some(signature) if signature.variadic => {
+                let fixed = signature.args.len();
+                if converted_args.len() < fixed {
+                    self.errors
+                        .push(AnalysisError::VariadicFuncCalledWithTooFewArgs(
+                            name.clone(),
+                            fixed,
+                            converted_args.len(),
+                            span,
+                        ));
+                } else if converted_args[..fixed] != signature.args[..] {
+                    self.errors.push(AnalysisError::FuncCalledWithWrongArgsType(
+                        name.clone(),
+                        signature.args.clone(),
+                        converted_args,
+                        span,
+                    ));
+                }
+                *external = Some(signature.external);
+                signature.returns
+            }
+            Some(signature) => {
+                if converted_args != signature.args {
+                    self.errors.push(AnalysisError::FuncCalledWithWrongArgsType(
+                        name.clone(),
+                        signature.args.clone(),
+                        converted_args,
+                        span,
+                    ));
+                }
+                *external = Some(signature.external);
+                signature.returns
+            }
+            None => {
+                self.errors
+                    .push(AnalysisError::FuncCalledButNoExist(name.clone(), span));
+                *external = Some(false);
+                Type::Any
+            }
+        };
         for arg in args.iter_mut() {
-            self.check_val(arg)?;
+            self.check_val(arg);
         }
-        Ok(())
+        returns
+    }
+}
+
+/// the type that is both `a` and `b`, like for a function that returns in more than one place. `None` if there isn't one
+fn unify(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (Type::Any, _) | (_, Type::Any) => Some(Type::Any),
+        (Type::Number, Type::Number) => Some(Type::Number),
+        // the length of an array doesn't matter once it is passed around
+        (Type::Arr(n), Type::Arr(_)) => Some(Type::Arr(n)),
+        _ => None,
     }
 }
 
+/// does control never go from this statement on to the next one
+fn never_falls_through(node: &AstNode) -> bool {
+    match node {
+        AstNode::Return { .. } | AstNode::Break { .. } => true,
+        AstNode::Loop { body, .. } => !breaks_out(body),
+        _ => false,
+    }
+}
+
+/// does a block always return (or never finish, like a loop without a break) before getting to its end
+fn always_returns(body: &[AstNode]) -> bool {
+    body.iter().any(|node| match node {
+        AstNode::Return { .. } => true,
+        AstNode::Loop { body, .. } => !breaks_out(body),
+        // there are no elses, so the body might not run
+        _ => false,
+    })
+}
+
+/// does a loop body have a break that gets out of it. breaks in loops inside of it don't count
+fn breaks_out(body: &[AstNode]) -> bool {
+    body.iter().any(|node| match node {
+        AstNode::Break { .. } => true,
+        AstNode::If { body, .. } => breaks_out(body),
+        _ => false,
+    })
+}
+
+/// is a val just a function call
+fn is_call(val: &Val) -> bool {
+    matches!(val, Val::Expr(Expr::FuncCall { .. }))
+}
+
 /// check if a num literal is > 64 bit
-fn check_num(num: &String) -> Result<i64, AnalysisError> {
+fn check_num(num: &String, span: Span) -> Result<i64, AnalysisError> {
     match num.parse::<i64>() {
         Ok(x) => Ok(x),
-        Err(_) => Err(AnalysisError::NumberTooBig(num.to_owned())),
+        Err(_) => Err(AnalysisError::NumberTooBig(num.to_owned(), span)),
     }
 }
 
@@ -450,6 +992,7 @@ fn get_all_var_decls(tree: &Vec<AstNode>) -> Vec<(String, Type)> {
             sete,
             type_of: TypeOfSetOrChange::SetIden,
             setor,
+            ..
         } = node
         {
             vars.push((sete.to_owned(), convert_ast_val_to_analyse_type(setor)));
@@ -457,10 +1000,10 @@ fn get_all_var_decls(tree: &Vec<AstNode>) -> Vec<(String, Type)> {
     }
     vars
 }
-fn convert_ast_type_to_analyse_type(x: &ast::Type) -> Type {
+fn convert_ast_type_to_analyse_type(x: &ast::Type, span: Span) -> Type {
     match x {
         ast::Type::Num(_) => Type::Number,
-        ast::Type::ArrNum(_, len) => Type::Arr(check_num(len).unwrap()),
+        ast::Type::ArrNum(_, len) => Type::Arr(check_num(len, span).unwrap()),
     }
 }
 fn convert_ast_val_to_analyse_type(x: &ast::Val) -> Type {
@@ -557,6 +1100,330 @@ mod tests {
         analyse::analize(&mut ast).unwrap();
     }
     #[test]
+    fn analyze_multiple_errors() {
+        use crate::analyse;
+        use crate::lexer;
+        use crate::parser;
+        let mut tokenizer = lexer::Tokenizer::new();
+        let input = "set x to a + b. set x to 1. loop, set y to 2. change y to 3.! break.
+        function F(n, n), return n + q.!";
+        let output = tokenizer.lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        let names: Vec<String> = errs.iter().map(|e| format!("{:?}", e)).collect();
+        assert_eq!(errs.len(), 7, "{:?}", names);
+        assert!(matches!(
+            errs[0],
+            analyse::AnalysisError::SameArgForFunction(..)
+        ));
+        assert!(matches!(errs[1], analyse::AnalysisError::VarNotExist(ref v, _) if v == "q"));
+        assert!(matches!(errs[2], analyse::AnalysisError::VarNotExist(ref v, _) if v == "a"));
+        assert!(matches!(errs[3], analyse::AnalysisError::VarNotExist(ref v, _) if v == "b"));
+        assert!(matches!(errs[4], analyse::AnalysisError::DoubleSet(..)));
+        assert!(matches!(errs[5], analyse::AnalysisError::SetInLoop(_)));
+        assert!(matches!(
+            errs[6],
+            analyse::AnalysisError::BreakWithoutLoop(_)
+        ));
+    }
+    #[test]
+    fn analyze_set_in_function() {
+        use crate::analyse;
+        use crate::lexer;
+        use crate::parser;
+        // the value of a set in a function is checked, like at the top level
+        let input =
+            "function F(), set x to y. return x.! function G(), set a to [1, z]. return a[1].!
+        set b to F() + G().";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert_eq!(errs.len(), 2, "{:?}", errs);
+        assert!(matches!(errs[0], analyse::AnalysisError::VarNotExist(ref v, _) if v == "y"));
+        assert!(matches!(errs[1], analyse::AnalysisError::VarNotExist(ref v, _) if v == "z"));
+    }
+    #[test]
+    fn analyze_unused_warnings() {
+        use crate::analyse;
+        use crate::errors::WarningKind;
+        use crate::lexer;
+        use crate::parser;
+        let mut tokenizer = lexer::Tokenizer::new();
+        let input = "external function PutChar(n).
+        function Helper(a, b, _c), set dead to 1. set arr to [1, 2]. change arr[0] to 3. return a.!
+        function Rec(n), return Rec(n).!
+        function Used(), return 0.!
+        export function Api(), return Used().!
+        set tmp to 0. change tmp to PutChar(65).
+        set x to 5. set _y to 1. set p to [0]. change @p to 3.
+        if x > 1, set z to 2. !";
+        let output = tokenizer.lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let mut warnings: Vec<(WarningKind, String)> = analyse::analize(&mut ast)
+            .unwrap()
+            .into_iter()
+            .map(|w| (w.kind, w.message))
+            .collect();
+        warnings.sort();
+        let expected: Vec<(WarningKind, String)> = vec![
+            (
+                WarningKind::UnusedVariable,
+                "the variable `arr` is set but never read",
+            ),
+            (
+                WarningKind::UnusedVariable,
+                "the variable `dead` is set but never read",
+            ),
+            (
+                WarningKind::UnusedVariable,
+                "the variable `z` is set but never read",
+            ),
+            (
+                WarningKind::UnusedParameter,
+                "the parameter `b` is never used",
+            ),
+            (
+                WarningKind::UnusedFunction,
+                "the function `Helper` is never called",
+            ),
+            (
+                WarningKind::UnusedFunction,
+                "the function `Rec` is never called",
+            ),
+        ]
+        .into_iter()
+        .map(|(k, m)| (k, m.to_string()))
+        .collect();
+        assert_eq!(warnings, expected);
+    }
+    #[test]
+    fn analyze_missing_return() {
+        use crate::analyse;
+        use crate::errors::WarningKind;
+        use crate::lexer;
+        use crate::parser;
+        let input = "function Ok(n), if n > 1, return 1. ! return 0.!
+        function Forever(n), loop, if n > 1, return n. ! loop, break. ! !!
+        function Nothing(n) returns nothing, if n > 1, return. ! !
+        function IfOnly(n), if n > 1, return 1. ! !
+        function Breaks(n), loop, if n > 1, break. ! return 1. ! !
+        set x to Ok(1) + Forever(2) + IfOnly(3) + Breaks(4). call Nothing(x).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let mut missing: Vec<String> = analyse::analize(&mut ast)
+            .unwrap()
+            .into_iter()
+            .filter(|w| w.kind == WarningKind::MissingReturn)
+            .map(|w| w.message.split('`').nth(1).unwrap().to_string())
+            .collect();
+        missing.sort();
+        assert_eq!(missing, vec!["Breaks", "IfOnly"]);
+
+        let input = "function A() returns nothing, return 1.! function B(), return.!
+        call A(). call B().";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert!(
+            matches!(&errs[..], [analyse::AnalysisError::ReturnValueFromNothingFunc(a, _), analyse::AnalysisError::ReturnWithoutValue(b, _)] if a == "A" && b == "B")
+        );
+    }
+    #[test]
+    fn analyze_types() {
+        use crate::analyse::{self, AnalysisError, Type};
+        use crate::lexer;
+        use crate::parser;
+        let input = "external function Get().
+        function MakeArr(), set r to [1, 2]. return r.!
+        function Mixed(n), set r to [1]. if n > 1, return r. ! return 0.!
+        function First(p), return p[1].!
+        set x to 5. set y to x[0].
+        set a to [1, 2]. set b to a + 1.
+        set m to @x.
+        set q to MakeArr() + First(a) + Mixed(1).
+        if a, set z to 1. !
+        change x to a.
+        set s to Get(). set c to s[1] + @s + y + b + m + q.";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        let errs: Vec<String> = errs
+            .into_iter()
+            .map(|e| match e {
+                AnalysisError::NotAnArray(expr, Type::Number, _) => format!("index {}", expr),
+                AnalysisError::NotAPointer(expr, Type::Number, _) => format!("deref {}", expr),
+                AnalysisError::ArrayInArithmetic(arr, expr, _) => {
+                    format!("arith {} in {}", arr, expr)
+                }
+                AnalysisError::MismatchedTypes(expr, should, is, _) => {
+                    format!("{} is {} not {}", expr, is, should)
+                }
+                e => format!("{:?}", e),
+            })
+            .collect();
+        assert_eq!(
+            errs,
+            vec![
+                "0 is a number not an array",
+                "index x[0]",
+                "arith a in (a + 1)",
+                "deref @x",
+                "arith MakeArr() in (MakeArr() + First(a))",
+                "arith Mixed(1) in ((MakeArr() + First(a)) + Mixed(1))",
+                "a is an array not a number",
+                "a is an array not a number",
+            ]
+        );
+    }
+    #[test]
+    fn analyze_unreachable_code() {
+        use crate::analyse;
+        use crate::ast::AstNode;
+        use crate::errors::WarningKind;
+        use crate::lexer;
+        use crate::parser;
+        let input = "external function PutChar(c).
+function F(n), return n. PutChar(1). PutChar(2).!
+set x to 0. loop, if x > 3, break. PutChar(3). ! change x to x + 1. !
+loop, PutChar(4). ! PutChar(5).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let spans: Vec<(u32, u32)> = analyse::analize(&mut ast)
+            .unwrap()
+            .into_iter()
+            .filter(|w| w.kind == WarningKind::UnreachableCode)
+            .map(|w| (w.span.start, w.span.end))
+            .collect();
+        assert_eq!(spans, vec![(55, 78), (115, 126), (170, 181)]);
+        // the dead statements are gone
+        match &ast.tree[..] {
+            [AstNode::Extern { .. }, AstNode::Func { body, .. }, AstNode::SetOrChange { .. }, AstNode::Loop {
+                body: loop_body, ..
+            }, AstNode::Loop { .. }] => {
+                assert_eq!(body.len(), 1);
+                match &loop_body[0] {
+                    AstNode::If { body, .. } => assert_eq!(body.len(), 1),
+                    n => panic!("{:?}", n),
+                }
+            }
+            t => panic!("{:?}", t),
+        }
+    }
+    #[test]
+    fn analyze_qualified_call() {
+        use crate::analyse::{self, AnalysisError};
+        use crate::lexer;
+        use crate::parser;
+        // only the functions of used files can be called with their module
+        let input = "external function F(). call Nope's F().";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert!(matches!(
+            &errs[..],
+            [AnalysisError::FuncCalledButNoExist(name, _)] if name == "Nope's F"
+        ));
+    }
+    #[test]
+    fn analyze_extern_arity() {
+        use crate::analyse::{self, AnalysisError, Type};
+        use crate::ast::{AstNode, Expr, Val};
+        use crate::lexer;
+        use crate::parser;
+        let input = "external function PutChar(n). external function Two(a, b).
+        PutChar(). PutChar(1, 2). call Two(1). set x to Two(1, 2) + PutChar(3).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        let calls: Vec<(&str, usize, usize)> = errs
+            .iter()
+            .map(|e| match e {
+                AnalysisError::FuncCalledWithWrongArgsType(name, should, had, _) => {
+                    assert!(should.iter().chain(had).all(|t| *t == Type::Number));
+                    (name.as_str(), should.len(), had.len())
+                }
+                e => panic!("{:?}", e),
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            vec![("PutChar", 1, 0), ("PutChar", 1, 2), ("Two", 2, 1)]
+        );
+
+        let input = "external function printf(fmt, ...).
+        call printf(1). call printf(1, 2, 3). call printf().";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert!(matches!(
+            &errs[..],
+            [AnalysisError::VariadicFuncCalledWithTooFewArgs(name, 1, 0, _)] if name == "printf"
+        ));
+
+        // the args all go in registers, so there can't be more than 6
+        let input = "external function printf(fmt, ...).
+        call printf(0, 1, 2, 3, 4, 5). call printf(0, 1, 2, 3, 4, 5, 6).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert!(matches!(
+            &errs[..],
+            [AnalysisError::TooManyArgs(name, 7, _)] if name == "printf"
+        ));
+        let input =
+            "function F(a, b, c, d, e, f, g), return a.! function G(a, b, c, d, e, f), return a.!";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        let errs = analyse::analize(&mut ast).unwrap_err();
+        assert!(matches!(
+            &errs[..],
+            [AnalysisError::TooManyParams(name, 7, _)] if name == "F"
+        ));
+
+        let input = "external function PutChar(n). export function Api(), return 0.!
+        function Mine(), return Api().! set x to PutChar(Mine()).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let mut external = Vec::new();
+        for node in ast.tree.iter() {
+            let mut exprs = Vec::new();
+            match node {
+                AstNode::Func { body, .. } => {
+                    if let AstNode::Return { val: Some(e), .. } = &body[0] {
+                        exprs.push(e)
+                    }
+                }
+                AstNode::SetOrChange {
+                    setor: Val::Expr(e),
+                    ..
+                } => {
+                    exprs.push(e);
+                    if let Expr::FuncCall { args, .. } = e {
+                        if let Val::Expr(e) = &args[0] {
+                            exprs.push(e)
+                        }
+                    }
+                }
+                _ => {}
+            }
+            for e in exprs {
+                if let Expr::FuncCall {
+                    func_name,
+                    external: Some(ext),
+                    ..
+                } = e
+                {
+                    external.push((func_name.as_str(), *ext));
+                }
+            }
+        }
+        assert_eq!(
+            external,
+            vec![("Api", true), ("PutChar", true), ("Mine", false)]
+        );
+    }
+    #[test]
     fn analyze_if_scope() {
         use crate::analyse;
         use crate::lexer;
@@ -0,0 +1,534 @@
+//! assembling the nasm that `codegen` makes into x86-64 machine code, so `jit` can run it without nasm or ld. it only
+//! knows the instructions, operands and directives that `codegen` (and the glue code in `jit`) use
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// the machine code of one file. the places that need the address of a symbol are filled in when it is linked
+#[derive(Debug, Default)]
+pub struct Object {
+    /// section .text
+    pub text: Vec<u8>,
+    /// how many bytes section .bss has. it starts out as zeroes
+    pub bss_len: usize,
+    /// the labels and the `resq`s
+    pub symbols: HashMap<String, Symbol>,
+    /// the symbols that the other objects can use
+    pub globals: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+/// where a symbol is in its object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Text(usize),
+    Bss(usize),
+}
+
+/// 4 bytes in the text that have to get the distance from the end of their instruction to `symbol + addend`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub at: usize,
+    /// where the instruction ends
+    pub end: usize,
+    pub symbol: String,
+    pub addend: i64,
+}
+
+/// a line that can't be assembled. the lines start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub text: String,
+    pub why: &'static str,
+}
+
+const REGISTERS: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+const BYTE_REGISTERS: [&str; 4] = ["al", "cl", "dl", "bl"];
+/// the register that `push symbol` uses, because push can't take a 64 bit address. codegen never uses it
+const SCRATCH: u8 = 11;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(u8),
+    ByteReg(u8),
+    Imm(i64),
+    /// the address of a symbol, like a label to jump to
+    Addr(String, i64),
+    Mem(Mem),
+}
+
+/// `[base + disp]` or `[symbol + disp]`
+#[derive(Debug, Clone, PartialEq)]
+struct Mem {
+    base: Option<u8>,
+    symbol: Option<String>,
+    disp: i64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Text,
+    Bss,
+}
+
+/// assemble the text of a file
+pub fn assemble(asm: &str) -> Result<Object, AsmError> {
+    let mut assembler = Assembler {
+        object: Object::default(),
+        section: Section::Text,
+    };
+    for (i, line) in asm.lines().enumerate() {
+        let text = line.split(';').next().unwrap_or("").trim();
+        assembler.line(text).map_err(|why| AsmError {
+            line: i + 1,
+            text: text.to_string(),
+            why,
+        })?;
+    }
+    Ok(assembler.object)
+}
+
+struct Assembler {
+    object: Object,
+    section: Section,
+}
+
+impl Assembler {
+    fn line(&mut self, text: &str) -> Result<(), &'static str> {
+        let (first, rest) = match text.find(char::is_whitespace) {
+            Some(i) => (&text[..i], text[i..].trim()),
+            None => (text, ""),
+        };
+        match first {
+            "" | "extern" => Ok(()),
+            "global" => {
+                self.object.globals.push(rest.to_string());
+                Ok(())
+            }
+            "section" => {
+                self.section = match rest {
+                    ".text" => Section::Text,
+                    ".bss" => Section::Bss,
+                    _ => return Err("there is only section .text and section .bss"),
+                };
+                Ok(())
+            }
+            // nasm lets local labels go without the colon
+            _ if rest.is_empty() && (first.ends_with(':') || first.starts_with('.')) => {
+                let name = first.trim_end_matches(':');
+                if !is_symbol(name) {
+                    return Err("this isn't a label");
+                }
+                self.define(name, Symbol::Text(self.object.text.len()))
+            }
+            _ if self.section == Section::Bss => {
+                let words = match rest.strip_prefix("resq") {
+                    Some(n) => n
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| "resq needs a number")?,
+                    None => return Err("section .bss can only have resq"),
+                };
+                self.define(first, Symbol::Bss(self.object.bss_len))?;
+                self.object.bss_len += words * 8;
+                Ok(())
+            }
+            mnemonic => {
+                let operands = if rest.is_empty() {
+                    Vec::new()
+                } else {
+                    rest.split(',').map(operand).collect::<Result<_, _>>()?
+                };
+                self.instruction(mnemonic, &operands)
+            }
+        }
+    }
+    fn define(&mut self, name: &str, symbol: Symbol) -> Result<(), &'static str> {
+        match self.object.symbols.insert(name.to_string(), symbol) {
+            Some(_) => Err("this symbol is already defined"),
+            None => Ok(()),
+        }
+    }
+    fn instruction(&mut self, mnemonic: &str, operands: &[Operand]) -> Result<(), &'static str> {
+        use Operand::*;
+        match (mnemonic, operands) {
+            ("push", [Reg(r)]) => {
+                self.rex(false, 0, *r);
+                self.bytes(&[0x50 + (r & 7)]);
+            }
+            ("push", [Imm(n)]) if fits_i8(*n) => self.bytes(&[0x6a, *n as u8]),
+            ("push", [Imm(n)]) if fits_i32(*n) => {
+                self.bytes(&[0x68]);
+                self.bytes(&(*n as i32).to_le_bytes());
+            }
+            ("push", [Mem(m)]) => self.modrm(false, &[0xff], 6, &Mem(m.clone()), &[])?,
+            ("push", [Addr(symbol, addend)]) => {
+                self.lea(SCRATCH, symbol, *addend)?;
+                self.instruction("push", &[Reg(SCRATCH)])?;
+            }
+            ("pop", [Reg(r)]) => {
+                self.rex(false, 0, *r);
+                self.bytes(&[0x58 + (r & 7)]);
+            }
+            ("mov", [Reg(d), Reg(s)]) => self.modrm(true, &[0x89], *s, &Reg(*d), &[])?,
+            ("mov", [Reg(d), Mem(m)]) => self.modrm(true, &[0x8b], *d, &Mem(m.clone()), &[])?,
+            ("mov", [dest @ Mem(_), Reg(s)]) => self.modrm(true, &[0x89], *s, dest, &[])?,
+            ("mov", [dest @ (Reg(_) | Mem(_)), Imm(n)]) if fits_i32(*n) => {
+                self.modrm(true, &[0xc7], 0, dest, &(*n as i32).to_le_bytes())?
+            }
+            ("mov", [Reg(d), Imm(n)]) => {
+                self.rex(true, 0, *d);
+                self.bytes(&[0xb8 + (d & 7)]);
+                self.bytes(&n.to_le_bytes());
+            }
+            ("mov", [Reg(d), Addr(symbol, addend)]) => self.lea(*d, symbol, *addend)?,
+            ("mov", [ByteReg(d), Imm(n)]) if fits_u8(*n) => self.bytes(&[0xb0 + d, *n as u8]),
+            ("lea", [Reg(d), Mem(m)]) => self.modrm(true, &[0x8d], *d, &Mem(m.clone()), &[])?,
+            ("imul", [Reg(d), src @ (Reg(_) | Mem(_))]) => {
+                self.modrm(true, &[0x0f, 0xaf], *d, src, &[])?
+            }
+            ("imul", [Reg(d), Imm(n)]) if fits_i8(*n) => {
+                self.modrm(true, &[0x6b], *d, &Reg(*d), &[*n as u8])?
+            }
+            ("imul", [Reg(d), Imm(n)]) if fits_i32(*n) => {
+                self.modrm(true, &[0x69], *d, &Reg(*d), &(*n as i32).to_le_bytes())?
+            }
+            (op, [dest, src]) if arithmetic(op).is_some() => {
+                let (opcode, ext) = arithmetic(op).unwrap();
+                match (dest, src) {
+                    (Reg(_) | Mem(_), Reg(s)) => self.modrm(true, &[opcode + 1], *s, dest, &[])?,
+                    (Reg(d), Mem(_)) => self.modrm(true, &[opcode + 3], *d, src, &[])?,
+                    (Reg(_) | Mem(_), Imm(n)) if fits_i8(*n) => {
+                        self.modrm(true, &[0x83], ext, dest, &[*n as u8])?
+                    }
+                    (Reg(_) | Mem(_), Imm(n)) if fits_i32(*n) => {
+                        self.modrm(true, &[0x81], ext, dest, &(*n as i32).to_le_bytes())?
+                    }
+                    _ => return Err("these operands can't be used with this instruction"),
+                }
+            }
+            ("call", [Reg(r)]) => self.modrm(false, &[0xff], 2, &Reg(*r), &[])?,
+            ("call", [Addr(symbol, addend)]) => self.relative(&[0xe8], symbol, *addend),
+            ("jmp", [Addr(symbol, addend)]) => self.relative(&[0xe9], symbol, *addend),
+            (jump, [Addr(symbol, addend)]) if condition(jump).is_some() => {
+                self.relative(&[0x0f, 0x80 + condition(jump).unwrap()], symbol, *addend)
+            }
+            ("ret", []) => self.bytes(&[0xc3]),
+            ("syscall", []) => self.bytes(&[0x0f, 0x05]),
+            _ => {
+                return Err("this instruction isn't known, or it can't be used with these operands")
+            }
+        }
+        Ok(())
+    }
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.object.text.extend_from_slice(bytes);
+    }
+    /// the rex prefix, if it is needed. `wide` is for 64 bit operands, `reg` is the register in the middle of the ModRM
+    /// and `base` is the one in the bottom of it (or in the opcode)
+    fn rex(&mut self, wide: bool, reg: u8, base: u8) {
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3) << 2 | base >> 3;
+        if rex != 0x40 {
+            self.bytes(&[rex]);
+        }
+    }
+    /// an instruction with a ModRM byte. `reg` is the register or the opcode extension, and `rm` is a register or memory.
+    /// `imm` goes after the memory operand
+    fn modrm(
+        &mut self,
+        wide: bool,
+        opcode: &[u8],
+        reg: u8,
+        rm: &Operand,
+        imm: &[u8],
+    ) -> Result<(), &'static str> {
+        if self.section != Section::Text {
+            return Err("instructions have to be in section .text");
+        }
+        let mem = match rm {
+            Operand::Reg(r) => {
+                self.rex(wide, reg, *r);
+                self.bytes(opcode);
+                self.bytes(&[0xc0 | (reg & 7) << 3 | (r & 7)]);
+                self.bytes(imm);
+                return Ok(());
+            }
+            Operand::Mem(mem) => mem,
+            _ => return Err("this operand has to be a register or memory"),
+        };
+        self.rex(wide, reg, mem.base.unwrap_or(0));
+        self.bytes(opcode);
+        let reg = (reg & 7) << 3;
+        match (mem.base, &mem.symbol) {
+            (Some(_), Some(_)) => return Err("memory can't be from a register and a symbol"),
+            // it is from the next instruction, so it works wherever the code is put
+            (None, Some(symbol)) => {
+                self.bytes(&[reg | 0b101]);
+                let at = self.object.text.len();
+                self.bytes(&[0; 4]);
+                self.bytes(imm);
+                self.object.relocations.push(Relocation {
+                    at,
+                    end: self.object.text.len(),
+                    symbol: symbol.clone(),
+                    addend: mem.disp,
+                });
+                return Ok(());
+            }
+            (None, None) => {
+                let disp = i32::try_from(mem.disp).map_err(|_| "the address is too big")?;
+                self.bytes(&[reg | 0b100, 0x25]);
+                self.bytes(&disp.to_le_bytes());
+            }
+            (Some(base), None) => {
+                let base = base & 7;
+                // rbp and r13 always need a displacement, and rsp and r12 need a SIB byte
+                let mode = if mem.disp == 0 && base != 5 {
+                    0
+                } else if fits_i8(mem.disp) {
+                    1
+                } else {
+                    2
+                };
+                self.bytes(&[mode << 6 | reg | base]);
+                if base == 4 {
+                    self.bytes(&[0x24]);
+                }
+                match mode {
+                    0 => {}
+                    1 => self.bytes(&[mem.disp as u8]),
+                    _ => {
+                        let disp =
+                            i32::try_from(mem.disp).map_err(|_| "the displacement is too big")?;
+                        self.bytes(&disp.to_le_bytes());
+                    }
+                }
+            }
+        }
+        self.bytes(imm);
+        Ok(())
+    }
+    /// `lea reg, [symbol + addend]`
+    fn lea(&mut self, reg: u8, symbol: &str, addend: i64) -> Result<(), &'static str> {
+        let mem = Mem {
+            base: None,
+            symbol: Some(symbol.to_string()),
+            disp: addend,
+        };
+        self.modrm(true, &[0x8d], reg, &Operand::Mem(mem), &[])
+    }
+    /// a jump or a call to a symbol
+    fn relative(&mut self, opcode: &[u8], symbol: &str, addend: i64) {
+        self.bytes(opcode);
+        let at = self.object.text.len();
+        self.bytes(&[0; 4]);
+        self.object.relocations.push(Relocation {
+            at,
+            end: at + 4,
+            symbol: symbol.to_string(),
+            addend,
+        });
+    }
+}
+
+/// the opcode of `op r/m8, r8` and the ModRM extension of `op r/m, imm`
+fn arithmetic(op: &str) -> Option<(u8, u8)> {
+    Some(match op {
+        "add" => (0x00, 0),
+        "or" => (0x08, 1),
+        "and" => (0x20, 4),
+        "sub" => (0x28, 5),
+        "xor" => (0x30, 6),
+        "cmp" => (0x38, 7),
+        _ => return None,
+    })
+}
+
+/// the condition code of a conditional jump
+fn condition(jump: &str) -> Option<u8> {
+    Some(match jump {
+        "je" => 0x4,
+        "jne" => 0x5,
+        "jl" => 0xc,
+        "jge" => 0xd,
+        "jle" => 0xe,
+        "jg" => 0xf,
+        _ => return None,
+    })
+}
+
+fn fits_i8(n: i64) -> bool {
+    i8::try_from(n).is_ok()
+}
+
+fn fits_u8(n: i64) -> bool {
+    u8::try_from(n).is_ok()
+}
+
+fn fits_i32(n: i64) -> bool {
+    i32::try_from(n).is_ok()
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// an operand, like `r8`, `5`, `qword [rsp + 2 * 8]` or `MaNgLe_x`
+fn operand(text: &str) -> Result<Operand, &'static str> {
+    let text = text.trim();
+    // the size is always a qword, so it doesn't change anything
+    let text = text.strip_prefix("qword ").unwrap_or(text).trim();
+    if let Some(r) = BYTE_REGISTERS.iter().position(|&r| r == text) {
+        return Ok(Operand::ByteReg(r as u8));
+    }
+    let (inside, is_mem) = match text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        Some(inside) => (inside, true),
+        None => (text, false),
+    };
+    let mut base = None;
+    let mut symbol = None;
+    let mut disp: i64 = 0;
+    for term in inside.split('+').map(str::trim) {
+        if let Some(r) = REGISTERS.iter().position(|&r| r == term) {
+            if base.replace(r as u8).is_some() {
+                return Err("there can only be one register in an address");
+            }
+        } else if term.contains('*') {
+            let mut product: i64 = 1;
+            for factor in term.split('*') {
+                let factor = factor
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| "only numbers can be multiplied")?;
+                product = product.checked_mul(factor).ok_or("the number is too big")?;
+            }
+            disp = disp.checked_add(product).ok_or("the number is too big")?;
+        } else if let Ok(n) = term.parse::<i64>() {
+            disp = disp.checked_add(n).ok_or("the number is too big")?;
+        } else if is_symbol(term) {
+            if symbol.replace(term.to_string()).is_some() {
+                return Err("there can only be one symbol in an operand");
+            }
+        } else {
+            return Err("this operand isn't known");
+        }
+    }
+    Ok(match (is_mem, base, symbol) {
+        (true, base, symbol) => Operand::Mem(Mem { base, symbol, disp }),
+        (false, Some(r), None) if disp == 0 => Operand::Reg(r),
+        (false, None, Some(symbol)) => Operand::Addr(symbol, disp),
+        (false, None, None) => Operand::Imm(disp),
+        _ => return Err("a register can only be added to in an address"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(asm: &str) -> Vec<u8> {
+        assemble(asm).unwrap().text
+    }
+
+    #[test]
+    fn assemble_instructions() {
+        // checked against nasm
+        assert_eq!(
+            text("push r8\npop rdi\npush rbp\npush 5\npush 1000"),
+            [0x41, 0x50, 0x5f, 0x55, 0x6a, 0x05, 0x68, 0xe8, 0x03, 0x00, 0x00]
+        );
+        assert_eq!(text("push qword [rsp + 2 * 8]"), [0xff, 0x74, 0x24, 0x10]);
+        assert_eq!(
+            text("mov rbp, rsp\nmov r8, rax"),
+            [0x48, 0x89, 0xe5, 0x49, 0x89, 0xc0]
+        );
+        assert_eq!(
+            text("mov r8, 5"),
+            [0x49, 0xc7, 0xc0, 0x05, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            text("mov r8, 9999999999"),
+            [0x49, 0xb8, 0xff, 0xe3, 0x0b, 0x54, 0x02, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            text("mov r8, [r8]\nmov qword [r9], r8"),
+            [0x4d, 0x8b, 0x00, 0x4d, 0x89, 0x01]
+        );
+        assert_eq!(
+            text("mov [rsp + 3 * 8 ], r8"),
+            [0x4c, 0x89, 0x44, 0x24, 0x18]
+        );
+        assert_eq!(
+            text("mov r8, qword [rsp + 20 * 8]"),
+            [0x4c, 0x8b, 0x84, 0x24, 0xa0, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            text("sub rsp, 2 * 8\nadd rsp, 0 * 8\nand rsp, -16"),
+            [0x48, 0x83, 0xec, 0x10, 0x48, 0x83, 0xc4, 0x00, 0x48, 0x83, 0xe4, 0xf0]
+        );
+        assert_eq!(
+            text("add r9, r8\nimul r9, r8\ncmp r9, r8\nimul r8, 8"),
+            [0x4d, 0x01, 0xc1, 0x4d, 0x0f, 0xaf, 0xc8, 0x4d, 0x39, 0xc1, 0x4d, 0x6b, 0xc0, 0x08]
+        );
+        assert_eq!(
+            text("xor rdi, rdi\nmov al, 0\ncall rax\nret\nsyscall"),
+            [0x48, 0x31, 0xff, 0xb0, 0x00, 0xff, 0xd0, 0xc3, 0x0f, 0x05]
+        );
+    }
+
+    #[test]
+    fn assemble_symbols() {
+        let object = assemble(
+            "global F
+section .text
+F:
+jmp .END
+push MaNgLe_x
+mov qword [MaNgLe_x + 1 * 8], 3
+.END
+section .bss
+MaNgLe_y resq 1
+MaNgLe_x resq 4",
+        )
+        .unwrap();
+        assert_eq!(object.globals, ["F"]);
+        assert_eq!(object.symbols["F"], Symbol::Text(0));
+        assert_eq!(object.symbols[".END"], Symbol::Text(object.text.len()));
+        assert_eq!(object.symbols["MaNgLe_x"], Symbol::Bss(8));
+        assert_eq!(object.bss_len, 40);
+        let relocation = |at, end, symbol: &str, addend| Relocation {
+            at,
+            end,
+            symbol: symbol.to_string(),
+            addend,
+        };
+        // push takes a 32 bit number, so the address goes through r11
+        assert_eq!(
+            object.relocations,
+            [
+                relocation(1, 5, ".END", 0),
+                relocation(8, 12, "MaNgLe_x", 0),
+                relocation(17, 25, "MaNgLe_x", 8),
+            ]
+        );
+        assert_eq!(
+            &object.text[5..14],
+            [0x4c, 0x8d, 0x1d, 0, 0, 0, 0, 0x41, 0x53]
+        );
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let error = assemble("mov r8, 1\nmovq r8, 1").unwrap_err();
+        assert_eq!((error.line, error.text.as_str()), (2, "movq r8, 1"));
+        assert!(assemble("push 9999999999").is_err());
+        assert!(assemble("mov r8, [rsp + MaNgLe_x]").is_err());
+        assert!(assemble("F:\nF:").is_err());
+    }
+}
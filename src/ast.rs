@@ -1,31 +1,49 @@
 //! the module where the abstract syntax tree is defined. we dont need tests in this because very litle code
 
-use crate::lexer::Token;
+use crate::lexer::{Span, Token};
 use std::collections::HashMap;
 
 /// an expression
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     /// a number
-    Number(String),
+    Number(String, Span),
     /// an iden
-    Iden(String),
+    Iden(String, Span),
     /// a binop
     BinOp {
         lhs: Box<Expr>,
         op: BinOp,
         rhs: Box<Expr>,
+        span: Span,
     },
     /// a function call
     FuncCall {
+        /// the module in `Math's Square(x)`, as it was written
+        module: Option<String>,
         func_name: String,
         args: Vec<Val>,
         external: Option<bool>,
+        span: Span,
     },
     /// @iden
-    DerefPtr(String),
+    DerefPtr(String, Span),
     /// iden[n]
-    AccessArray(String, Box<Expr>),
+    AccessArray(String, Box<Expr>, Span),
+}
+
+impl Expr {
+    /// where the expression is in the source
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Number(_, span)
+            | Expr::Iden(_, span)
+            | Expr::BinOp { span, .. }
+            | Expr::FuncCall { span, .. }
+            | Expr::DerefPtr(_, span)
+            | Expr::AccessArray(_, _, span) => *span,
+        }
+    }
 }
 
 pub fn convert_tok_to_ast_binop(tok: Token) -> BinOp {
@@ -76,6 +94,7 @@ pub enum AstNode {
         sete: String,
         setor: Val,
         type_of: TypeOfSetOrChange,
+        span: Span,
     },
     /// an if statement
     If {
@@ -83,9 +102,11 @@ pub enum AstNode {
         body: Vec<AstNode>,
         /// for the variables declared inside the if statement
         vars_declared: Option<HashMap<String, (u32, bool, u8)>>,
+        span: Span,
     },
     Loop {
         body: Vec<AstNode>,
+        span: Span,
     },
     Func {
         name: String,
@@ -96,16 +117,57 @@ pub enum AstNode {
         vars_declared: Option<HashMap<String, (u32, bool, u8)>>,
         /// export it?
         export: bool,
+        /// declared with `returns nothing`
+        returns_nothing: bool,
+        span: Span,
     },
+    /// `return.` has no val
     Return {
-        val: Expr,
+        val: Option<Expr>,
+        span: Span,
+    },
+    Break {
+        span: Span,
     },
-    Break,
     Extern {
+        /// the module the function is from, for the exported functions of used files. lowercase
+        module: Option<String>,
         name: String,
         /// the arguments used in the function
         args: Vec<Type>,
+        /// declared with `...` after the args, so it takes any number of extra ones. like printf
+        variadic: bool,
+        /// declared with `returns nothing`
+        returns_nothing: bool,
+        span: Span,
+    },
+    /// `use "lib.ez".` load another file so its exported functions can be called. the driver replaces these with `Extern`s for the functions
+    Use {
+        path: String,
+        span: Span,
     },
+    /// call a function and throw away what it returns. `func_call` is always an `Expr::FuncCall`
+    Call {
+        func_call: Expr,
+        span: Span,
+    },
+}
+
+impl AstNode {
+    /// where the statement is in the source. for blocks this is the whole block
+    pub fn span(&self) -> Span {
+        match self {
+            AstNode::SetOrChange { span, .. }
+            | AstNode::If { span, .. }
+            | AstNode::Loop { span, .. }
+            | AstNode::Func { span, .. }
+            | AstNode::Return { span, .. }
+            | AstNode::Break { span }
+            | AstNode::Extern { span, .. }
+            | AstNode::Use { span, .. }
+            | AstNode::Call { span, .. } => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
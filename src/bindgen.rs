@@ -0,0 +1,491 @@
+//! `ezc bindgen`: reading a simple C header and making ez `external function` declarations for the functions in it
+
+use crate::codegen::MAX_ARGS;
+use crate::lexer::{get_kword, Token};
+use std::fmt::Write;
+
+/// something in a header that doesn't fit the way ez calls functions
+#[derive(Debug, PartialEq)]
+pub struct BindgenWarning {
+    /// the line of the header that the declaration starts on
+    pub line: usize,
+    pub message: String,
+}
+
+/// what a C type is to ez
+#[derive(Debug, PartialEq)]
+enum CType {
+    /// fits in a register. `narrow_signed` is for signed types smaller than 64 bits, which don't come back as negative ez numbers
+    Int {
+        narrow_signed: bool,
+    },
+    Pointer,
+    Void,
+    /// float and double. they go in the xmm registers, which ez doesn't use
+    Float,
+    /// a struct or union passed by value
+    Aggregate,
+    /// a name we don't know, like a typedef from another header. it is assumed to be an integer
+    Unknown(String),
+}
+
+/// a function declared in the header
+#[derive(Debug)]
+struct CFunc {
+    name: String,
+    ret: CType,
+    /// the text of the return type, for the warnings
+    ret_text: String,
+    params: Vec<(Option<String>, CType)>,
+    variadic: bool,
+}
+
+/// make ez declarations for the functions declared in `header`. things that don't fit ez are warned about
+pub fn bindgen(header: &str) -> (String, Vec<BindgenWarning>) {
+    let mut out = String::new();
+    let mut warnings = Vec::new();
+    for (decl, line) in declarations(header) {
+        let func = match parse_decl(&tokens(&decl)) {
+            None => continue,
+            Some(Ok(func)) => func,
+            Some(Err(message)) => {
+                warnings.push(BindgenWarning { line, message });
+                continue;
+            }
+        };
+        let mut warn = |message: String| warnings.push(BindgenWarning { line, message });
+        let name = &func.name;
+        let all_types = func.params.iter().map(|p| &p.1).chain(Some(&func.ret));
+        if func.ret == CType::Float || func.params.iter().any(|p| p.1 == CType::Float) {
+            warn(format!(
+                "`{}` passes a floating point number, which ez can't do, so it was left out",
+                name
+            ));
+            continue;
+        }
+        if all_types.clone().any(|t| *t == CType::Aggregate) {
+            warn(format!(
+                "`{}` passes a struct or union by value, which ez can't do, so it was left out",
+                name
+            ));
+            continue;
+        }
+        if func.params.iter().any(|p| p.1 == CType::Void) {
+            warn(format!(
+                "`{}` has a `void` parameter, so it was left out",
+                name
+            ));
+            continue;
+        }
+        if func.params.len() > MAX_ARGS {
+            warn(format!(
+                "`{}` takes {} arguments but ez can only pass {}, so it was left out",
+                name,
+                func.params.len(),
+                MAX_ARGS
+            ));
+            continue;
+        }
+        if get_kword(name) != Token::Iden(name.to_string()) {
+            warn(format!("`{}` is a keyword in ez, so it was left out", name));
+            continue;
+        }
+        for t in all_types {
+            if let CType::Unknown(t) = t {
+                warn(format!(
+                    "`{}` uses the type `{}`, which isn't known. it is treated like an integer",
+                    name, t
+                ));
+            }
+        }
+        if func.ret
+            == (CType::Int {
+                narrow_signed: true,
+            })
+        {
+            warn(format!(
+                "`{}` returns `{}`, which is smaller than an ez number, so negative numbers it returns will be big positive ones",
+                name, func.ret_text
+            ));
+        }
+        let mut params: Vec<String> = func
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, (param, _))| match param {
+                Some(p) if get_kword(p) == Token::Iden(p.to_string()) => p.clone(),
+                // no name or one that ez can't use
+                _ => format!("arg{}", i + 1),
+            })
+            .collect();
+        if func.variadic {
+            params.push(String::from("..."));
+        }
+        writeln!(
+            out,
+            "external function {}({}){}.",
+            name,
+            params.join(", "),
+            if func.ret == CType::Void {
+                " returns nothing"
+            } else {
+                ""
+            }
+        )
+        .unwrap();
+    }
+    (out, warnings)
+}
+
+/// split a header into the declarations in it (the text before each `;`), with the line each starts on.
+/// comments and preprocessor lines are skipped, and so are the bodies of structs and functions. the insides of `extern "C" { }` are kept
+fn declarations(header: &str) -> Vec<(String, usize)> {
+    let chars: Vec<char> = header.chars().collect();
+    let mut decls = Vec::new();
+    let mut cur = String::new();
+    let mut cur_line = 1;
+    let mut line = 1;
+    // how deep in the braces being skipped we are
+    let mut depth = 0;
+    let mut at_line_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+            cur.push(' ');
+            continue;
+        }
+        if c == '/' && next == Some('/') || c == '#' && at_line_start {
+            // up to the end of the line. preprocessor lines can go on with a `\`
+            while i < chars.len() && chars[i] != '\n' {
+                if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                    line += 1;
+                    i += 1;
+                }
+                i += 1;
+            }
+            continue;
+        }
+        i += 1;
+        if c == '\n' {
+            line += 1;
+            at_line_start = true;
+            cur.push(' ');
+            continue;
+        }
+        if !c.is_whitespace() {
+            at_line_start = false;
+        }
+        if depth > 0 {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            ';' => {
+                if !cur.trim().is_empty() {
+                    decls.push((cur.trim().to_string(), cur_line));
+                }
+                cur.clear();
+            }
+            '{' => {
+                if cur.split_whitespace().collect::<Vec<&str>>() != ["extern", "\"C\""] {
+                    depth = 1;
+                }
+                cur.clear();
+            }
+            // the end of an `extern "C"` block
+            '}' => cur.clear(),
+            _ => {
+                if cur.trim().is_empty() && !c.is_whitespace() {
+                    cur_line = line;
+                }
+                cur.push(c);
+            }
+        }
+    }
+    decls
+}
+
+/// split a declaration into words and punctuation. attributes and qualifiers that don't matter to ez are taken out
+fn tokens(decl: &str) -> Vec<String> {
+    let chars: Vec<char> = decl.chars().collect();
+    let mut toks: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            toks.push(chars[start..i].iter().collect());
+            continue;
+        }
+        if c == '.' && chars.get(i..i + 3) == Some(&['.', '.', '.']) {
+            toks.push(String::from("..."));
+            i += 3;
+            continue;
+        }
+        if !c.is_whitespace() {
+            toks.push(c.to_string());
+        }
+        i += 1;
+    }
+    // take out `__attribute__((...))` and the like
+    let mut out = Vec::new();
+    let mut iter = toks.into_iter();
+    while let Some(t) = iter.next() {
+        match t.as_str() {
+            "__attribute__" | "__asm__" | "__declspec" => {
+                let mut depth = 0;
+                for t in iter.by_ref() {
+                    match t.as_str() {
+                        "(" => depth += 1,
+                        ")" => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            "const" | "volatile" | "restrict" | "__restrict" | "__restrict__" | "inline"
+            | "__inline" | "extern" | "register" => {}
+            _ => out.push(t),
+        }
+    }
+    out
+}
+
+/// is this a C word that is part of a type and can't be a name
+fn is_type_word(word: &str) -> bool {
+    matches!(
+        word,
+        "void"
+            | "char"
+            | "short"
+            | "int"
+            | "long"
+            | "signed"
+            | "unsigned"
+            | "float"
+            | "double"
+            | "_Bool"
+            | "bool"
+            | "struct"
+            | "union"
+            | "enum"
+    )
+}
+
+/// is this an identifier
+fn is_iden(tok: &str) -> bool {
+    tok.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+}
+
+/// work out what the tokens of a type are to ez. `array` is for params like `int a[]`, which are pointers
+fn classify(toks: &[String], array: bool) -> CType {
+    if array || toks.iter().any(|t| t == "*" || t == "(") {
+        return CType::Pointer;
+    }
+    let words: Vec<&str> = toks.iter().map(|t| t.as_str()).collect();
+    if words
+        .iter()
+        .any(|w| matches!(*w, "float" | "double" | "_Complex"))
+    {
+        return CType::Float;
+    }
+    match words.first() {
+        Some(&"struct") | Some(&"union") => return CType::Aggregate,
+        Some(&"enum") => {
+            return CType::Int {
+                narrow_signed: true,
+            }
+        }
+        _ => {}
+    }
+    if words == ["void"] {
+        return CType::Void;
+    }
+    if !words.is_empty() && words.iter().all(|w| is_type_word(w)) {
+        let unsigned = words
+            .iter()
+            .any(|w| matches!(*w, "unsigned" | "_Bool" | "bool"));
+        let long = words.contains(&"long");
+        return CType::Int {
+            narrow_signed: !unsigned && !long,
+        };
+    }
+    match words[..] {
+        ["int8_t"] | ["int16_t"] | ["int32_t"] | ["pid_t"] | ["wchar_t"] => CType::Int {
+            narrow_signed: true,
+        },
+        ["int64_t"]
+        | ["uint8_t"]
+        | ["uint16_t"]
+        | ["uint32_t"]
+        | ["uint64_t"]
+        | ["size_t"]
+        | ["ssize_t"]
+        | ["intptr_t"]
+        | ["uintptr_t"]
+        | ["ptrdiff_t"]
+        | ["intmax_t"]
+        | ["uintmax_t"]
+        | ["off_t"]
+        | ["uid_t"]
+        | ["gid_t"] => CType::Int {
+            narrow_signed: false,
+        },
+        _ => CType::Unknown(words.join(" ")),
+    }
+}
+
+/// parse one parameter. gives back its name (if it has one) and its type
+fn parse_param(toks: &[String]) -> (Option<String>, CType) {
+    // a function pointer: `int (*callback)(int)`
+    if let Some(p) = toks.iter().position(|t| t == "(") {
+        let name = toks[p..].iter().find(|t| is_iden(t)).cloned();
+        return (name, CType::Pointer);
+    }
+    let (toks, array) = match toks.iter().position(|t| t == "[") {
+        Some(b) => (&toks[..b], true),
+        None => (toks, false),
+    };
+    match toks.split_last() {
+        // the last word is the name when there is a type before it. `unsigned long` has no name
+        Some((last, rest)) if !rest.is_empty() && is_iden(last) && !is_type_word(last) => {
+            (Some(last.clone()), classify(rest, array))
+        }
+        _ => (None, classify(toks, array)),
+    }
+}
+
+/// parse a declaration. `None` if it isn't a function declaration (or it is one that can't be linked to), and an `Err` with a warning if it is one ez can't read
+fn parse_decl(toks: &[String]) -> Option<Result<CFunc, String>> {
+    if toks.iter().any(|t| t == "typedef" || t == "static") {
+        return None;
+    }
+    let open = toks.iter().position(|t| t == "(")?;
+    if open < 2 || !is_iden(&toks[open - 1]) {
+        // a function pointer variable or something else that isn't a function
+        return None;
+    }
+    let name = toks[open - 1].clone();
+    let mut depth = 0;
+    let close = open
+        + toks[open..].iter().position(|t| {
+            match t.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            depth == 0
+        })?;
+    if close + 1 != toks.len() {
+        return Some(Err(format!(
+            "the declaration of `{}` is too complicated to read, so it was left out",
+            name
+        )));
+    }
+    let mut params = Vec::new();
+    let mut variadic = false;
+    let inside = &toks[open + 1..close];
+    if !(inside.is_empty() || inside.len() == 1 && inside[0] == "void") {
+        let mut depth = 0;
+        for param in inside.split(|t| {
+            match t.as_str() {
+                "(" => depth += 1,
+                ")" => depth -= 1,
+                _ => {}
+            }
+            depth == 0 && t == ","
+        }) {
+            if param.len() == 1 && param[0] == "..." {
+                variadic = true;
+            } else {
+                params.push(parse_param(param));
+            }
+        }
+    }
+    let ret_toks = &toks[..open - 1];
+    Some(Ok(CFunc {
+        name,
+        ret: classify(ret_toks, false),
+        ret_text: ret_toks.join(" "),
+        params,
+        variadic,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bindgen_simple_header() {
+        use crate::bindgen;
+        let header = "#ifndef LIB_H
+#define LIB_H \\
+    1
+#include <stdint.h>
+/* a comment
+ * with a ; in it */
+struct point { int64_t x; int64_t y; };
+typedef long number;
+extern int64_t PutNum(int64_t n);
+void PutChar(int64_t);
+static inline int helper(int a) { return a; }
+char *strdup(const char *s);
+unsigned long Sum(unsigned long *arr, size_t len, int to);
+int printf(const char *fmt, ...);
+double sqrt(double x);
+int64_t Dist(struct point a, struct point b);
+int Seven(int a, int b, int c, int d, int e, int f, int g);
+number Weird(number n) __attribute__((pure));
+int (*handler)(int);
+void loop(void);
+#endif
+";
+        let (out, warnings) = bindgen::bindgen(header);
+        assert_eq!(
+            out,
+            "external function PutNum(n).
+external function PutChar(arg1) returns nothing.
+external function strdup(s).
+external function Sum(arr, len, arg3).
+external function printf(fmt, ...).
+external function Weird(n).
+"
+        );
+        let warnings: Vec<(usize, &str)> = warnings
+            .iter()
+            .map(|w| (w.line, w.message.split('`').nth(1).unwrap()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (14, "printf"),
+                (15, "sqrt"),
+                (16, "Dist"),
+                (17, "Seven"),
+                (18, "Weird"),
+                (18, "Weird"),
+                (20, "loop"),
+            ]
+        );
+    }
+}
@@ -1,161 +1,572 @@
-use std::env::args;
+use std::collections::hash_map::RandomState;
+use std::env::{self, args};
+use std::fmt::Write;
 use std::fs;
-use std::process::exit;
-use std::process::Command;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::{self, exit, Command};
+use std::thread;
 
-use crate::analyse;
-use crate::codegen;
-use crate::lexer;
-use crate::parser;
-
-const ERROR: &str = "\x1B[31;1mERROR: \x1B[0m";
+use ezc::bindgen;
+use ezc::errors::{Diagnostics, ErrorFormat, Printer, Severity, WarningKind, WarningOptions};
+use ezc::interpret;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use ezc::jit;
+use ezc::lexer::{self, Span, Token};
+use ezc::parser;
+use ezc::repl;
+use ezc::{CompiledFile, Options};
 
 /// the driver function for the whole compiler
 pub fn driver() {
-    // generate the code
-    let opts = parse_cmd_line_opts();
-    let input = match fs::read_to_string(&opts.filename) {
-        Ok(a) => a,
-        Err(_) => {
-            eprintln!("{}Cannot read file: `{}`.", ERROR, opts.filename);
+    if args().nth(1).as_deref() == Some("bindgen") {
+        bindgen_driver(args().skip(2).collect());
+        return;
+    }
+    if args().nth(1).as_deref() == Some("repl") {
+        repl_driver(args().skip(2).collect());
+        return;
+    }
+    // `ezc run` takes the same options, and runs the program after making it
+    let run = args().nth(1).as_deref() == Some("run");
+    let opts = parse_cmd_line_opts(args().skip(if run { 2 } else { 1 }).collect(), run);
+    // the temporary files go in a folder for this process, so ezcs running at the same time don't write over each other's files
+    let tmp_dir = match TempDir::new(&opts.printer) {
+        Ok(tmp_dir) => tmp_dir,
+        Err(e) => {
+            opts.printer.error(
+                "E402",
+                format!(
+                    "Cannot make temporary directory in `{}`: {}",
+                    env::temp_dir().display(),
+                    e
+                ),
+            );
             exit(1);
         }
     };
-    let code = parse_input_to_code(input, opts.library);
-    // write the code to temp asm file
-    fs::write("out.asm", code).unwrap_or_else(|e| {
-        eprintln!("{}Cannot write assembly to temporary file: {}", ERROR, e);
-        exit(1);
-    });
-    // assemble it
-    if opts.debug {
-        command_run_error_printing(
-            "nasm",
-            Command::new("nasm")
-                .arg("-felf64")
-                .arg("-F")
-                .arg("dwarf")
-                .arg("-g")
-                .arg("out.asm")
-                .arg("-o")
-                .arg(opts.filename.clone() + ".o"),
+    let result = build(&opts, &tmp_dir.path);
+    // `exit` doesn't run destructors, so the folder has to be removed before it
+    drop(tmp_dir);
+    match result {
+        Ok(0) => {}
+        // the program that `ezc run` ran failed
+        Ok(code) => exit(code),
+        Err(()) => exit(1),
+    }
+}
+
+/// a folder for the temporary files. it is removed when this is dropped, so it is also removed if the build fails or panics
+struct TempDir<'a> {
+    path: PathBuf,
+    printer: &'a Printer,
+}
+
+impl<'a> TempDir<'a> {
+    /// make a new folder in the system temporary directory. `create_dir` fails if the name is already taken, even by a
+    /// symlink, so the files can't be made to go somewhere else. then it tries again with a random name
+    fn new(printer: &'a Printer) -> io::Result<Self> {
+        let mut name = format!("ezc-{}", process::id());
+        for _ in 0..100 {
+            let path = env::temp_dir().join(&name);
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(TempDir { path, printer }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+            let random = RandomState::new().build_hasher().finish();
+            name = format!("ezc-{}-{:016x}", process::id(), random);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "all of the names tried are taken",
+        ))
+    }
+}
+
+impl Drop for TempDir<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            self.printer.error(
+                "E402",
+                format!(
+                    "Cannot remove temporary directory `{}`: {}",
+                    self.path.display(),
+                    e
+                ),
+            );
+        }
+    }
+}
+
+/// compile and link the files, and run the program for `ezc run`. gives back the exit code of the program, or 0 if it
+/// wasn't run. the errors are printed before `Err` is given back, so all that is left to do is clean up
+fn build(opts: &CmdArgInfo, tmp_dir: &Path) -> Result<i32, ()> {
+    let mut printer = opts.printer.clone();
+    let mut roots = Vec::new();
+    // C files and objects that get linked with the ez ones
+    let mut c_files = Vec::new();
+    let mut extra_objects = Vec::new();
+    for file in &opts.files {
+        match Path::new(file).extension().and_then(|e| e.to_str()) {
+            Some("ez") => match fs::read_to_string(file) {
+                Ok(input) => roots.push((PathBuf::from(file), input)),
+                Err(_) => {
+                    printer.error("E401", format!("Cannot read file: `{}`.", file));
+                    return Err(());
+                }
+            },
+            Some("c") => c_files.push(file.clone()),
+            Some("o") | Some("a") => extra_objects.push(file.clone()),
+            _ => {
+                printer.error(
+                    "E404",
+                    format!(
+                        "I don't know what to do with `{}`. The files have to be .ez, .c, .o or .a files.",
+                        file
+                    ),
+                );
+                return Err(());
+            }
+        }
+    }
+    if roots.is_empty() {
+        printer.error("E404", "I need an input file.");
+        return Err(());
+    }
+    if opts.run && (opts.library || opts.no_link || opts.emit.is_some()) {
+        printer.error(
+            "E404",
+            "ezc run makes a program and runs it, so it can't be used with -lib, -nolink or --emit.",
         );
-    } else {
-        command_run_error_printing(
-            "nasm",
-            Command::new("nasm")
-                .arg("-felf64")
-                .arg("out.asm")
-                .arg("-o")
-                .arg(opts.filename.clone() + ".o"),
+        return Err(());
+    }
+    if opts.interpret && (!c_files.is_empty() || !extra_objects.is_empty()) {
+        printer.error("E404", "The interpreter can only run .ez files.");
+        return Err(());
+    }
+    let many_roots = roots.len() > 1;
+    // the errors in the only given file don't need to say which file they are in
+    if !many_roots {
+        printer.main_file = Some(roots[0].0.display().to_string());
+    }
+    // these come before the used files are loaded, so they are only for the given files
+    if let Some(emit @ (Emit::Tokens | Emit::Ast)) = opts.emit {
+        let mut text = String::new();
+        for (path, input) in &roots {
+            let mut diagnostics = Diagnostics::new(opts.error_limit);
+            let (tokens, locs) = lexer::Tokenizer::new().lex(input);
+            let tokens = match tokens {
+                Ok(tokens) => tokens,
+                Err(errs) => {
+                    for e in errs {
+                        diagnostics.push(e.diagnostic(input).in_file(path));
+                    }
+                    diagnostics.print(&printer);
+                    return Err(());
+                }
+            };
+            let dump = if emit == Emit::Tokens {
+                dump_tokens(input, &tokens, &locs)
+            } else {
+                match parser::parse(tokens, locs) {
+                    Ok(ast) => format!("{:#?}\n", ast),
+                    Err(errs) => {
+                        for e in errs {
+                            diagnostics.push(e.diagnostic(input).in_file(path));
+                        }
+                        diagnostics.print(&printer);
+                        return Err(());
+                    }
+                }
+            };
+            emit_file(&mut text, path, many_roots, &dump);
+        }
+        return write_emitted(opts, &printer, &text).map(|()| 0);
+    }
+    // `ezc run` runs a program made of .ez files inside ezc. it is only linked into an executable when it needs other
+    // code, or when the files that make it are asked for. the jit only makes x86-64 linux code
+    let jit = cfg!(all(target_os = "linux", target_arch = "x86_64"))
+        && opts.run
+        && !opts.interpret
+        && c_files.is_empty()
+        && extra_objects.is_empty()
+        && !opts.libc
+        && !opts.debug
+        && !opts.emit_header
+        && opts.output.is_none()
+        && opts.stdlib_path.is_none();
+    let options = Options {
+        // the files have their own paths
+        path: PathBuf::new(),
+        library: opts.library,
+        libc: opts.libc,
+        jit,
+        c_main: !c_files.is_empty() || !extra_objects.is_empty(),
+        emit_header: opts.emit_header,
+        warnings: opts.warnings.clone(),
+        error_limit: opts.error_limit,
+    };
+    let artifacts = match ezc::compile_files(roots, &options) {
+        Ok(artifacts) => artifacts,
+        Err(diagnostics) => {
+            diagnostics.print(&printer);
+            return Err(());
+        }
+    };
+    // the warnings go to stderr, so they don't get mixed in with what `--emit` prints
+    for w in &artifacts.warnings {
+        printer.print(w);
+    }
+    if opts.interpret {
+        return interpret_files(&artifacts.files, &printer);
+    }
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    if jit {
+        return jit_files(&artifacts.files, &printer);
+    }
+    let link = !opts.library && !opts.no_link && opts.emit.is_none();
+    let text_stage = matches!(opts.emit, Some(Emit::AnalysedAst | Emit::Asm));
+    // without linking the objects are what is made, so `-o` can only name one of them
+    if !link && !text_stage && opts.output.is_some() && artifacts.files.len() + c_files.len() > 1 {
+        printer.error(
+            "E404",
+            "-o can only be used with -lib or -nolink when there is one file to compile.",
         );
+        return Err(());
     }
-    // link it
-    if let Some(p) = opts.stdlib_path {
-        if !opts.library && !opts.no_link {
-            command_run_error_printing(
-                "ld",
-                Command::new("ld")
-                    .arg(opts.filename.clone() + ".o")
-                    .arg(p.as_str())
-                    .arg("-o")
-                    .arg("a.out"),
-            );
+    // the object for a file. when linking it gets thrown away, so it goes with the other temporary files
+    let object_path = |file: &Path, tmp_name: String| -> PathBuf {
+        match &opts.output {
+            _ if link => tmp_dir.join(tmp_name),
+            Some(output) => PathBuf::from(output),
+            None => PathBuf::from(format!("{}.o", file.display())),
+        }
+    };
+    // if no file has `_start`, the program starts at the `main` of the C code
+    let has_start = artifacts.files.iter().any(|f| f.main);
+    let many_files = artifacts.files.len() > 1;
+    let mut text = String::new();
+    let mut objects = Vec::new();
+    for file in artifacts.files {
+        if let Some(header) = &file.header {
+            let path = file.path.with_extension("h");
+            if let Err(e) = fs::write(&path, header) {
+                printer.error(
+                    "E402",
+                    format!("Cannot write header `{}`: {}", path.display(), e),
+                );
+                return Err(());
+            }
+        }
+        match opts.emit {
+            Some(Emit::AnalysedAst) => {
+                emit_file(
+                    &mut text,
+                    &file.path,
+                    many_files,
+                    &format!("{:#?}\n", file.ast),
+                );
+                continue;
+            }
+            Some(Emit::Asm) => {
+                emit_file(&mut text, &file.path, many_files, &file.asm);
+                continue;
+            }
+            _ => {}
         }
-    } else {
-        if !opts.library && !opts.no_link {
-            command_run_error_printing(
-                "ld",
-                Command::new("ld")
-                    .arg(opts.filename.clone() + ".o")
-                    .arg("-o")
-                    .arg("a.out"),
+        // module names are unique, so they can be the names of the temporary files
+        let object = object_path(&file.path, format!("{}.o", file.name));
+        // with debug info the assembly is kept next to the file, so the debugger can show it
+        let asm = if opts.debug {
+            PathBuf::from(format!("{}.asm", file.path.display()))
+        } else {
+            tmp_dir.join(format!("{}.asm", file.name))
+        };
+        if let Err(e) = fs::write(&asm, &file.asm) {
+            printer.error(
+                "E402",
+                format!("Cannot write assembly to `{}`: {}", asm.display(), e),
             );
+            return Err(());
+        }
+        // assemble it
+        let mut nasm = Command::new("nasm");
+        nasm.arg("-felf64");
+        if opts.debug {
+            nasm.arg("-F").arg("dwarf").arg("-g");
+        }
+        command_run_error_printing(&printer, "nasm", nasm.arg(&asm).arg("-o").arg(&object))?;
+        objects.push(object);
+    }
+    if text_stage {
+        return write_emitted(opts, &printer, &text).map(|()| 0);
+    }
+    // the C files are compiled after the ez ones, so they can include the headers from --emit-header
+    for (i, c_file) in c_files.iter().enumerate() {
+        let stem = Path::new(c_file).file_stem().unwrap_or_default();
+        let object = object_path(
+            Path::new(c_file),
+            format!("{}_{}.o", stem.to_string_lossy(), i),
+        );
+        let mut cc = Command::new("cc");
+        if opts.debug {
+            cc.arg("-g");
         }
+        command_run_error_printing(
+            &printer,
+            "cc",
+            cc.arg("-c").arg(c_file).arg("-o").arg(&object),
+        )?;
+        objects.push(object);
     }
-    // remove temp files if not in debug mode
-    if !opts.debug {
-        if let Err(e) = fs::remove_file("out.asm") {
-            eprintln!("{}Cannot remove temporary file: {}", ERROR, e);
+    // link it
+    if link {
+        // a C `main` needs the C runtime to call it, so cc does the linking
+        let mut linker = if has_start {
+            Command::new("ld")
+        } else {
+            let mut cc = Command::new("cc");
+            cc.arg("-no-pie");
+            cc
+        };
+        linker.args(&objects).args(&extra_objects);
+        if let Some(p) = &opts.stdlib_path {
+            linker.arg(p.as_str());
+        }
+        if opts.libc && has_start {
+            // libc is a shared library, so the dynamic linker has to load it when the program starts
+            linker
+                .arg("-lc")
+                .arg("-dynamic-linker")
+                .arg("/lib64/ld-linux-x86-64.so.2");
+        }
+        let name = if has_start { "ld" } else { "cc" };
+        // the program that `ezc run` runs is thrown away after, unless -o says where to keep it
+        let output = match &opts.output {
+            Some(output) => PathBuf::from(output),
+            None if opts.run => tmp_dir.join("a.out"),
+            None => PathBuf::from("a.out"),
+        };
+        command_run_error_printing(&printer, name, linker.arg("-o").arg(&output))?;
+        if opts.run {
+            // `./` so a relative path isn't looked for in $PATH
+            let program = Path::new(".").join(&output);
+            return match Command::new(&program).status() {
+                Ok(status) => Ok(status.code().unwrap_or(1)),
+                Err(e) => {
+                    printer.error(
+                        "E403",
+                        format!("Failed to run `{}`: {}", program.display(), e),
+                    );
+                    Err(())
+                }
+            };
         }
     }
-    if !opts.library && !opts.no_link {
-        if let Err(e) = fs::remove_file(opts.filename.clone() + ".o") {
-            eprintln!("{}Cannot remove temporary file: {}", ERROR, e);
+    Ok(0)
+}
+
+/// `ezc run --interpret`: run the program with the interpreter instead of making an executable
+fn interpret_files(files: &[CompiledFile], printer: &Printer) -> Result<i32, ()> {
+    // the interpreter goes deeper into its own stack for every call in the program, so it gets a thread with a big one
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(interpret::STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let stdin = io::stdin();
+                interpret::run(files, &mut stdin.lock(), &mut io::stdout().lock())
+            })
+            .expect("cannot make a thread for the interpreter")
+            .join()
+    });
+    match result {
+        Ok(Ok(())) => Ok(0),
+        Ok(Err(diagnostics)) => {
+            diagnostics.print(printer);
+            Err(())
         }
+        Err(panic) => std::panic::resume_unwind(panic),
     }
 }
 
-fn parse_input_to_code(input: String, lib: bool) -> String {
-    let mut tokenizer = lexer::Tokenizer::new();
-    let output = tokenizer.lex(&input);
-    if let Err(e) = output.0 {
-        println!("{}{}", ERROR, e.print_the_error(&input));
+/// `ezc run`: run the program inside ezc, without nasm or ld
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn jit_files(files: &[CompiledFile], printer: &Printer) -> Result<i32, ()> {
+    // the program gets a stack as big as the one it would get as an executable
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(jit::STACK_SIZE)
+            .spawn_scoped(scope, || {
+                let stdin = io::stdin();
+                jit::run(files, &mut stdin.lock(), &mut io::stdout().lock())
+            })
+            .expect("cannot make a thread for the program")
+            .join()
+    });
+    match result {
+        Ok(Ok(())) => Ok(0),
+        Ok(Err(diagnostics)) => {
+            diagnostics.print(printer);
+            Err(())
+        }
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+/// `ezc repl`: run ez code as it is typed in
+fn repl_driver(args: Vec<String>) {
+    let printer = Printer::for_stderr();
+    if !args.is_empty() {
+        printer.error("E404", "Usage: ezc repl");
         exit(1);
     }
-    let code_text;
-    let output = parser::parse(output.0.unwrap(), output.1);
-    match output {
-        Ok(mut res) => match analyse::analize(&mut res) {
-            Ok(_) => {
-                let mut code = codegen::Code::new();
-                code.cgen(res);
-                code_text = format!("{}", code.fmt(lib));
-            }
-            Err(e) => {
-                println!("{}{}", ERROR, e);
-                exit(1);
-            }
-        },
-        Err(e) => {
-            println!("{}{}", ERROR, e.print_the_error(&input));
+    // the prompts are only for a person, not for code piped in
+    let prompts = io::stdin().is_terminal();
+    // like `ezc run --interpret`, the calls need a big stack
+    thread::Builder::new()
+        .stack_size(interpret::STACK_SIZE)
+        .spawn(move || {
+            let stdin = io::stdin();
+            repl::run(
+                &mut stdin.lock(),
+                &mut io::stdout().lock(),
+                &printer,
+                prompts,
+            )
+        })
+        .expect("cannot make a thread for the repl")
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+}
+
+/// `ezc bindgen header.h`: print ez declarations for the functions in a C header
+fn bindgen_driver(args: Vec<String>) {
+    let printer = Printer::for_stderr();
+    let filename = match &args[..] {
+        [filename] => filename,
+        _ => {
+            printer.error("E404", "Usage: ezc bindgen [header.h]");
             exit(1);
         }
+    };
+    let header = fs::read_to_string(filename).unwrap_or_else(|_| {
+        printer.error("E401", format!("Cannot read file: `{}`.", filename));
+        exit(1);
+    });
+    let (decls, warnings) = bindgen::bindgen(&header);
+    // the warnings go to stderr so the declarations can be piped into a file
+    for w in warnings {
+        eprintln!(
+            "{}{}:{}: {}",
+            printer.label(Severity::Warning),
+            filename,
+            w.line,
+            w.message
+        );
+    }
+    print!("{{ made by ezc bindgen from {} }}\n{}", filename, decls);
+}
+
+/// the stages that `--emit` can stop after
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    /// the tokens from the lexer
+    Tokens,
+    /// the tree from the parser, before the used files are loaded
+    Ast,
+    /// the tree after the analysis, with `vars_declared` and `static_vars` filled in
+    AnalysedAst,
+    /// the assembly
+    Asm,
+    /// the object files, without linking them
+    Obj,
+}
+
+impl Emit {
+    fn from_name(name: &str) -> Option<Emit> {
+        match name {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "analysed-ast" => Some(Emit::AnalysedAst),
+            "asm" => Some(Emit::Asm),
+            "obj" => Some(Emit::Obj),
+            _ => None,
+        }
+    }
+}
+
+/// one token a line, with the line and column it starts at
+fn dump_tokens(input: &str, tokens: &[Token], locs: &[Span]) -> String {
+    // the char index that each line starts at
+    let mut line_starts = vec![0];
+    for (i, c) in input.chars().enumerate() {
+        if c == '\n' {
+            line_starts.push(i as u32 + 1);
+        }
+    }
+    let mut out = String::new();
+    for (token, span) in tokens.iter().zip(locs) {
+        let line = line_starts.partition_point(|&start| start <= span.start);
+        let col = span.start - line_starts[line - 1] + 1;
+        writeln!(out, "{}:{} {:?}", line, col, token).unwrap();
+    }
+    out
+}
+
+/// add what a stage made for a file to `out`. when there is more than one file, each one starts with its name
+fn emit_file(out: &mut String, path: &Path, many: bool, text: &str) {
+    if many {
+        writeln!(out, "==> {} <==", path.display()).unwrap();
+    }
+    out.push_str(text);
+}
+
+/// write what `--emit` made to the `-o` file, or print it
+fn write_emitted(opts: &CmdArgInfo, printer: &Printer, text: &str) -> Result<(), ()> {
+    match &opts.output {
+        Some(output) => fs::write(output, text).map_err(|e| {
+            printer.error("E402", format!("Cannot write `{}`: {}", output, e));
+        }),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
     }
-    code_text
 }
 
 struct CmdArgInfo {
     debug: bool,
     no_link: bool,
-    filename: String,
+    /// the .ez, .c, .o and .a files to compile and link
+    files: Vec<String>,
+    /// `-o`. where the executable goes, or the object with -lib or -nolink, or what `--emit` made
+    output: Option<String>,
+    /// `--emit=stage`. stop after this stage and write what it made
+    emit: Option<Emit>,
     library: bool,
     help: bool,
     stdlib_path: Option<String>,
+    libc: bool,
+    emit_header: bool,
+    error_limit: usize,
+    warnings: WarningOptions,
+    /// `--error-format` and `--color`
+    printer: Printer,
+    /// `ezc run`. run the program after making it
+    run: bool,
+    /// `--interpret`. with `ezc run`, run the program with the interpreter instead of making it
+    interpret: bool,
 }
 
-fn parse_cmd_line_opts() -> CmdArgInfo {
-    let mut cmd_args_tmp = args().collect::<Vec<String>>();
-    if cmd_args_tmp.len() == 1 {
-        eprintln!("{}I need an input file.", ERROR);
+/// `cmd_args` are the args after `ezc` (or `ezc run`)
+fn parse_cmd_line_opts(cmd_args: Vec<String>, run: bool) -> CmdArgInfo {
+    if cmd_args.is_empty() {
+        Printer::for_stderr().error("E404", "I need an input file.");
         exit(1);
     }
-    let mut cmd_line_args: Vec<&str> = cmd_args_tmp.iter_mut().map(|x| x.as_str()).collect();
-    cmd_line_args.remove(0);
-    let filename = cmd_line_args.remove(0);
-    match filename {
-        "-h" | "--help" => {
-            println!(
-                "ezc
-
-ezc version {}
-
-Usage: ezc [file] [options] ...
-Options:
-
--g                  Include Debug Info
--lib                Just compile the functions into a library/object (.o) file
--nolink             Just compile it into a .o file. Do not link. But this will contain _start.
--stdlib-path path   The path of the standard library object file so we can link to it.
--h | --help     Show This Help Message and Exit
-
-To Report Bugs Go To: github.com/g-w1/ezc/issues/",
-                env!("CARGO_PKG_VERSION")
-            );
-            exit(0);
-        }
-        _ => {}
-    }
+    let cmd_line_args: Vec<&str> = cmd_args.iter().map(|x| x.as_str()).collect();
     // let input = match fs::read_to_string(&filename) {
     //     Ok(a) => a,
     //     Err(_) => {
@@ -169,7 +580,16 @@ To Report Bugs Go To: github.com/g-w1/ezc/issues/",
         library: false,
         no_link: false,
         stdlib_path: None,
-        filename: filename.to_string(),
+        libc: false,
+        emit_header: false,
+        error_limit: 20,
+        warnings: WarningOptions::default(),
+        files: Vec::new(),
+        output: None,
+        emit: None,
+        printer: Printer::for_stderr(),
+        run,
+        interpret: false,
     };
     let mut args_iter = cmd_line_args.iter();
     while let Some(&i) = args_iter.next() {
@@ -182,13 +602,35 @@ To Report Bugs Go To: github.com/g-w1/ezc/issues/",
 
 ezc version {}
 
-Usage: ezc [file] [options] ...
+Usage: ezc [files] [options] ...
+       ezc run [files] [options] ...   Make the program and run it. .ez files are run inside ezc, without nasm or ld.
+                                       With C files, objects, -libc, -g, -o, -stdlib-path or --emit-header
+                                       it is linked into an executable that gets run
+       ezc repl                 Type in ez code and run it right away. An expression on its own prints its value
+       ezc bindgen [header.h]   Print ez declarations for the functions in a C header
+
+The files can be .ez files, C files and .o or .a files. They are all compiled and linked together.
+Only one .ez file can have code outside of functions, that is where the program starts.
+If none of them do, the program starts at the `main` of the C code.
 Options:
 
--g                  Include Debug Info
+-o path             Where to write the executable. Default is a.out. With -lib or -nolink it is the object file.
+                    With --emit it is where the output goes
+-g                  Include Debug Info. The assembly is kept next to each file as file.ez.asm
 -lib                Just compile the functions into a library/object (.o) file
 -nolink             Just compile it into a .o file. Do not link. But this will contain _start.
 -stdlib-path path   The path of the standard library object file so we can link to it.
+-libc               Link with libc so C functions like printf can be called. Ex `external function printf(fmt, ...).`
+--emit=stage        Stop after a stage and write what it made: tokens, ast, analysed-ast, asm or obj.
+                    The text ones are printed, or written to the -o file. obj keeps the objects like -nolink
+--emit-header       Also write a C header for the exported functions. lib.ez gets lib.h
+-error-limit n      Stop printing errors after n of them. 0 means no limit. Default is 20.
+-Wname | -Wno-name  Turn the warning with this name (or code) on or off. Ex -Wno-unused-variable or -Wno-W001
+-Werror             Turn warnings into errors
+--error-format=fmt  How to print errors and warnings: human (the default) or json, one object a line
+--color=when        Colour the errors: auto (the default, only when stderr is a terminal), always or never
+--interpret         With ezc run, run the program with the interpreter. It doesn't need nasm, ld or the standard library,
+                    but it can only run .ez files
 -h | --help     Show This Help Message and Exit
 
 To Report Bugs Go To: github.com/g-w1/ezc/issues/",
@@ -198,48 +640,130 @@ To Report Bugs Go To: github.com/g-w1/ezc/issues/",
             }
             "-lib" => arg_info.library = true,
             "-nolink" => arg_info.no_link = true,
+            "-libc" => arg_info.libc = true,
+            "--interpret" if run => arg_info.interpret = true,
+            "--interpret" => {
+                arg_info
+                    .printer
+                    .error("E404", "--interpret can only be used with `ezc run`.");
+                exit(1);
+            }
+            "--emit-header" => arg_info.emit_header = true,
+            "-o" => {
+                arg_info.output = Some({
+                    if let Some(x) = args_iter.next() {
+                        x.to_string()
+                    } else {
+                        arg_not_found(
+                            &arg_info.printer,
+                            "Need another option after -o: The path of the output",
+                        );
+                        exit(1)
+                    }
+                })
+            }
             "-stdlib-path" => {
                 arg_info.stdlib_path = Some({
                     if let Some(x) = args_iter.next() {
                         x.to_string()
                     } else {
-                        arg_not_found("Need another option after -stdlib-path: The actual path");
+                        arg_not_found(
+                            &arg_info.printer,
+                            "Need another option after -stdlib-path: The actual path",
+                        );
                         exit(1)
                     }
                 })
             }
-            e => arg_not_found(e),
+            "-error-limit" => {
+                arg_info.error_limit = match args_iter.next().map(|x| x.parse()) {
+                    Some(Ok(n)) => n,
+                    _ => {
+                        arg_not_found(
+                            &arg_info.printer,
+                            "Need a number after -error-limit: The most errors to print",
+                        );
+                        exit(1)
+                    }
+                }
+            }
+            "-Werror" => arg_info.warnings.as_errors = true,
+            e if e.starts_with("--emit=") => match Emit::from_name(&e["--emit=".len()..]) {
+                Some(emit) => arg_info.emit = Some(emit),
+                None => {
+                    arg_info.printer.error(
+                        "E404",
+                        format!(
+                            "There is no stage called `{}`. The stages are tokens, ast, analysed-ast, asm and obj.",
+                            &e["--emit=".len()..]
+                        ),
+                    );
+                    exit(1);
+                }
+            },
+            e if e.starts_with("--error-format=") => match &e["--error-format=".len()..] {
+                "human" => arg_info.printer.format = ErrorFormat::Human,
+                "json" => arg_info.printer.format = ErrorFormat::Json,
+                _ => arg_not_found(&arg_info.printer, e),
+            },
+            c if c.starts_with("--color=") => match &c["--color=".len()..] {
+                "auto" => arg_info.printer.color = Printer::for_stderr().color,
+                "always" => arg_info.printer.color = true,
+                "never" => arg_info.printer.color = false,
+                _ => arg_not_found(&arg_info.printer, c),
+            },
+            w if w.starts_with("-W") => {
+                let (name, on) = match w.strip_prefix("-Wno-") {
+                    Some(name) => (name, false),
+                    None => (&w[2..], true),
+                };
+                match WarningKind::from_name(name) {
+                    Some(kind) => arg_info.warnings.set(kind, on),
+                    None => {
+                        arg_info
+                            .printer
+                            .error("E404", format!("There is no warning called `{}`.", name));
+                        exit(1);
+                    }
+                }
+            }
+            file if !file.starts_with('-') => arg_info.files.push(file.to_string()),
+            e => arg_not_found(&arg_info.printer, e),
         }
     }
     arg_info
 }
 
-fn arg_not_found(arg: &str) {
-    eprintln!("{}Invalid option: {}", ERROR, arg);
+fn arg_not_found(printer: &Printer, arg: &str) {
+    printer.error("E404", format!("Invalid option: {}", arg));
     exit(1);
 }
 
-fn command_run_error_printing(cmd_name: &'static str, cmd: &mut Command) {
+/// run a command. if it fails, what it printed is shown
+fn command_run_error_printing(
+    printer: &Printer,
+    cmd_name: &'static str,
+    cmd: &mut Command,
+) -> Result<(), ()> {
     match cmd.output() {
         Ok(output) => {
             if !output.status.success() {
-                eprintln!("{}{} failed:", ERROR, cmd_name);
-                eprintln!(
-                    "{} stderr:\n{}",
-                    cmd_name,
-                    String::from_utf8(output.stderr).expect("invalid nasm stderr")
-                );
-                eprintln!(
-                    "{} stdout:\n{}",
-                    cmd_name,
-                    String::from_utf8(output.stdout).expect("invalid nasm stderr")
+                printer.error(
+                    "E403",
+                    format!(
+                        "{0} failed:\n{0} stderr:\n{1}\n{0} stdout:\n{2}",
+                        cmd_name,
+                        String::from_utf8_lossy(&output.stderr),
+                        String::from_utf8_lossy(&output.stdout)
+                    ),
                 );
-                exit(1);
+                return Err(());
             }
+            Ok(())
         }
         Err(e) => {
-            eprintln!("{}Failed to execute {}: {}", ERROR, cmd_name, e);
-            exit(1);
+            printer.error("E403", format!("Failed to execute {}: {}", cmd_name, e));
+            Err(())
         }
     }
 }
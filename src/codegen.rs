@@ -4,6 +4,8 @@ use crate::ast::{AstNode, AstRoot, BinOp, Expr, TypeOfSetOrChange, Val};
 use std::collections::HashMap;
 use std::collections::HashSet;
 const FUNCTION_PARAMS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+/// the most args a function can be called with. they all get passed in registers, there are no stack args
+pub const MAX_ARGS: usize = FUNCTION_PARAMS.len();
 /// section .bss
 #[derive(Debug)]
 pub struct Bss {
@@ -29,6 +31,14 @@ pub struct Code {
     number_for_mangling: u32,
     stack_p_offset: u32,
     cur_func: String,
+    /// the external functions declared with `...`
+    variadic_functions: HashSet<String>,
+    /// the program gets linked with libc, so it exits by calling `exit` to flush things like printf's buffer
+    libc: bool,
+    /// the program is run by `jit` inside ezc, so `_start` is called and returns instead of exiting
+    jit: bool,
+    /// what goes in front of the names of statics and functions that aren't exported. has the module name in it so files can be linked together
+    mangle_prefix: String,
 }
 
 impl Code {
@@ -48,20 +58,41 @@ impl Code {
             initalized_static_vars: HashMap::new(),
             initalized_array_lengths: HashMap::new(),
             cur_func: String::new(),
+            variadic_functions: HashSet::new(),
+            libc: false,
+            jit: false,
+            mangle_prefix: String::from("MaNgLe_"),
         }
     }
+    /// say which module this is, so its symbols don't clash with the ones from other files. `MaNgLe_x` becomes `MaNgLe_module.x`
+    pub fn set_module(&mut self, module: &str) {
+        self.mangle_prefix = format!("MaNgLe_{}.", module);
+    }
+    /// the symbol for a static var or a function that isn't exported
+    fn mangle(&self, name: &str) -> String {
+        format!("{}{}", self.mangle_prefix, name)
+    }
+    /// say that the program will be linked with libc
+    pub fn use_libc(&mut self) {
+        self.libc = true;
+    }
+    /// say that the program will be run by `jit`
+    pub fn use_jit(&mut self) {
+        self.jit = true;
+    }
     /// generate the code. dont deal with any of the sections
     pub fn cgen(&mut self, tree: AstRoot) {
         for var in tree.static_vars.unwrap() {
             self.bss.instructions.push(format!(
-                "MaNgLe_{} resq {}",
-                &var.0,
+                "{} resq {}",
+                self.mangle(&var.0),
                 match var.1 {
                     crate::analyse::Type::Arr(n) => {
                         self.initalized_static_vars.insert(var.0.clone(), true);
-                        n + 1 // + 1 because 1st elem in array is len
+                        n + 2 // + 2 because the array starts with a pointer to itself and its len
                     }
-                    crate::analyse::Type::Number => {
+                    // static vars are only ever made as arrays or numbers
+                    _ => {
                         self.initalized_static_vars.insert(var.0.clone(), false);
                         1
                     } // if its a number we just allocate 1 byte
@@ -82,21 +113,31 @@ impl Code {
                     sete,
                     setor,
                     type_of,
+                    ..
                 } => self.cgen_set_or_change_stmt(sete, setor, type_of),
                 AstNode::If {
                     guard,
                     body,
                     vars_declared,
+                    ..
                 } => self.cgen_if_stmt(guard, vars_declared.unwrap(), body, None), // we unwrap because it was analised
-                AstNode::Loop { body } => self.cgen_loop_stmt(body),
+                AstNode::Loop { body, .. } => self.cgen_loop_stmt(body),
                 AstNode::Func {
                     name,
                     args,
                     body,
                     vars_declared,
                     export,
+                    ..
                 } => self.cgen_function(name, args, body, vars_declared.unwrap(), export),
-                AstNode::Extern { name, .. } => self.text.external_function_names.push(name),
+                AstNode::Extern { name, variadic, .. } => {
+                    if variadic {
+                        self.variadic_functions.insert(name.clone());
+                    }
+                    self.text.external_function_names.push(name)
+                }
+                // the result in r8 just gets ignored
+                AstNode::Call { func_call, .. } => self.cgen_expr(func_call),
                 _ => unreachable!(),
             }
         }
@@ -104,23 +145,26 @@ impl Code {
             self.text.instructions.push(String::from("_start:"))
         }
     }
-    fn cgen_return_stmt(&mut self, val: Expr) {
-        self.cgen_expr(val);
+    fn cgen_return_stmt(&mut self, val: Option<Expr>) {
+        match val {
+            Some(val) => {
+                self.cgen_expr(val);
+                self.text.instructions.push(String::from("mov rax, r8"));
+            }
+            None => self.text.instructions.push(String::from("mov rax, 0")),
+        }
         self.text
             .instructions
-            .push(format!("mov rax, r8\njmp .RETURN_{}", self.cur_func));
+            .push(format!("jmp .RETURN_{}", self.cur_func));
     }
     /// a little helper fn
     fn reg_to_farness_stack(&mut self, n: usize) -> i8 {
-        if n < 6 {
-            self.text
-                .instructions
-                .push(format!("push {}", FUNCTION_PARAMS[n]));
-            self.stack_p_offset += 1;
-            n as i8
-        } else {
-            unimplemented!();
-        }
+        debug_assert!(n < MAX_ARGS, "the analysis only allows {} params", MAX_ARGS);
+        self.text
+            .instructions
+            .push(format!("push {}", FUNCTION_PARAMS[n]));
+        self.stack_p_offset += 1;
+        n as i8
     }
     // ////////////////////////////////////////////////////////////  Systemv abi: https://wiki.osdev.org/Calling_Conventions
     // Platform | Return Value | Parameter Registers        | Additional Parameters |Stack Alignment | Scratch Registers 	                     | Preserved Registers 	             | Call List
@@ -137,8 +181,10 @@ impl Code {
         /////////////////////////// Some setup ///////////////////////// clear local vars bc a func starts with none
         self.initalized_local_vars.clear();
         if !export {
-            self.text.function_names.push(format!("MaNgLe_{}", &name)); // declaring it global
-            self.text.instructions.push(format!("MaNgLe_{}:", &name));
+            // only exported functions are global. the others are only called from this file
+            self.text
+                .instructions
+                .push(format!("{}:", self.mangle(&name)));
         } else {
             self.text.function_names.push(format!("{}", &name)); // declaring it global
             self.text.instructions.push(format!("{}:", &name));
@@ -161,14 +207,17 @@ impl Code {
                     sete,
                     setor,
                     type_of,
+                    ..
                 } => self.cgen_set_or_change_stmt(sete, setor, type_of),
                 AstNode::If {
                     body,
                     guard,
                     vars_declared,
+                    ..
                 } => self.cgen_if_stmt(guard, vars_declared.unwrap(), body, None),
-                AstNode::Return { val } => self.cgen_return_stmt(val),
-                AstNode::Loop { body } => self.cgen_loop_stmt(body),
+                AstNode::Return { val, .. } => self.cgen_return_stmt(val),
+                AstNode::Loop { body, .. } => self.cgen_loop_stmt(body),
+                AstNode::Call { func_call, .. } => self.cgen_expr(func_call),
                 _ => unreachable!(), // function or break statement
             }
         }
@@ -194,23 +243,38 @@ impl Code {
     }
     /// code generation for a function call
     fn cgen_funcall_expr(&mut self, func_name: &str, mangle: bool, args: &Vec<Val>) {
-        for (i, arg) in args.iter().enumerate() {
-            if i > 6 {
-                // we dont support more than 6 args becasuse of abi stuff. should be pretty easy to fix tho
-                unimplemented!()
-            }
+        debug_assert!(
+            args.len() <= MAX_ARGS,
+            "the analysis only allows {} args",
+            MAX_ARGS
+        );
+        for arg in args {
             match arg {
                 Val::Expr(e) => self.cgen_expr(e.clone()),
                 Val::Array(_ve) => unreachable!(),
             }
+            // the next args can call functions and use r8 and r9, so they all go in registers at the end
+            self.text.instructions.push(String::from("push r8"));
+            self.stack_p_offset += 1;
+        }
+        for i in (0..args.len()).rev() {
             self.text
                 .instructions
-                .push(format!("mov {}, r8", FUNCTION_PARAMS[i]));
+                .push(format!("pop {}", FUNCTION_PARAMS[i]));
+            self.stack_p_offset -= 1;
         }
         if !mangle {
             self.text
                 .instructions
-                .push(format!("call MaNgLe_{}\nmov r8, rax", func_name));
+                .push(format!("call {}\nmov r8, rax", self.mangle(func_name)));
+        } else if self.variadic_functions.contains(func_name) {
+            // al is how many vector registers have args in them, which is always 0 for us.
+            // varargs functions can use aligned sse instructions on the stack, so align it to 16 and put it back after.
+            // rbx is saved by the callee so it can hold the old rsp
+            self.text.instructions.push(format!(
+                "push rbx\nmov rbx, rsp\nand rsp, -16\nmov al, 0\ncall {}\nmov rsp, rbx\npop rbx\nmov r8, rax",
+                func_name
+            ));
         } else {
             self.text
                 .instructions
@@ -222,26 +286,26 @@ impl Code {
         // not sure if this is a bad decision
         let len_of_arr = ve.len() as u32;
         if let Some(off) = self.initalized_local_vars.get(sete) {
-            // we know it is a stack allocated var
-            let tmp_addr = self.stack_p_offset + off.0 - 2 - len_of_arr;
+            // we know it is a stack allocated var. it starts with a pointer to itself, then the len, then the elements.
+            // where it starts depends on how much space it has, which can be more than this array needs
+            let start = self.stack_p_offset - off.0 - self.initalized_array_lengths[sete];
             self.text
                 .instructions
-                .push(format!("lea r8, [rsp + {} * 8]", tmp_addr));
+                .push(format!("lea r8, [rsp + {} * 8]", start));
             self.text
                 .instructions
-                .push(format!("mov [rsp + {} * 8 ], r8", tmp_addr));
+                .push(format!("mov [rsp + {} * 8 ], r8", start));
             // move the length to the first element in the array
             self.text
                 .instructions
                 .push(format!("mov r8, {}", len_of_arr));
-            self.text.instructions.push(format!(
-                "mov [rsp + {} * 8 ], r8",
-                (self.stack_p_offset + off.0 - 1 - len_of_arr),
-            ));
-            let newoff = off.clone(); // we do this to avoid weird ownership stuff. not my proudest code
-            for (i, e) in ve.iter().rev().enumerate() {
+            self.text
+                .instructions
+                .push(format!("mov [rsp + {} * 8 ], r8", start + 1));
+            // in order, like the static ones, so calls in the elements happen in the same order
+            for (i, e) in ve.iter().enumerate() {
                 self.cgen_expr(e.clone());
-                let tmpval = self.stack_p_offset + &newoff.0 - i as u32 - 1;
+                let tmpval = start + 2 + i as u32;
                 self.text
                     .instructions
                     .push(format!("mov [rsp + {} * 8 ], r8", tmpval));
@@ -250,21 +314,22 @@ impl Code {
             // move ptr to first elem to point to itself
             self.text
                 .instructions
-                .push(format!("lea qword r8, [MaNgLe_{} + 0]", sete,));
+                .push(format!("lea qword r8, [{} + 0]", self.mangle(sete)));
             self.text
                 .instructions
-                .push(format!("mov qword [MaNgLe_{}+0], r8", sete,));
+                .push(format!("mov qword [{}+0], r8", self.mangle(sete)));
             // move the length to the 2nd element in the array
             self.text.instructions.push(format!(
-                "mov qword [MaNgLe_{} + 1 * 8], {}",
-                sete, len_of_arr
+                "mov qword [{} + 1 * 8], {}",
+                self.mangle(sete),
+                len_of_arr
             ));
             // we know it is a static var
             for (i, e) in ve.iter().enumerate() {
                 self.cgen_expr(e.clone());
                 self.text.instructions.push(format!(
-                    "mov qword [MaNgLe_{} + {} * 8], r8",
-                    sete,
+                    "mov qword [{} + {} * 8], r8",
+                    self.mangle(sete),
                     i + 2
                 ));
             }
@@ -273,14 +338,14 @@ impl Code {
     /// code generation for an expr. moves the result to r8
     fn cgen_expr(&mut self, expr: Expr) {
         match expr {
-            Expr::BinOp { lhs, op, rhs } => {
+            Expr::BinOp { lhs, op, rhs, .. } => {
                 self.cgen_binop_expr(*lhs, op, *rhs);
                 self.text.instructions.push(String::from("pop r8"));
                 self.stack_p_offset -= 1;
             }
-            Expr::Number(n) => self.text.instructions.push(format!("mov r8, {}", n)),
-            Expr::Iden(i) => {
-                let r = self.cgen_get_display_asm(&Expr::Iden(i));
+            Expr::Number(n, _) => self.text.instructions.push(format!("mov r8, {}", n)),
+            Expr::Iden(i, _) => {
+                let r = self.cgen_get_var_display_asm(&i);
                 self.text.instructions.push(format!("mov r8, {}", r));
             }
 
@@ -288,15 +353,16 @@ impl Code {
                 func_name,
                 args,
                 external,
+                ..
             } => {
                 self.cgen_funcall_expr(&func_name, external.unwrap_or(true), &args);
             }
-            Expr::DerefPtr(a) => {
-                let r = self.cgen_get_display_asm(&Expr::Iden(a));
+            Expr::DerefPtr(a, _) => {
+                let r = self.cgen_get_var_display_asm(&a);
                 self.text.instructions.push(format!("mov r8, {}", r));
                 self.text.instructions.push(format!("mov r8, [r8]",));
             }
-            Expr::AccessArray(a, e) => {
+            Expr::AccessArray(a, e, _) => {
                 self.cgen_access_array(&a, &*e, true);
             }
         }
@@ -306,7 +372,7 @@ impl Code {
         self.text.instructions.push(format!("add r8, 1"));
         self.text.instructions.push(format!("imul r8, 8"));
         self.text.instructions.push(format!("mov r9, r8"));
-        let r = self.cgen_get_display_asm(&Expr::Iden(a.to_string()));
+        let r = self.cgen_get_var_display_asm(a);
         self.text.instructions.push(format!("mov r8, {}", r));
         self.text.instructions.push(format!("add r8, r9",));
         if access {
@@ -406,19 +472,22 @@ impl Code {
         for node in body {
             match node {
                 AstNode::Func { .. } => unreachable!(),
-                AstNode::Return { val } => self.cgen_return_stmt(val),
+                AstNode::Return { val, .. } => self.cgen_return_stmt(val),
                 AstNode::If {
                     body,
                     guard,
                     vars_declared,
-                } => self.cgen_if_stmt(guard, vars_declared.unwrap(), body, None),
+                    ..
+                } => self.cgen_if_stmt(guard, vars_declared.unwrap(), body, loop_num),
                 AstNode::SetOrChange {
                     sete,
                     setor,
                     type_of,
+                    ..
                 } => self.cgen_set_or_change_stmt(sete, setor, type_of),
-                AstNode::Loop { body } => self.cgen_loop_stmt(body),
-                AstNode::Break => self
+                AstNode::Loop { body, .. } => self.cgen_loop_stmt(body),
+                AstNode::Call { func_call, .. } => self.cgen_expr(func_call),
+                AstNode::Break { .. } => self
                     .text
                     .instructions
                     .push(format!("jmp .END_LOOP_{}", loop_num.unwrap())),
@@ -449,7 +518,7 @@ impl Code {
             .push(format!(".START_LOOP_{}", our_number_for_mangling));
         for node in body {
             match node {
-                AstNode::Return { val } => self.cgen_return_stmt(val),
+                AstNode::Return { val, .. } => self.cgen_return_stmt(val),
                 AstNode::Func { .. } => unreachable!(),
                 AstNode::SetOrChange {
                     type_of: TypeOfSetOrChange::SetIden,
@@ -459,23 +528,26 @@ impl Code {
                     sete,
                     type_of,
                     setor,
+                    ..
                 } => self.cgen_set_or_change_stmt(sete, setor, type_of),
                 AstNode::If {
                     guard,
                     body,
                     vars_declared,
+                    ..
                 } => self.cgen_if_stmt(
                     guard,
                     vars_declared.unwrap(),
                     body,
                     Some(our_number_for_mangling),
                 ),
-                AstNode::Loop { body } => self.cgen_loop_stmt(body),
-                AstNode::Break => self
+                AstNode::Loop { body, .. } => self.cgen_loop_stmt(body),
+                AstNode::Call { func_call, .. } => self.cgen_expr(func_call),
+                AstNode::Break { .. } => self
                     .text
                     .instructions
                     .push(format!("jmp .END_LOOP_{}", our_number_for_mangling)),
-                AstNode::Extern { .. } => unreachable!(),
+                AstNode::Extern { .. } | AstNode::Use { .. } => unreachable!(),
             }
         }
         self.text.instructions.push(format!(
@@ -490,7 +562,7 @@ impl Code {
             SetIden | ChangeIden => match setor {
                 Val::Expr(e) => {
                     self.cgen_expr(e);
-                    let tmpsete = self.cgen_get_display_asm(&Expr::Iden(sete));
+                    let tmpsete = self.cgen_get_var_display_asm(&sete);
                     self.text.instructions.push(format!("mov {}, r8", tmpsete,));
                 }
                 Val::Array(ae) => {
@@ -500,7 +572,7 @@ impl Code {
             ChangePtrDeref => match setor {
                 Val::Expr(e) => {
                     self.cgen_expr(e);
-                    let tmpsete = self.cgen_get_display_asm(&Expr::Iden(sete));
+                    let tmpsete = self.cgen_get_var_display_asm(&sete);
                     self.text.instructions.push(format!("mov r9, {}", tmpsete));
                     self.text.instructions.push(format!("mov qword [r9], r8",));
                 }
@@ -510,7 +582,7 @@ impl Code {
                 match setor {
                     Val::Expr(e) => {
                         self.cgen_expr(e);
-                        // let tmpsete = self.cgen_get_display_asm(&Expr::Iden(sete));
+                        // let tmpsete = self.cgen_get_var_display_asm(&sete);
                         self.text.instructions.push(format!("mov r10, r8",));
                     }
                     Val::Array(_) => unreachable!(),
@@ -534,11 +606,13 @@ impl Code {
                     lhs: lreclhs,
                     op: lrecop,
                     rhs: lrecrhs,
+                    ..
                 },
                 Expr::BinOp {
                     lhs: rreclhs,
                     op: rrecop,
                     rhs: rrecrhs,
+                    ..
                 },
             ) => {
                 self.cgen_binop_expr(*lreclhs, lrecop, *lrecrhs);
@@ -558,6 +632,7 @@ impl Code {
                     lhs: reclhs,
                     op: recop,
                     rhs: recrhs,
+                    ..
                 },
                 _,
             ) => {
@@ -580,6 +655,7 @@ impl Code {
                     lhs: reclhs,
                     op: recop,
                     rhs: recrhs,
+                    ..
                 },
             ) => {
                 let tmplhs = self.cgen_get_display_asm(&cloned_lhs);
@@ -607,35 +683,46 @@ impl Code {
         }
     }
     /// if its a num or iden give how to display it deferenecd
+    /// the operand for the variable `name`
+    fn cgen_get_var_display_asm(&self, name: &str) -> String {
+        match self.initalized_local_vars.get(name) {
+            None => {
+                if !self.initalized_static_vars.get(name).unwrap() {
+                    format!("qword [{}]", self.mangle(name))
+                } else {
+                    self.mangle(name)
+                }
+            }
+            Some(num) => {
+                let val = if let Some(z) = self.initalized_array_lengths.get(name) {
+                    // the var is the pointer at the start of the array
+                    self.stack_p_offset - num.0 - z
+                } else {
+                    self.stack_p_offset - num.0 - 1
+                };
+                format!("qword [rsp + {} * 8]", val)
+            }
+        }
+    }
     fn cgen_get_display_asm(&mut self, expr: &Expr) -> String {
         match expr {
-            Expr::Number(n) => n.to_owned(),
-            Expr::Iden(a) => match self.initalized_local_vars.get(a) {
-                None => {
-                    if !self.initalized_static_vars.get(a).unwrap() {
-                        format!("qword [MaNgLe_{}]", a)
-                    } else {
-                        format!("MaNgLe_{}", a)
-                    }
-                }
-                Some(num) => {
-                    let val = if let Some(z) = self.initalized_array_lengths.get(a) {
-                        self.stack_p_offset + num.0 - z
-                    } else {
-                        self.stack_p_offset - num.0 - 1
-                    };
-                    format!("qword [rsp + {} * 8]", val)
-                }
-            },
+            // push only takes 32 bit numbers
+            Expr::Number(n, _) if n.parse::<i32>().is_err() => {
+                self.text.instructions.push(format!("mov r8, {}", n));
+                String::from("r8")
+            }
+            Expr::Number(n, _) => n.to_owned(),
+            Expr::Iden(a, _) => self.cgen_get_var_display_asm(a),
             Expr::FuncCall {
                 func_name,
                 args,
                 external,
+                ..
             } => {
                 self.cgen_funcall_expr(&func_name, external.unwrap(), args);
                 format!("r8")
             }
-            Expr::AccessArray(a, e) => {
+            Expr::AccessArray(a, e, _) => {
                 self.cgen_access_array(a, &*e, true);
                 format!("r8")
             }
@@ -716,6 +803,9 @@ impl Code {
                 writeln!(f, "extern {}", i).unwrap();
             }
             if !lib {
+                if self.libc {
+                    writeln!(f, "extern exit").unwrap();
+                }
                 writeln!(f, "global _start").unwrap();
             }
             for i in &self.text.function_names {
@@ -737,7 +827,17 @@ impl Code {
                 }
             }
             // exit 0
-            if !lib {
+            if !lib && self.jit {
+                writeln!(f, "ret").unwrap();
+            } else if !lib && self.libc {
+                writeln!(
+                    f,
+                    "and rsp, -16
+xor rdi, rdi
+call exit"
+                )
+                .unwrap();
+            } else if !lib {
                 writeln!(
                     f,
                     "mov rax, 60
@@ -973,7 +1073,7 @@ set tmp to AddOne(1).
         analyse::analize(&mut ast).unwrap();
         let mut code = codegen::Code::new();
         code.cgen(ast);
-        let correct_code = "global _start\nglobal AddOne\nsection .text\nAddOne:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 1 * 8\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov rax, r8\njmp .RETURN_AddOne\nmov rax, 0\n.RETURN_AddOne\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 1\nmov rdi, r8\ncall AddOne\nmov r8, rax\nmov qword [MaNgLe_tmp], r8\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_tmp resq 1\n";
+        let correct_code = "global _start\nglobal AddOne\nsection .text\nAddOne:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 1 * 8\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov rax, r8\njmp .RETURN_AddOne\nmov rax, 0\n.RETURN_AddOne\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 1\npush r8\npop rdi\ncall AddOne\nmov r8, rax\nmov qword [MaNgLe_tmp], r8\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_tmp resq 1\n";
         assert_eq!(format!("{}", code.fmt(false)), correct_code);
     }
     #[test]
@@ -998,10 +1098,70 @@ set z to fib(50).
         analyse::analize(&mut ast).unwrap();
         let mut code = codegen::Code::new();
         code.cgen(ast);
-        let correct_code = "global _start\nglobal MaNgLe_fib\nsection .text\nMaNgLe_fib:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 1 * 8\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\ncmp r9, r8\njle .IF_HEADER_2\njg .IF_HEADER_FAILED_2\n.IF_HEADER_2\npush 1\njmp .END_IF_HEADER_2\n.IF_HEADER_FAILED_2\npush 0\n.END_IF_HEADER_2\npop r8\ncmp r8, 1\nje .IF_BODY_0\njne .IF_END_0\n.IF_BODY_0\nsub rsp, 0 * 8\nmov r8, qword [rsp + 1 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_0\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\nsub r9, r8\npush r9\npop r8\nmov rdi, r8\ncall MaNgLe_fib\nmov r8, rax\npush r8\npush qword [rsp + 2 * 8]\npush 2\npop r8\npop r9\nsub r9, r8\npush r9\npop r8\nmov rdi, r8\ncall MaNgLe_fib\nmov r8, rax\npush r8\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov rax, r8\njmp .RETURN_fib\nmov rax, 0\n.RETURN_fib\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 50\nmov rdi, r8\ncall MaNgLe_fib\nmov r8, rax\nmov qword [MaNgLe_z], r8\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_z resq 1\n";
+        let correct_code = "global _start\nsection .text\nMaNgLe_fib:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 1 * 8\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\ncmp r9, r8\njle .IF_HEADER_2\njg .IF_HEADER_FAILED_2\n.IF_HEADER_2\npush 1\njmp .END_IF_HEADER_2\n.IF_HEADER_FAILED_2\npush 0\n.END_IF_HEADER_2\npop r8\ncmp r8, 1\nje .IF_BODY_0\njne .IF_END_0\n.IF_BODY_0\nsub rsp, 0 * 8\nmov r8, qword [rsp + 1 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_0\npush qword [rsp + 1 * 8]\npush 1\npop r8\npop r9\nsub r9, r8\npush r9\npop r8\npush r8\npop rdi\ncall MaNgLe_fib\nmov r8, rax\npush r8\npush qword [rsp + 2 * 8]\npush 2\npop r8\npop r9\nsub r9, r8\npush r9\npop r8\npush r8\npop rdi\ncall MaNgLe_fib\nmov r8, rax\npush r8\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov rax, r8\njmp .RETURN_fib\nmov rax, 0\n.RETURN_fib\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 50\npush r8\npop rdi\ncall MaNgLe_fib\nmov r8, rax\nmov qword [MaNgLe_z], r8\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_z resq 1\n";
+        assert_eq!(format!("{}", code.fmt(false)), correct_code);
+    }
+    #[test]
+    fn codegen_call_stmt() {
+        use crate::analyse;
+        use crate::codegen;
+        use crate::lexer;
+        use crate::parser;
+
+        let mut tokenizer = lexer::Tokenizer::new();
+        let input = "external function PutChar(c).
+function F(), call PutChar(72). return 0.!
+PutChar(F()).";
+        let output = tokenizer.lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let mut code = codegen::Code::new();
+        code.cgen(ast);
+        let correct_code = "extern PutChar\nglobal _start\nsection .text\nMaNgLe_F:\npush rbp\nmov rbp, rsp\nsub rsp, 0 * 8\nmov r8, 72\npush r8\npop rdi\ncall PutChar\nmov r8, rax\nmov r8, 0\nmov rax, r8\njmp .RETURN_F\nmov rax, 0\n.RETURN_F\nmov rsp, rbp\npop rbp\nret\n_start:\ncall MaNgLe_F\nmov r8, rax\npush r8\npop rdi\ncall PutChar\nmov r8, rax\nmov rax, 60\nxor rdi, rdi\nsyscall\n";
+        assert_eq!(format!("{}", code.fmt(false)), correct_code);
+    }
+    #[test]
+    fn codegen_variadic_call() {
+        use crate::analyse;
+        use crate::codegen;
+        use crate::lexer;
+        use crate::parser;
+
+        let input = "external function printf(fmt, ...).
+set f to 0. call printf(f, 1).";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let mut code = codegen::Code::new();
+        code.use_libc();
+        code.cgen(ast);
+        let correct_code = "extern printf\nextern exit\nglobal _start\nsection .text\n_start:\nmov r8, 0\nmov qword [MaNgLe_f], r8\nmov r8, qword [MaNgLe_f]\npush r8\nmov r8, 1\npush r8\npop rsi\npop rdi\npush rbx\nmov rbx, rsp\nand rsp, -16\nmov al, 0\ncall printf\nmov rsp, rbx\npop rbx\nmov r8, rax\nand rsp, -16\nxor rdi, rdi\ncall exit\nsection .bss\nMaNgLe_f resq 1\n";
         assert_eq!(format!("{}", code.fmt(false)), correct_code);
     }
     #[test]
+    fn codegen_module_mangling() {
+        use crate::analyse;
+        use crate::codegen;
+        use crate::lexer;
+        use crate::parser;
+
+        let input = "function F(), return 1.! set x to F().";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let mut code = codegen::Code::new();
+        code.set_module("math");
+        code.cgen(ast);
+        let asm = code.fmt(false);
+        // it is only called from this file, so it isn't global
+        assert!(!asm.contains("global MaNgLe_math.F"));
+        assert!(asm.contains("MaNgLe_math.F:\n"));
+        assert!(asm.contains("call MaNgLe_math.F\n"));
+        assert!(asm.contains("mov qword [MaNgLe_math.x], r8\n"));
+        assert!(asm.contains("MaNgLe_math.x resq 1\n"));
+        assert!(!asm.contains("MaNgLe_F") && !asm.contains("MaNgLe_x"));
+    }
+    #[test]
     fn codegen_change_stmt() {
         use crate::analyse;
         use crate::codegen;
@@ -1118,7 +1278,7 @@ if you < me,
         analyse::analize(&mut ast).unwrap();
         let mut code = codegen::Code::new();
         code.cgen(ast);
-        let correct_code = "extern PutStringLine\nextern PutString\nextern PutNumBin\nextern PutChar\nextern PutNewLine\nglobal _start\nglobal MaNgLe_TakeArray\nsection .text\nMaNgLe_TakeArray:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 2 * 8\nmov r8, qword [rsp + 2 * 8]\nmov rdi, r8\ncall PutString\nmov r8, rax\nmov qword [rsp + 0 * 8], r8\nmov r8, 0\nmov rax, r8\njmp .RETURN_TakeArray\nmov rax, 0\n.RETURN_TakeArray\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 5\nmov qword [MaNgLe_you], r8\npush qword [MaNgLe_you]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [MaNgLe_me], r8\npush qword [MaNgLe_you]\npush qword [MaNgLe_me]\npop r8\npop r9\ncmp r9, r8\njl .IF_HEADER_2\njge .IF_HEADER_FAILED_2\n.IF_HEADER_2\npush 1\njmp .END_IF_HEADER_2\n.IF_HEADER_FAILED_2\npush 0\n.END_IF_HEADER_2\npop r8\ncmp r8, 1\nje .IF_BODY_0\njne .IF_END_0\n.IF_BODY_0\nsub rsp, 17 * 8\nmov r8, 0\nmov rdi, r8\ncall PutChar\nmov r8, rax\nmov qword [rsp + 0 * 8], r8\nmov r8, 0\nmov qword [rsp + 1 * 8], r8\nlea r8, [rsp + 4 * 8]\nmov [rsp + 4 * 8 ], r8\nmov r8, 13\nmov [rsp + 5 * 8 ], r8\nmov r8, 10\nmov [rsp + 18 * 8 ], r8\nmov r8, 33\nmov [rsp + 17 * 8 ], r8\nmov r8, 100\nmov [rsp + 16 * 8 ], r8\nmov r8, 108\nmov [rsp + 15 * 8 ], r8\nmov r8, 114\nmov [rsp + 14 * 8 ], r8\nmov r8, 111\nmov [rsp + 13 * 8 ], r8\nmov r8, 87\nmov [rsp + 12 * 8 ], r8\nmov r8, 32\nmov [rsp + 11 * 8 ], r8\nmov r8, 111\nmov [rsp + 10 * 8 ], r8\nmov r8, 108\nmov [rsp + 9 * 8 ], r8\nmov r8, 108\nmov [rsp + 8 * 8 ], r8\nmov r8, 101\nmov [rsp + 7 * 8 ], r8\nmov r8, 72\nmov rdi, r8\ncall PutChar\nmov r8, rax\nmov [rsp + 6 * 8 ], r8\nmov r8, qword [rsp + 4 * 8]\nmov rdi, r8\ncall PutString\nmov r8, rax\nmov qword [rsp + 1 * 8], r8\nmov r8, qword [rsp + 4 * 8]\nmov rdi, r8\ncall MaNgLe_TakeArray\nmov r8, rax\nmov qword [rsp + 1 * 8], r8\npush qword [rsp + 0 * 8]\npush 4\npop r8\npop r9\ncmp r9, r8\nje .IF_HEADER_5\njne .IF_HEADER_FAILED_5\n.IF_HEADER_5\npush 1\njmp .END_IF_HEADER_5\n.IF_HEADER_FAILED_5\npush 0\n.END_IF_HEADER_5\npop r8\ncmp r8, 1\nje .IF_BODY_3\njne .IF_END_3\n.IF_BODY_3\nsub rsp, 10 * 8\nlea r8, [rsp + 36 * 8]\nmov [rsp + 36 * 8 ], r8\nmov r8, 8\nmov [rsp + 37 * 8 ], r8\nmov r8, 104\nmov [rsp + 45 * 8 ], r8\nmov r8, 103\nmov [rsp + 44 * 8 ], r8\nmov r8, 102\nmov [rsp + 43 * 8 ], r8\nmov r8, 101\nmov [rsp + 42 * 8 ], r8\nmov r8, 100\nmov [rsp + 41 * 8 ], r8\nmov r8, 99\nmov [rsp + 40 * 8 ], r8\nmov r8, 98\nmov [rsp + 39 * 8 ], r8\nmov r8, 97\nmov [rsp + 38 * 8 ], r8\nmov r8, qword [rsp + 36 * 8]\nmov rdi, r8\ncall PutStringLine\nmov r8, rax\nmov qword [rsp + 11 * 8], r8\nmov r8, qword [rsp + 14 * 8]\nmov rdi, r8\ncall PutString\nmov r8, rax\nmov qword [rsp + 11 * 8], r8\nadd rsp, 10 * 8\n.IF_END_3\nadd rsp, 17 * 8\n.IF_END_0\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_you resq 1\nMaNgLe_me resq 1\n";
+        let correct_code = "extern PutStringLine\nextern PutString\nextern PutNumBin\nextern PutChar\nextern PutNewLine\nglobal _start\nsection .text\nMaNgLe_TakeArray:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 2 * 8\nmov r8, qword [rsp + 2 * 8]\npush r8\npop rdi\ncall PutString\nmov r8, rax\nmov qword [rsp + 0 * 8], r8\nmov r8, 0\nmov rax, r8\njmp .RETURN_TakeArray\nmov rax, 0\n.RETURN_TakeArray\nmov rsp, rbp\npop rbp\nret\n_start:\nmov r8, 5\nmov qword [MaNgLe_you], r8\npush qword [MaNgLe_you]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [MaNgLe_me], r8\npush qword [MaNgLe_you]\npush qword [MaNgLe_me]\npop r8\npop r9\ncmp r9, r8\njl .IF_HEADER_2\njge .IF_HEADER_FAILED_2\n.IF_HEADER_2\npush 1\njmp .END_IF_HEADER_2\n.IF_HEADER_FAILED_2\npush 0\n.END_IF_HEADER_2\npop r8\ncmp r8, 1\nje .IF_BODY_0\njne .IF_END_0\n.IF_BODY_0\nsub rsp, 17 * 8\nmov r8, 0\npush r8\npop rdi\ncall PutChar\nmov r8, rax\nmov qword [rsp + 0 * 8], r8\nmov r8, 0\nmov qword [rsp + 1 * 8], r8\nlea r8, [rsp + 2 * 8]\nmov [rsp + 2 * 8 ], r8\nmov r8, 13\nmov [rsp + 3 * 8 ], r8\nmov r8, 72\npush r8\npop rdi\ncall PutChar\nmov r8, rax\nmov [rsp + 4 * 8 ], r8\nmov r8, 101\nmov [rsp + 5 * 8 ], r8\nmov r8, 108\nmov [rsp + 6 * 8 ], r8\nmov r8, 108\nmov [rsp + 7 * 8 ], r8\nmov r8, 111\nmov [rsp + 8 * 8 ], r8\nmov r8, 32\nmov [rsp + 9 * 8 ], r8\nmov r8, 87\nmov [rsp + 10 * 8 ], r8\nmov r8, 111\nmov [rsp + 11 * 8 ], r8\nmov r8, 114\nmov [rsp + 12 * 8 ], r8\nmov r8, 108\nmov [rsp + 13 * 8 ], r8\nmov r8, 100\nmov [rsp + 14 * 8 ], r8\nmov r8, 33\nmov [rsp + 15 * 8 ], r8\nmov r8, 10\nmov [rsp + 16 * 8 ], r8\nmov r8, qword [rsp + 2 * 8]\npush r8\npop rdi\ncall PutString\nmov r8, rax\nmov qword [rsp + 1 * 8], r8\nmov r8, qword [rsp + 2 * 8]\npush r8\npop rdi\ncall MaNgLe_TakeArray\nmov r8, rax\nmov qword [rsp + 1 * 8], r8\npush qword [rsp + 0 * 8]\npush 4\npop r8\npop r9\ncmp r9, r8\nje .IF_HEADER_5\njne .IF_HEADER_FAILED_5\n.IF_HEADER_5\npush 1\njmp .END_IF_HEADER_5\n.IF_HEADER_FAILED_5\npush 0\n.END_IF_HEADER_5\npop r8\ncmp r8, 1\nje .IF_BODY_3\njne .IF_END_3\n.IF_BODY_3\nsub rsp, 10 * 8\nlea r8, [rsp + 0 * 8]\nmov [rsp + 0 * 8 ], r8\nmov r8, 8\nmov [rsp + 1 * 8 ], r8\nmov r8, 97\nmov [rsp + 2 * 8 ], r8\nmov r8, 98\nmov [rsp + 3 * 8 ], r8\nmov r8, 99\nmov [rsp + 4 * 8 ], r8\nmov r8, 100\nmov [rsp + 5 * 8 ], r8\nmov r8, 101\nmov [rsp + 6 * 8 ], r8\nmov r8, 102\nmov [rsp + 7 * 8 ], r8\nmov r8, 103\nmov [rsp + 8 * 8 ], r8\nmov r8, 104\nmov [rsp + 9 * 8 ], r8\nmov r8, qword [rsp + 0 * 8]\npush r8\npop rdi\ncall PutStringLine\nmov r8, rax\nmov qword [rsp + 11 * 8], r8\nmov r8, qword [rsp + 12 * 8]\npush r8\npop rdi\ncall PutString\nmov r8, rax\nmov qword [rsp + 11 * 8], r8\nadd rsp, 10 * 8\n.IF_END_3\nadd rsp, 17 * 8\n.IF_END_0\nmov rax, 60\nxor rdi, rdi\nsyscall\nsection .bss\nMaNgLe_you resq 1\nMaNgLe_me resq 1\n";
         assert_eq!(format!("{}", code.fmt(false)), correct_code);
     }
     #[test]
@@ -1214,7 +1374,7 @@ MaNgLe_x resq 1
         analyse::analize(&mut ast).unwrap();
         let mut code = codegen::Code::new();
         code.cgen(ast);
-        let correct_code = "global MaNgLe_fib\nsection .text\nMaNgLe_fib:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 4 * 8\nmov r8, 1\nmov qword [rsp + 0 * 8], r8\nmov r8, 1\nmov qword [rsp + 1 * 8], r8\nmov r8, 0\nmov qword [rsp + 2 * 8], r8\n.START_LOOP_0\npush qword [rsp + 1 * 8]\npush qword [rsp + 3 * 8]\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 1 * 8], r8\npush qword [rsp + 0 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 0 * 8], r8\npush qword [rsp + 0 * 8]\npush qword [rsp + 5 * 8]\npop r8\npop r9\ncmp r9, r8\njg .IF_HEADER_3\njle .IF_HEADER_FAILED_3\n.IF_HEADER_3\npush 1\njmp .END_IF_HEADER_3\n.IF_HEADER_FAILED_3\npush 0\n.END_IF_HEADER_3\npop r8\ncmp r8, 1\nje .IF_BODY_1\njne .IF_END_1\n.IF_BODY_1\nsub rsp, 0 * 8\nmov r8, qword [rsp + 1 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_1\npush qword [rsp + 1 * 8]\npush qword [rsp + 3 * 8]\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 2 * 8], r8\npush qword [rsp + 0 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 0 * 8], r8\npush qword [rsp + 0 * 8]\npush qword [rsp + 5 * 8]\npop r8\npop r9\ncmp r9, r8\njg .IF_HEADER_7\njle .IF_HEADER_FAILED_7\n.IF_HEADER_7\npush 1\njmp .END_IF_HEADER_7\n.IF_HEADER_FAILED_7\npush 0\n.END_IF_HEADER_7\npop r8\ncmp r8, 1\nje .IF_BODY_5\njne .IF_END_5\n.IF_BODY_5\nsub rsp, 0 * 8\nmov r8, qword [rsp + 2 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_5\njmp .START_LOOP_0\n.END_LOOP_0\nmov rax, 0\n.RETURN_fib\nmov rsp, rbp\npop rbp\nret\n";
+        let correct_code = "section .text\nMaNgLe_fib:\npush rbp\nmov rbp, rsp\npush rdi\nsub rsp, 4 * 8\nmov r8, 1\nmov qword [rsp + 0 * 8], r8\nmov r8, 1\nmov qword [rsp + 1 * 8], r8\nmov r8, 0\nmov qword [rsp + 2 * 8], r8\n.START_LOOP_0\npush qword [rsp + 1 * 8]\npush qword [rsp + 3 * 8]\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 1 * 8], r8\npush qword [rsp + 0 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 0 * 8], r8\npush qword [rsp + 0 * 8]\npush qword [rsp + 5 * 8]\npop r8\npop r9\ncmp r9, r8\njg .IF_HEADER_3\njle .IF_HEADER_FAILED_3\n.IF_HEADER_3\npush 1\njmp .END_IF_HEADER_3\n.IF_HEADER_FAILED_3\npush 0\n.END_IF_HEADER_3\npop r8\ncmp r8, 1\nje .IF_BODY_1\njne .IF_END_1\n.IF_BODY_1\nsub rsp, 0 * 8\nmov r8, qword [rsp + 1 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_1\npush qword [rsp + 1 * 8]\npush qword [rsp + 3 * 8]\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 2 * 8], r8\npush qword [rsp + 0 * 8]\npush 1\npop r8\npop r9\nadd r9, r8\npush r9\npop r8\nmov qword [rsp + 0 * 8], r8\npush qword [rsp + 0 * 8]\npush qword [rsp + 5 * 8]\npop r8\npop r9\ncmp r9, r8\njg .IF_HEADER_7\njle .IF_HEADER_FAILED_7\n.IF_HEADER_7\npush 1\njmp .END_IF_HEADER_7\n.IF_HEADER_FAILED_7\npush 0\n.END_IF_HEADER_7\npop r8\ncmp r8, 1\nje .IF_BODY_5\njne .IF_END_5\n.IF_BODY_5\nsub rsp, 0 * 8\nmov r8, qword [rsp + 2 * 8]\nmov rax, r8\njmp .RETURN_fib\nadd rsp, 0 * 8\n.IF_END_5\njmp .START_LOOP_0\n.END_LOOP_0\nmov rax, 0\n.RETURN_fib\nmov rsp, rbp\npop rbp\nret\n";
         assert_eq!(format!("{}", code.fmt(true)), correct_code);
     }
 }
@@ -0,0 +1,240 @@
+//! compiling ez code to assembly without printing anything or exiting, so the compiler can be used from other programs
+
+use crate::analyse;
+use crate::ast::AstRoot;
+use crate::codegen;
+use crate::errors::{Diagnostic, Diagnostics, Suppressions, WarningOptions};
+use crate::header;
+use crate::modules::{self, Module};
+use std::path::PathBuf;
+
+/// how to compile
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// where the source is. the paths in its `use`s are from its folder. the file doesn't have to exist if it doesn't `use` anything
+    pub path: PathBuf,
+    /// only compile the functions, with no `_start`
+    pub library: bool,
+    /// the program is linked with libc, so it exits with `exit`
+    pub libc: bool,
+    /// the program is run inside this process by `jit::run`, so `_start` returns when it is done
+    pub jit: bool,
+    /// the program starts at a C `main` in something else that gets linked in, so a file only gets `_start` if it has code outside of functions
+    pub c_main: bool,
+    /// make C headers for the exported functions of the given files
+    pub emit_header: bool,
+    /// which warnings are on, and if they are errors
+    pub warnings: WarningOptions,
+    /// the most errors that `Diagnostics::print` prints. 0 means no limit
+    pub error_limit: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            path: PathBuf::from("main.ez"),
+            library: false,
+            libc: false,
+            jit: false,
+            c_main: false,
+            emit_header: false,
+            warnings: WarningOptions::default(),
+            error_limit: 20,
+        }
+    }
+}
+
+/// what compiling made
+#[derive(Debug)]
+pub struct Artifacts {
+    /// every file, the used ones before the ones that use them
+    pub files: Vec<CompiledFile>,
+    /// the warnings that are on and not allowed in the code, with the files they are in
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// one compiled file
+#[derive(Debug)]
+pub struct CompiledFile {
+    pub path: PathBuf,
+    /// the source, for showing where the errors from running it are
+    pub input: String,
+    /// the name of the file in the mangled names. unique in the program
+    pub name: String,
+    /// the file was given to the compiler, it isn't just used by another one
+    pub root: bool,
+    /// it has the `_start` of the program
+    pub main: bool,
+    /// the tree after the analysis
+    pub ast: AstRoot,
+    /// the nasm assembly
+    pub asm: String,
+    /// the C header for the exported functions, if `Options::emit_header` and the file is a given one
+    pub header: Option<String>,
+}
+
+/// compile `source`, and the files it uses
+pub fn compile(source: &str, options: &Options) -> Result<Artifacts, Diagnostics> {
+    compile_files(vec![(options.path.clone(), source.to_string())], options)
+}
+
+/// compile the files and the files they use. `options.path` isn't used, the files have their paths.
+/// only one of the files can have code outside of functions
+pub fn compile_files(
+    roots: Vec<(PathBuf, String)>,
+    options: &Options,
+) -> Result<Artifacts, Diagnostics> {
+    let mut diagnostics = Diagnostics::new(options.error_limit);
+    let modules = match modules::load(roots) {
+        Ok(modules) => modules,
+        Err(errs) => {
+            for e in errs {
+                diagnostics.push(e);
+            }
+            return Err(diagnostics);
+        }
+    };
+    // `_start` goes in the file with code at the top level. `modules::load` makes sure there is only one
+    let main = modules
+        .iter()
+        .position(|m| m.top_level_code().is_some())
+        .or_else(|| {
+            if options.c_main {
+                None
+            } else {
+                modules.iter().position(|m| m.root)
+            }
+        });
+    let mut artifacts = Artifacts {
+        files: Vec::new(),
+        warnings: Vec::new(),
+    };
+    for (i, module) in modules.into_iter().enumerate() {
+        let file = compile_module(
+            module,
+            options,
+            main == Some(i),
+            &mut artifacts.warnings,
+            &mut diagnostics,
+        );
+        // keep going so the errors in all of the files are found
+        if let Some(file) = file {
+            artifacts.files.push(file);
+        }
+    }
+    if diagnostics.has_any() {
+        Err(diagnostics)
+    } else {
+        Ok(artifacts)
+    }
+}
+
+/// analyse a loaded file and make the assembly for it. only the main file gets a `_start`, the others are compiled like libraries.
+/// gives back `None` if there were errors
+fn compile_module(
+    module: Module,
+    options: &Options,
+    is_main: bool,
+    warnings_out: &mut Vec<Diagnostic>,
+    diagnostics: &mut Diagnostics,
+) -> Option<CompiledFile> {
+    let Module {
+        path,
+        name,
+        input,
+        ast: mut res,
+        special_comments,
+        root,
+    } = module;
+    let mut warnings = match analyse::analize(&mut res) {
+        Ok(warnings) => warnings,
+        Err(errs) => {
+            for e in errs {
+                diagnostics.push(e.diagnostic(&input).in_file(&path));
+            }
+            return None;
+        }
+    };
+    let (suppressions, comment_warnings) = Suppressions::from_comments(&special_comments, &input);
+    warnings.extend(comment_warnings);
+    warnings.sort_by_key(|w| w.span.start);
+    let errors_before = diagnostics.all().len();
+    for w in warnings {
+        if !options.warnings.is_on(w.kind) || suppressions.allows(&w, &input) {
+            continue;
+        }
+        let d = w
+            .diagnostic(&input, options.warnings.as_errors)
+            .in_file(&path);
+        if options.warnings.as_errors {
+            diagnostics.push(d);
+        } else {
+            warnings_out.push(d);
+        }
+    }
+    if diagnostics.all().len() != errors_before {
+        return None;
+    }
+    let header = if options.emit_header && root {
+        // lib.ez gets lib.h
+        let header_path = path.with_extension("h");
+        let header_name = header_path.file_name().unwrap().to_string_lossy();
+        Some(header::gen_header(&res, &header_name))
+    } else {
+        None
+    };
+    let mut code = codegen::Code::new();
+    // so functions and statics that aren't exported don't clash with the ones in other files
+    code.set_module(&name);
+    if options.libc {
+        code.use_libc();
+    }
+    if options.jit {
+        code.use_jit();
+    }
+    code.cgen(res.clone());
+    Some(CompiledFile {
+        asm: code.fmt(options.library || !is_main),
+        path,
+        input,
+        name,
+        root,
+        main: is_main,
+        ast: res,
+        header,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn compile_source() {
+        use crate::compile::{compile, Options};
+        let options = Options::default();
+        let artifacts = compile("set x to 1. set y to 2. change x to y.", &options).unwrap();
+        assert_eq!(artifacts.files.len(), 1);
+        let file = &artifacts.files[0];
+        assert!(file.main && file.root);
+        assert!(file.asm.contains("_start:"));
+        assert!(file.ast.static_vars.is_some());
+        assert!(file.header.is_none());
+        assert_eq!(artifacts.warnings.len(), 1);
+        assert!(artifacts.warnings[0].message.contains("`x`"));
+        assert_eq!(artifacts.warnings[0].file.as_deref(), Some("main.ez"));
+
+        // the errors come back instead of being printed
+        let diagnostics = compile("set x to y.", &options).unwrap_err();
+        assert_eq!(diagnostics.all().len(), 1);
+        assert!(diagnostics.all()[0].message.contains('y'));
+        assert_eq!(diagnostics.all()[0].code, "E202");
+
+        let mut options = Options::default();
+        options.warnings.as_errors = true;
+        options.emit_header = true;
+        options.path = std::path::PathBuf::from("lib.ez");
+        assert!(compile("set x to 1.", &options).is_err());
+        let artifacts = compile("export function F(), return 1.!", &options).unwrap();
+        let header = artifacts.files[0].header.as_ref().unwrap();
+        assert!(header.contains("#ifndef LIB_H") && header.contains("int64_t F(void);"));
+    }
+}
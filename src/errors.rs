@@ -1,107 +1,820 @@
-use crate::analyse::AnalysisError;
-use crate::ast::TypeOfSetOrChange;
-use crate::lexer::{LexError, Token, Token::*};
+use crate::analyse::{AnalysisError, Type};
+use crate::assemble::AsmError;
+use crate::ast::{BinOp, Expr, TypeOfSetOrChange, Val};
+use crate::codegen::MAX_ARGS;
+use crate::interpret::RuntimeError;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+use crate::jit::JitError;
+use crate::lexer::{LexError, Span, Token, Token::*};
+use crate::modules::ModuleError;
 use crate::parser::ParserError;
+use std::collections::HashSet;
 use std::fmt;
+use std::io::IsTerminal;
+use std::path::Path;
+
+/// how bad a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// where in a file a diagnostic is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// in chars, like the spans from the lexer
+    pub span: Span,
+    /// counting from 1
+    pub line: usize,
+    /// counting from 1, in chars
+    pub column: usize,
+    /// the text of the line, so it can be shown with a caret under it
+    pub source_line: String,
+}
+
+/// an error or a warning. it is kept like this until it is printed, so it can be printed for people or as json
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// like `E201` or `W001`. the errors are E0.. from the lexer, E1.. from the parser, E2.. from the analysis,
+    /// E3.. from loading files, E4.. from the driver and E5.. from running the program
+    pub code: &'static str,
+    /// what is wrong. the lines after the first one are hints
+    pub message: String,
+    /// the file it is in. `None` when it isn't about one file
+    pub file: Option<String>,
+    /// `None` when it isn't about a place in the code
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    /// an error that isn't about a place in the code, like a file that can't be written
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            file: None,
+            location: None,
+        }
+    }
+    /// a diagnostic about `span` in `input_code`
+    pub fn at(
+        severity: Severity,
+        code: &'static str,
+        message: String,
+        input_code: &str,
+        span: Span,
+    ) -> Self {
+        Diagnostic {
+            severity,
+            code,
+            message,
+            file: None,
+            location: Some(locate(input_code, span)),
+        }
+    }
+    /// say which file it is in
+    pub fn in_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.display().to_string());
+        self
+    }
+}
+
+/// how diagnostics are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+    /// with the line and a caret under the bad code
+    #[default]
+    Human,
+    /// one json object per line
+    Json,
+}
+
+/// prints diagnostics. they all go to stderr
+#[derive(Debug, Clone, Default)]
+pub struct Printer {
+    pub format: ErrorFormat,
+    /// use ANSI colour codes. only for `ErrorFormat::Human`
+    pub color: bool,
+    /// the file that isn't named in the human format because it is the only one that was given
+    pub main_file: Option<String>,
+}
+
+impl Printer {
+    /// the human format, in colour if stderr is a terminal
+    pub fn for_stderr() -> Self {
+        Printer {
+            color: std::io::stderr().is_terminal(),
+            ..Printer::default()
+        }
+    }
+    /// `ERROR: ` or `WARNING: `
+    pub fn label(&self, severity: Severity) -> String {
+        match (severity, self.color) {
+            (Severity::Error, true) => String::from("\x1B[31;1mERROR: \x1B[0m"),
+            (Severity::Warning, true) => String::from("\x1B[33;1mWARNING: \x1B[0m"),
+            (Severity::Error, false) => String::from("ERROR: "),
+            (Severity::Warning, false) => String::from("WARNING: "),
+        }
+    }
+    /// the text for a diagnostic, without a newline at the end
+    pub fn render(&self, d: &Diagnostic) -> String {
+        match self.format {
+            ErrorFormat::Human => self.render_human(d),
+            ErrorFormat::Json => render_json(d),
+        }
+    }
+    fn render_human(&self, d: &Diagnostic) -> String {
+        let mut res = self.label(d.severity);
+        if let Some(file) = &d.file {
+            if self.main_file.as_ref() != Some(file) {
+                res += &format!("In `{}`: ", file);
+            }
+        }
+        // warnings say which one they are, so they can be turned off
+        if let Some(kind) = WarningKind::from_name(d.code) {
+            res += &format!("[{} {}] ", kind.code(), kind.name());
+        }
+        res += &d.message;
+        if let Some(l) = &d.location {
+            let len = (l.span.end - l.span.start) as usize;
+            res += &format!(
+                "\n{}:{}:\n{}\n{}",
+                l.line,
+                l.column,
+                l.source_line,
+                up_caret(&l.source_line, l.column, len, self.color)
+            );
+        }
+        res
+    }
+    /// print a diagnostic
+    pub fn print(&self, d: &Diagnostic) {
+        eprintln!("{}", self.render(d));
+    }
+    /// print an error that isn't about a place in the code
+    pub fn error(&self, code: &'static str, message: impl Into<String>) {
+        self.print(&Diagnostic::error(code, message));
+    }
+}
+
+/// a diagnostic as one line of json
+fn render_json(d: &Diagnostic) -> String {
+    let (line, column, span) = match &d.location {
+        Some(l) => (
+            l.line.to_string(),
+            l.column.to_string(),
+            format!("{{\"start\":{},\"end\":{}}}", l.span.start, l.span.end),
+        ),
+        None => ("null".into(), "null".into(), "null".into()),
+    };
+    format!(
+        "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"span\":{}}}",
+        d.severity.name(),
+        d.code,
+        json_string(&d.message),
+        d.file.as_deref().map_or("null".into(), json_string),
+        line,
+        column,
+        span
+    )
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => res += "\\\"",
+            '\\' => res += "\\\\",
+            '\n' => res += "\\n",
+            '\r' => res += "\\r",
+            '\t' => res += "\\t",
+            c if (c as u32) < 0x20 => res += &format!("\\u{:04x}", c as u32),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// collects the errors of a compile so they can all be printed at once.
+/// only the first `limit` get printed for people so a broken file doesn't flood the terminal
+#[derive(Debug)]
+pub struct Diagnostics {
+    list: Vec<Diagnostic>,
+    /// 0 means no limit
+    limit: usize,
+}
+
+impl Diagnostics {
+    pub fn new(limit: usize) -> Self {
+        Diagnostics {
+            list: Vec::new(),
+            limit,
+        }
+    }
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.list.push(diagnostic);
+    }
+    pub fn has_any(&self) -> bool {
+        !self.list.is_empty()
+    }
+    /// all of the errors, even the ones past the limit
+    pub fn all(&self) -> &[Diagnostic] {
+        &self.list
+    }
+    /// the errors that get printed and how many were left out
+    fn shown(&self) -> (&[Diagnostic], usize) {
+        if self.limit == 0 || self.list.len() <= self.limit {
+            (&self.list, 0)
+        } else {
+            (&self.list[..self.limit], self.list.len() - self.limit)
+        }
+    }
+    /// print the errors. json gets all of them and no summary, so every line is a record
+    pub fn print(&self, printer: &Printer) {
+        if printer.format == ErrorFormat::Json {
+            for d in &self.list {
+                printer.print(d);
+            }
+            return;
+        }
+        let (shown, hidden) = self.shown();
+        for d in shown {
+            printer.print(d);
+        }
+        if hidden != 0 {
+            // not a diagnostic of its own, so it has no label
+            eprintln!(
+                "{} more error{} not shown (see -error-limit)",
+                hidden,
+                if hidden == 1 { "" } else { "s" }
+            );
+        }
+        if self.list.len() > 1 {
+            eprintln!("{} errors found", self.list.len());
+        }
+    }
+}
+
+/// the kinds of warnings. each one has a code and a name that never change, so they can be turned on and off by either
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum WarningKind {
+    /// a variable that is set but never read
+    UnusedVariable,
+    /// a `{ez: ...}` comment that doesn't make sense
+    BadAllowComment,
+    /// a function parameter that is never read
+    UnusedParameter,
+    /// a function that is not exported and never called
+    UnusedFunction,
+    /// a function that can get to its end without returning a value
+    MissingReturn,
+    /// statements after a `return`, a `break` or a loop that never stops
+    UnreachableCode,
+}
+
+impl WarningKind {
+    /// all of the warnings, in order of their codes
+    pub const ALL: [WarningKind; 6] = [
+        WarningKind::UnusedVariable,
+        WarningKind::BadAllowComment,
+        WarningKind::UnusedParameter,
+        WarningKind::UnusedFunction,
+        WarningKind::MissingReturn,
+        WarningKind::UnreachableCode,
+    ];
+    /// the code, like `W001`
+    pub fn code(self) -> &'static str {
+        match self {
+            WarningKind::UnusedVariable => "W001",
+            WarningKind::BadAllowComment => "W002",
+            WarningKind::UnusedParameter => "W003",
+            WarningKind::UnusedFunction => "W004",
+            WarningKind::MissingReturn => "W005",
+            WarningKind::UnreachableCode => "W006",
+        }
+    }
+    /// the name, like `unused-variable`
+    pub fn name(self) -> &'static str {
+        match self {
+            WarningKind::UnusedVariable => "unused-variable",
+            WarningKind::BadAllowComment => "bad-allow-comment",
+            WarningKind::UnusedParameter => "unused-parameter",
+            WarningKind::UnusedFunction => "unused-function",
+            WarningKind::MissingReturn => "missing-return",
+            WarningKind::UnreachableCode => "unreachable-code",
+        }
+    }
+    /// look up a warning by its code or its name
+    pub fn from_name(name: &str) -> Option<WarningKind> {
+        WarningKind::ALL
+            .iter()
+            .copied()
+            .find(|k| k.code().eq_ignore_ascii_case(name) || k.name() == name)
+    }
+}
+
+/// something that is probably a mistake but still compiles
+#[derive(Debug, Clone)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Warning {
+    /// the diagnostic for the warning. with `-Werror` it is an error
+    pub fn diagnostic(&self, input_code: &str, as_error: bool) -> Diagnostic {
+        let severity = if as_error {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        Diagnostic::at(
+            severity,
+            self.kind.code(),
+            self.message.clone(),
+            input_code,
+            self.span,
+        )
+    }
+}
+
+/// which warnings are turned on. they all start on. set from the `-W` flags
+#[derive(Debug, Default, Clone)]
+pub struct WarningOptions {
+    disabled: HashSet<WarningKind>,
+    /// turn warnings into errors (`-Werror`)
+    pub as_errors: bool,
+}
+
+impl WarningOptions {
+    /// turn a warning on or off
+    pub fn set(&mut self, kind: WarningKind, on: bool) {
+        if on {
+            self.disabled.remove(&kind);
+        } else {
+            self.disabled.insert(kind);
+        }
+    }
+    pub fn is_on(&self, kind: WarningKind) -> bool {
+        !self.disabled.contains(&kind)
+    }
+}
+
+/// the warnings turned off in the source code with comments.
+/// `{ez:allow name, ...}` turns them off for the line it is on and the line after it.
+/// `{ez:allow-file name, ...}` turns them off for the whole file
+#[derive(Debug)]
+pub struct Suppressions {
+    file: HashSet<WarningKind>,
+    /// the line (counting from 0) and the warning that is off on it
+    lines: HashSet<(usize, WarningKind)>,
+}
+
+impl Suppressions {
+    /// read the `{ez: ...}` comments that the lexer found. comments that don't make sense give back warnings
+    pub fn from_comments(comments: &[(String, Span)], input_code: &str) -> (Self, Vec<Warning>) {
+        let mut suppressions = Suppressions {
+            file: HashSet::new(),
+            lines: HashSet::new(),
+        };
+        let mut warnings = Vec::new();
+        for (text, span) in comments {
+            let (whole_file, names) = if let Some(names) = text.strip_prefix("allow-file ") {
+                (true, names)
+            } else if let Some(names) = text.strip_prefix("allow ") {
+                (false, names)
+            } else {
+                warnings.push(Warning {
+                    kind: WarningKind::BadAllowComment,
+                    message: format!("unknown `{{ez: ...}}` comment: `{}`\nHint: use `{{ez:allow name}}` or `{{ez:allow-file name}}`", text),
+                    span: *span,
+                });
+                continue;
+            };
+            let line = line_of(input_code, span.end.saturating_sub(1));
+            for name in names.split(',').map(str::trim) {
+                match WarningKind::from_name(name) {
+                    Some(kind) if whole_file => {
+                        suppressions.file.insert(kind);
+                    }
+                    Some(kind) => {
+                        suppressions.lines.insert((line, kind));
+                        suppressions.lines.insert((line + 1, kind));
+                    }
+                    None => warnings.push(Warning {
+                        kind: WarningKind::BadAllowComment,
+                        message: format!("there is no warning called `{}`", name),
+                        span: *span,
+                    }),
+                }
+            }
+        }
+        (suppressions, warnings)
+    }
+    /// is a warning turned off by a comment
+    pub fn allows(&self, warning: &Warning, input_code: &str) -> bool {
+        self.file.contains(&warning.kind)
+            || self
+                .lines
+                .contains(&(line_of(input_code, warning.span.start), warning.kind))
+    }
+}
+
+/// the line (counting from 0) that a char index is on
+fn line_of(input_code: &str, pos: u32) -> usize {
+    input_code
+        .chars()
+        .take(pos as usize)
+        .filter(|c| *c == '\n')
+        .count()
+}
 
 impl LexError {
-    pub fn print_the_error(&self, input_code: &str) -> String {
+    pub fn code(&self) -> &'static str {
         match self {
-            LexError::UnexpectedChar(char_unex, pos) => format!(
-                "Lexer Error: Unexpected Char: `{}`\n{}",
-                char_unex,
-                special_error_printing_with_caret(&input_code, pos)
-            ),
+            LexError::UnexpectedChar(..) => "E001",
+            LexError::CharLitNotOneCodePoint(..) => "E002",
         }
     }
+    /// the diagnostic for the error, with the place in the code that it happened
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
+        let (message, span) = match self {
+            LexError::UnexpectedChar(char_unex, span) => (
+                format!("Lexer Error: Unexpected Char: `{}`", char_unex),
+                span,
+            ),
+            LexError::CharLitNotOneCodePoint(span) => (
+                String::from("Lexer Error: A char literal must hold exactly one Unicode code point\nHint: ez chars are code points, and strings are arrays of code points that get printed as UTF-8. Use a string for text made of more than one code point: Ex `set s to \"é\".`"),
+                span,
+            ),
+        };
+        Diagnostic::at(Severity::Error, self.code(), message, input_code, *span)
+    }
 }
 
 impl ParserError {
-    /// a method to print a parser error
-    pub fn print_the_error(&self, input_code: &str) -> String {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::ExectedOneFoundAnother { .. } => "E101",
+        }
+    }
+    /// the diagnostic for the error, with the place in the code that it happened
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
         match self {
             ParserError::ExectedOneFoundAnother {
                 expected,
                 found,
-                pos,
-            } => format!(
-                "Parser Error: expected {}, found {}\n{}",
-                expected,
-                found,
-                special_error_printing_with_caret(&input_code, &pos)
+                span,
+            } => Diagnostic::at(
+                Severity::Error,
+                self.code(),
+                format!("Parser Error: expected {}, found {}", expected, found),
+                input_code,
+                *span,
             ),
         }
     }
 }
 
-/// special error fancy printing
-fn special_error_printing_with_caret(input_code: &str, pos: &u32) -> String {
+impl ModuleError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModuleError::CannotRead(..) => "E301",
+            ModuleError::Cycle(..) => "E302",
+            ModuleError::TopLevelCode(..) => "E303",
+            ModuleError::SameExport(..) => "E304",
+            ModuleError::TwoStarts(..) => "E305",
+        }
+    }
+    /// the diagnostic for the error, with the `use` (or the code) that it is about
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
+        Diagnostic::at(
+            Severity::Error,
+            self.code(),
+            self.to_string(),
+            input_code,
+            self.span(),
+        )
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModuleError::CannotRead(path, why, _) => write!(f, "Module Error: cannot read the used file `{}`: {}", path, why),
+            ModuleError::Cycle(paths, _) => write!(f, "Module Error: these files use each other in a circle: {}\nHint: move what they share into a file that they both use", paths.join(" -> ")),
+            ModuleError::TopLevelCode(path, _) => write!(f, "Module Error: `{}` is used by another file, so it can only have functions at the top level. this code would never run", path),
+            ModuleError::TwoStarts(other, _) => write!(f, "Module Error: this file and `{}` both have code outside of functions. the program can only start in one of them", other),
+            ModuleError::SameExport(name, other, _) => write!(f, "Module Error: the function {} is exported here and in `{}`. exported functions keep their names, so the exports of all files share one namespace and two files can't export the same name", name, other),
+        }
+    }
+}
+
+impl RuntimeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::UnknownExternal(..) => "E501",
+            RuntimeError::BadAddress(..) => "E502",
+            RuntimeError::StackOverflow(..) => "E503",
+            RuntimeError::EndOfInput(..) => "E504",
+        }
+    }
+    /// the diagnostic for the error, with the place in the code that it happened
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
+        Diagnostic::at(
+            Severity::Error,
+            self.code(),
+            self.to_string(),
+            input_code,
+            self.span(),
+        )
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::UnknownExternal(name, _) => write!(f, "Runtime Error: the external function {} isn't in the standard library or in an ez file, so the interpreter can't call it\nHint: run it without --interpret, and give ezc the C file or object that has the function", name),
+            RuntimeError::BadAddress(addr, _) => write!(f, "Runtime Error: there is no memory at the address {:#x}", addr),
+            RuntimeError::StackOverflow(_) => write!(f, "Runtime Error: more than {} calls are inside each other", crate::interpret::MAX_DEPTH),
+            RuntimeError::EndOfInput(_) => write!(f, "Runtime Error: InputLine was called, but there is nothing left to read"),
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl JitError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            JitError::UnknownExternal(..) => "E501",
+            JitError::EndOfInput => "E504",
+            JitError::Assemble(..) => "E505",
+            JitError::Memory(..) => "E506",
+        }
+    }
+    /// the diagnostic for the error. `input_code` is only used by the errors about a place in the code
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
+        match self {
+            JitError::UnknownExternal(_, span) => Diagnostic::at(
+                Severity::Error,
+                self.code(),
+                self.to_string(),
+                input_code,
+                *span,
+            ),
+            _ => Diagnostic::error(self.code(), self.to_string()),
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+impl fmt::Display for JitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JitError::UnknownExternal(name, _) => write!(f, "Runtime Error: the external function {} isn't in the standard library or in an ez file, so it can't be run inside ezc\nHint: give ezc the C file or object that has the function, then the program is linked and run like with -o", name),
+            JitError::EndOfInput => write!(f, "Runtime Error: InputLine was called, but there is nothing left to read"),
+            JitError::Assemble(e) => write!(f, "Internal Error: {}\nHint: this is a bug in ezc. the program can still be run with --interpret", e),
+            JitError::Memory(e) => write!(f, "Runtime Error: cannot get memory to run the program in: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {} of the assembly, `{}`, can't be assembled: {}",
+            self.line, self.text, self.why
+        )
+    }
+}
+
+impl AnalysisError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AnalysisError::DoubleSet(..) => "E201",
+            AnalysisError::VarNotExist(..) => "E202",
+            AnalysisError::NumberTooBig(..) => "E203",
+            AnalysisError::SetInLoop(..) => "E204",
+            AnalysisError::BreakWithoutLoop(..) => "E205",
+            AnalysisError::ReturnOutSideOfFunc(..) => "E206",
+            AnalysisError::FuncAlreadyExists(..) => "E207",
+            AnalysisError::SameArgForFunction(..) => "E208",
+            AnalysisError::FuncCalledWithWrongArgsType(..) => "E209",
+            AnalysisError::FuncCalledButNoExist(..) => "E210",
+            AnalysisError::CannotChangeSomethingToArray(..) => "E211",
+            AnalysisError::ReturnValueFromNothingFunc(..) => "E212",
+            AnalysisError::ReturnWithoutValue(..) => "E213",
+            AnalysisError::NotAnArray(..) => "E214",
+            AnalysisError::NotAPointer(..) => "E215",
+            AnalysisError::ArrayInArithmetic(..) => "E216",
+            AnalysisError::NothingUsedAsValue(..) => "E217",
+            AnalysisError::MismatchedTypes(..) => "E218",
+            AnalysisError::VariadicFuncCalledWithTooFewArgs(..) => "E219",
+            AnalysisError::TooManyArgs(..) => "E220",
+            AnalysisError::TooManyParams(..) => "E221",
+        }
+    }
+    /// the diagnostic for the error, with the place in the code that it happened
+    pub fn diagnostic(&self, input_code: &str) -> Diagnostic {
+        Diagnostic::at(
+            Severity::Error,
+            self.code(),
+            self.to_string(),
+            input_code,
+            self.span(),
+        )
+    }
+}
+
+/// find where a span is. the span is in char indexes (not byte indexes) because that is what the lexer counts in
+fn locate(input_code: &str, span: Span) -> Location {
+    let pos = span.start as usize;
     let mut until_pos_counter = 0;
-    let mut special_line: &str = "";
-    let mut special_row = 0;
-    let mut special_col = 0;
-    for (i, line) in input_code.lines().enumerate() {
-        until_pos_counter += line.len() + 1;
-        if until_pos_counter >= *pos as usize {
-            special_line = line;
-            special_row = i;
-            special_col = *pos as usize + line.len() - until_pos_counter + 2;
+    let mut location = Location {
+        span,
+        line: 1,
+        column: 1,
+        source_line: String::new(),
+    };
+    // split on '\n' instead of using `lines` so that a '\r' from CRLF files still counts as a char
+    for (i, line) in input_code.split('\n').enumerate() {
+        let line_len = line.chars().count();
+        until_pos_counter += line_len + 1;
+        if until_pos_counter > pos {
+            location.source_line = line.strip_suffix('\r').unwrap_or(line).to_string();
+            location.line = i + 1;
+            location.column = pos + line_len + 2 - until_pos_counter;
             break;
         }
     }
-    format!(
-        "{}:{}:\n{}\n{}",
-        special_row + 1,
-        special_col,
-        special_line,
-        up_caret(&special_col)
-    )
+    location
 }
 
-/// A function to put an up caret under a bad code sample for coolness
-fn up_caret(num: &usize) -> String {
-    let mut res = String::new();
-    for _ in 0..(num - 1) {
+/// A function to put an up caret under a bad code sample for coolness. tabs before the caret are kept as tabs so it lines up whatever the tab width is.
+/// `len` is how many chars to put carets under. they stop at the end of the line
+fn up_caret(line: &str, col: usize, len: usize, color: bool) -> String {
+    let mut res: String = line
+        .chars()
+        .take(col - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    // if the line is shorter than the column (the error is at the end of the line) pad it out
+    for _ in res.chars().count()..(col - 1) {
         res += " ";
     }
-    res += "\x1B[31;1m^\x1B[0m";
+    let len = len.min(line.chars().count().saturating_sub(col - 1)).max(1);
+    if color {
+        res += "\x1B[31;1m";
+    }
+    for _ in 0..len {
+        res += "^";
+    }
+    if color {
+        res += "\x1B[0m";
+    }
     res
 }
 
 impl fmt::Display for AnalysisError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            AnalysisError::FuncAlreadyExists(s) => write!(f, "Analysis Error: function is declared twice: {}", s),
-            AnalysisError::CannotChangeSomethingToArray(s,t) =>  write!(f, "Analysis Error: cannot set \"{}\" to an array",
+            AnalysisError::FuncAlreadyExists(s, _) => write!(f, "Analysis Error: function is declared twice: {}", s),
+            AnalysisError::CannotChangeSomethingToArray(s, t, _) =>  write!(f, "Analysis Error: cannot set \"{}\" to an array",
                 match t {
                   TypeOfSetOrChange::ChangeIden => s.clone(),
                   TypeOfSetOrChange::ChangePtrDeref => format!("@{}", s),
-                  TypeOfSetOrChange::ChangeArrIndex(e) => format!("{}[{:?}]", s, e),
+                  TypeOfSetOrChange::ChangeArrIndex(e) => format!("{}[{}]", s, e),
                   _ => unreachable!()
                 }
             ),
-            AnalysisError::SameArgForFunction(s) => match s {
+            AnalysisError::SameArgForFunction(s, _) => match s {
                     crate::ast::Type::Num(name) => write!(f, "Analysis Error: the same arg was used in a function definition: {}", name) ,
                     crate::ast::Type::ArrNum(name, num) => write!(f, "Analysis Error: the same arg was used in a function definition: [{}]{}", num,name),
                 }
-            AnalysisError::BreakWithoutLoop => write!(f, "Analysis Error: there was a break statement outside of a loop."),
-            AnalysisError::DoubleSet(v) => write!(f, "Analysis Error: the same variable `{}` was set twice. \nHint: use `change` to change the value of the variable once it is set: Ex `set x to 0. change x to 4.`", v),
-            AnalysisError::VarNotExist(v) => write!(f, "Analysis Error: the variable `{}` was used, but it doesn't exist in this scope.", v),
-            AnalysisError::NumberTooBig(num) => write!(f, "Analysis Error: Number too big: `{}`", num),
-            AnalysisError::SetInLoop => write!(f, "A set statement was used in a loop. Not allowed."),
-            AnalysisError::ReturnOutSideOfFunc => write!(f, "A return statement was used outside of a function. Not allowed."),
-            AnalysisError::FuncCalledWithWrongArgsType(name, should, had) => write!(f, "The function {} was called with {:?} args but it takes {:?} args.", name, had,should),
-            AnalysisError::FuncCalledButNoExist(name) => write!(f, "The function {} was called but it does not exist.",name),
+            AnalysisError::BreakWithoutLoop(_) => write!(f, "Analysis Error: there was a break statement outside of a loop."),
+            AnalysisError::DoubleSet(v, _) => write!(f, "Analysis Error: the same variable `{}` was set twice. \nHint: use `change` to change the value of the variable once it is set: Ex `set x to 0. change x to 4.`", v),
+            AnalysisError::VarNotExist(v, _) => write!(f, "Analysis Error: the variable `{}` was used, but it doesn't exist in this scope.", v),
+            AnalysisError::NumberTooBig(num, _) => write!(f, "Analysis Error: Number too big: `{}`", num),
+            AnalysisError::SetInLoop(_) => write!(f, "A set statement was used in a loop. Not allowed."),
+            AnalysisError::ReturnOutSideOfFunc(_) => write!(f, "A return statement was used outside of a function. Not allowed."),
+            AnalysisError::FuncCalledWithWrongArgsType(name, should, had, _) if should.len() != had.len() => write!(f, "The function {} takes {} argument{} but it was called with {}.", name, should.len(), if should.len() == 1 { "" } else { "s" }, had.len()),
+            AnalysisError::FuncCalledWithWrongArgsType(name, should, had, _) => write!(f, "The function {} was called with {:?} args but it takes {:?} args.", name, had,should),
+            AnalysisError::FuncCalledButNoExist(name, _) => write!(f, "The function {} was called but it does not exist.",name),
+            AnalysisError::ReturnValueFromNothingFunc(name, _) => write!(f, "Analysis Error: the function `{}` returns nothing, but a value is returned from it.\nHint: use `return.` to return without a value", name),
+            AnalysisError::ReturnWithoutValue(name, _) => write!(f, "Analysis Error: `return.` was used in the function `{}`, but it returns a value.\nHint: return a value (Ex `return 0.`) or say that the function `returns nothing`", name),
+            AnalysisError::NotAnArray(expr, ty, _) => write!(f, "Analysis Error: only arrays can be indexed, but in `{}` the thing being indexed is {}", expr, ty),
+            AnalysisError::NotAPointer(expr, ty, _) => write!(f, "Analysis Error: `@` only works on pointers and arrays, but in `{}` it is used on {}", expr, ty),
+            AnalysisError::ArrayInArithmetic(array, expr, _) => write!(f, "Analysis Error: `{}` is an array, so it can't be used in `{}`.\nHint: use one of its elements (Ex `{}[1]`)", array, expr, array),
+            AnalysisError::NothingUsedAsValue(expr, _) => write!(f, "Analysis Error: `{}` returns nothing, so it can't be used as a value.\nHint: call it on its own (Ex `call {}.`)", expr, expr),
+            AnalysisError::MismatchedTypes(expr, should, is, _) => write!(f, "Analysis Error: `{}` is {}, but it should be {}", expr, is, should),
+            AnalysisError::VariadicFuncCalledWithTooFewArgs(name, should, had, _) => write!(f, "The function {} takes at least {} argument{} but it was called with {}.", name, should, if *should == 1 { "" } else { "s" }, had),
+            AnalysisError::TooManyParams(name, takes, _) => write!(f, "The function {} takes {} arguments, but only {} can be passed.", name, takes, MAX_ARGS),
+            AnalysisError::TooManyArgs(name, had, _) => write!(f, "The function {} was called with {} arguments, but only {} can be passed.", name, had, MAX_ARGS),
 
         }
     }
 }
+/// say what a type is in words
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "a number"),
+            Type::Arr(_) => write!(f, "an array"),
+            Type::Any => write!(f, "anything"),
+            Type::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+/// print an expression like it was written in the source
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Number(n, _) => write!(f, "{}", n),
+            Expr::Iden(i, _) => write!(f, "{}", i),
+            Expr::BinOp { lhs, op, rhs, .. } => write!(f, "({} {} {})", lhs, op, rhs),
+            Expr::FuncCall {
+                module,
+                func_name,
+                args,
+                ..
+            } => {
+                if let Some(module) = module {
+                    write!(f, "{}'s ", module)?;
+                }
+                write!(f, "{}(", func_name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::DerefPtr(p, _) => write!(f, "@{}", p),
+            Expr::AccessArray(a, e, _) => write!(f, "{}[{}]", a, e),
+        }
+    }
+}
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Expr(e) => write!(f, "{}", e),
+            Val::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinOp::Add => write!(f, "+"),
+            BinOp::Sub => write!(f, "-"),
+            BinOp::Mul => write!(f, "*"),
+            BinOp::Gt => write!(f, ">"),
+            BinOp::Lt => write!(f, "<"),
+            BinOp::Equ => write!(f, "="),
+            BinOp::Lte => write!(f, "<="),
+            BinOp::Gte => write!(f, ">="),
+            BinOp::Ne => write!(f, "!="),
+            BinOp::And => write!(f, "and"),
+            BinOp::Or => write!(f, "or"),
+        }
+    }
+}
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             EndOfLine => write!(f, "'.'"),
+            Ellipsis => write!(f, "'...'"),
+            Possessive => write!(f, "\"'s\""),
             Kset => write!(f, "\"set\""),
             Kextern => write!(f, "\"external\""),
+            Kcall => write!(f, "\"call\""),
+            Kuse => write!(f, "\"use\""),
+            Path(p) => write!(f, "the path \"{}\"", p),
             Kexport => write!(f, "\"export\""),
             Kchange => write!(f, "\"change\""),
             Comma => write!(f, "','"),
@@ -134,3 +847,117 @@ impl fmt::Display for Token {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn caret_with_tabs_and_crlf() {
+        let input = "set x to 5.\r\n\tset y to $.";
+        let mut tokenizer = crate::lexer::Tokenizer::new();
+        let err = tokenizer.lex(&String::from(input)).0.unwrap_err();
+        let printer = super::Printer {
+            color: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            printer.render(&err[0].diagnostic(input)),
+            "\x1B[31;1mERROR: \x1B[0mLexer Error: Unexpected Char: `$`\n2:11:\n\tset y to $.\n\t         \x1B[31;1m^\x1B[0m"
+        );
+    }
+    #[test]
+    fn warning_suppressions() {
+        use super::{Suppressions, Warning, WarningKind};
+        let input = "{ez:allow unused-variable}\nset x to 1.\nset y to 2. {ez:allow W001, nope}\n\nset z to 3.\n{ez:deny W001}";
+        let mut tokenizer = crate::lexer::Tokenizer::new();
+        tokenizer.lex(&String::from(input)).0.unwrap();
+        let (suppressions, warnings) =
+            Suppressions::from_comments(&tokenizer.special_comments, input);
+        let kinds: Vec<WarningKind> = warnings.iter().map(|w| w.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![WarningKind::BadAllowComment, WarningKind::BadAllowComment]
+        );
+        let unused = |start| Warning {
+            kind: WarningKind::UnusedVariable,
+            message: String::new(),
+            span: crate::lexer::Span {
+                file: 0,
+                start,
+                end: start + 1,
+            },
+        };
+        assert!(suppressions.allows(&unused(31), input));
+        assert!(suppressions.allows(&unused(43), input));
+        assert!(!suppressions.allows(&unused(79), input));
+        assert_eq!(
+            WarningKind::from_name("w001"),
+            WarningKind::from_name("unused-variable")
+        );
+    }
+    #[test]
+    fn diagnostics_limit() {
+        let mut diagnostics = super::Diagnostics::new(2);
+        for i in 0..5 {
+            diagnostics.push(super::Diagnostic::error("E401", i.to_string()));
+        }
+        let (shown, hidden) = diagnostics.shown();
+        let messages: Vec<&str> = shown.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!((messages, hidden), (vec!["0", "1"], 3));
+        diagnostics.limit = 0;
+        assert_eq!(diagnostics.shown().1, 0);
+    }
+    #[test]
+    fn analysis_error_caret() {
+        let input = "set x to 5.\nset y to x + zed.";
+        let mut tokenizer = crate::lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from(input));
+        let mut ast = crate::parser::parse(output.0.unwrap(), output.1).unwrap();
+        let err = crate::analyse::analize(&mut ast).unwrap_err();
+        let printer = super::Printer::default();
+        assert_eq!(
+            printer.render(&err[0].diagnostic(input)),
+            "ERROR: Analysis Error: the variable `zed` was used, but it doesn't exist in this scope.\n2:14:\nset y to x + zed.\n             ^^^"
+        );
+    }
+    #[test]
+    fn json_diagnostics() {
+        use super::{Diagnostic, ErrorFormat, Printer, Severity};
+        let input = "set x to 5.\nset y to \"zed\" + x.";
+        let span = crate::lexer::Span {
+            file: 0,
+            start: 21,
+            end: 26,
+        };
+        let d = Diagnostic::at(
+            Severity::Warning,
+            "W001",
+            String::from("a \"string\"\nHint: x"),
+            input,
+            span,
+        )
+        .in_file(std::path::Path::new("a.ez"));
+        let printer = Printer {
+            format: ErrorFormat::Json,
+            color: true,
+            main_file: Some(String::from("a.ez")),
+        };
+        assert_eq!(
+            printer.render(&d),
+            r#"{"severity":"warning","code":"W001","message":"a \"string\"\nHint: x","file":"a.ez","line":2,"column":10,"span":{"start":21,"end":26}}"#
+        );
+        assert_eq!(
+            printer.render(&Diagnostic::error("E404", "no")),
+            r#"{"severity":"error","code":"E404","message":"no","file":null,"line":null,"column":null,"span":null}"#
+        );
+        // the only file isn't named for people, and warnings say which one they are
+        let printer = Printer {
+            main_file: Some(String::from("a.ez")),
+            ..Default::default()
+        };
+        assert!(printer
+            .render(&d)
+            .starts_with("WARNING: [W001 unused-variable] a \"string\""));
+        let printer = Printer::default();
+        assert!(printer.render(&d).starts_with("WARNING: In `a.ez`: [W001"));
+    }
+}
@@ -0,0 +1,176 @@
+//! making C headers for the exported functions, so C code can call them
+
+use crate::ast::{AstNode, AstRoot, Type};
+use std::fmt::Write;
+
+/// the C header for all of the `export function`s in `tree`. `name` is what the header is called (Ex `lib.h`) and is used for the include guard
+pub fn gen_header(tree: &AstRoot, name: &str) -> String {
+    let mut guard: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // a C name can't start with a digit
+    if guard.starts_with(|c: char| c.is_ascii_digit()) {
+        guard.insert_str(0, "EZ_");
+    }
+    let mut f = String::new();
+    writeln!(f, "#ifndef {}\n#define {}\n", guard, guard).unwrap();
+    writeln!(f, "#include <stdint.h>\n").unwrap();
+    for node in &tree.tree {
+        if let AstNode::Func {
+            name,
+            args,
+            export: true,
+            returns_nothing,
+            ..
+        } = node
+        {
+            // exported functions aren't mangled so C can use the name as is
+            writeln!(
+                f,
+                "{} {}({});",
+                if *returns_nothing { "void" } else { "int64_t" },
+                name,
+                c_params(args)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(f, "\n#endif /* {} */", guard).unwrap();
+    f
+}
+
+/// the C keywords, and `int64_t` from the header. they can be ez names, but not the names of C parameters
+const C_KEYWORDS: [&str; 45] = [
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "int64_t",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+];
+
+/// the parameter list of a C prototype. every number is an int64_t and arrays are pointers to them
+fn c_params(args: &[Type]) -> String {
+    if args.is_empty() {
+        return String::from("void");
+    }
+    let names: Vec<&str> = args
+        .iter()
+        .map(|a| match a {
+            Type::Num(name) | Type::ArrNum(name, _) => name.as_str(),
+        })
+        .collect();
+    args.iter()
+        .zip(&names)
+        .map(|(a, name)| {
+            // a parameter called `int` gets a `_` on the end, and more if another one already has that name
+            let mut name = name.to_string();
+            if C_KEYWORDS.contains(&name.as_str()) {
+                name.push('_');
+                while names.contains(&name.as_str()) {
+                    name.push('_');
+                }
+            }
+            match a {
+                Type::Num(_) => format!("int64_t {}", name),
+                Type::ArrNum(..) => format!("int64_t *{}", name),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn header_for_exports() {
+        use crate::analyse;
+        use crate::ast::{AstNode, Type};
+        use crate::header;
+        use crate::lexer;
+        use crate::parser;
+
+        let input = "export function Fib_rec(n), return n.!
+function Hidden(), return 0.!
+export function Show(a, b) returns nothing, return.!
+export function Zero(), return Hidden().!";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let correct_header = "#ifndef LIB_H
+#define LIB_H
+
+#include <stdint.h>
+
+int64_t Fib_rec(int64_t n);
+void Show(int64_t a, int64_t b);
+int64_t Zero(void);
+
+#endif /* LIB_H */
+";
+        assert_eq!(header::gen_header(&ast, "lib.h"), correct_header);
+
+        // arrays can't be written as parameters yet, but they are pointers when they can
+        if let AstNode::Func { args, .. } = &mut ast.tree[0] {
+            args.push(Type::ArrNum(String::from("arr"), String::from("3")));
+        }
+        assert!(
+            header::gen_header(&ast, "lib.h").contains("int64_t Fib_rec(int64_t n, int64_t *arr);")
+        );
+
+        // the header is still C when the file name starts with a digit or a parameter has the name of a C keyword
+        let input = "export function Pick(int, char, int_), return int.!";
+        let output = lexer::Tokenizer::new().lex(&String::from(input));
+        let mut ast = parser::parse(output.0.unwrap(), output.1).unwrap();
+        analyse::analize(&mut ast).unwrap();
+        let header = header::gen_header(&ast, "1lib.h");
+        assert!(header.starts_with("#ifndef EZ_1LIB_H\n#define EZ_1LIB_H\n"));
+        assert!(header.contains("int64_t Pick(int64_t int__, int64_t char_, int64_t int_);"));
+    }
+}
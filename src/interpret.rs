@@ -0,0 +1,679 @@
+//! a tree-walking interpreter for analysed ez code. it does what the assembly from `codegen` does, and has the functions
+//! from `lib.zig` built in, so programs can run without nasm, ld or the standard library
+
+use crate::ast::{AstNode, BinOp, Expr, TypeOfSetOrChange, Val};
+use crate::errors::{Diagnostic, Diagnostics};
+use crate::lexer::Span;
+use crate::CompiledFile;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{BufRead, Write};
+
+/// where the memory for arrays made by `set` starts. it is given back when the function that made it returns, like a stack
+const STACK_START: i64 = 0x10000;
+/// where the memory that lives until the end starts, like the strings from `InputLine`
+const HEAP_START: i64 = 0x4000_0000_0000;
+/// the most calls that can be inside each other. the driver runs the interpreter on a thread with a big stack so this fits
+pub const MAX_DEPTH: usize = 10_000;
+/// how big the stack of the thread that runs the interpreter has to be for `MAX_DEPTH` calls
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// an error while the program is running
+#[derive(Debug, PartialEq)]
+pub enum RuntimeError {
+    /// an external function that isn't in the standard library or in one of the files
+    UnknownExternal(String, Span),
+    /// reading or writing memory that isn't there. the address is in bytes, like in the assembly
+    BadAddress(i64, Span),
+    /// too many calls inside each other
+    StackOverflow(Span),
+    /// `InputLine` when there is nothing left to read
+    EndOfInput(Span),
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::UnknownExternal(_, span)
+            | RuntimeError::BadAddress(_, span)
+            | RuntimeError::StackOverflow(span)
+            | RuntimeError::EndOfInput(span) => *span,
+        }
+    }
+}
+
+/// the memory of the program, in 8 byte words. addresses are in bytes so pointer math works like it does in the assembly
+#[derive(Debug, Default)]
+struct Memory {
+    stack: Vec<i64>,
+    heap: Vec<i64>,
+}
+
+impl Memory {
+    /// the word for an address, if it is there
+    fn word(&mut self, addr: i64) -> Option<&mut i64> {
+        if addr % 8 != 0 {
+            return None;
+        }
+        let (region, start) = if addr >= HEAP_START {
+            (&mut self.heap, HEAP_START)
+        } else {
+            (&mut self.stack, STACK_START)
+        };
+        let index = usize::try_from((addr - start) / 8).ok()?;
+        region.get_mut(index)
+    }
+    /// make an array like the assembly does: a pointer to itself, the length and then the elements. gives back its address
+    fn alloc_array(&mut self, elements: &[i64], heap: bool) -> i64 {
+        let (region, start) = if heap {
+            (&mut self.heap, HEAP_START)
+        } else {
+            (&mut self.stack, STACK_START)
+        };
+        let addr = start + region.len() as i64 * 8;
+        region.push(addr);
+        region.push(elements.len() as i64);
+        region.extend_from_slice(elements);
+        addr
+    }
+}
+
+/// what running a statement did
+enum Flow {
+    Next,
+    Break,
+    Return(i64),
+}
+
+/// the variables of the top level or of a function call
+#[derive(Debug, Default)]
+struct Frame {
+    vars: HashMap<String, i64>,
+    /// the memory that was made for an array variable and how many elements fit in it. setting the variable to another
+    /// array that fits puts it in the same place, like the assembly does
+    arrays: HashMap<String, (i64, usize)>,
+}
+
+/// what a program has made so far: its memory and the variables of its top level. the repl gives it from one
+/// interpreter to the next, so each input goes on from the ones before it
+#[derive(Debug, Default)]
+pub struct State {
+    memory: Memory,
+    globals: Frame,
+}
+
+/// a function in one of the files
+struct Function<'a> {
+    /// which file it is in
+    file: usize,
+    args: &'a [crate::ast::Type],
+    body: &'a [AstNode],
+}
+
+/// runs the files of a program
+pub struct Interpreter<'a> {
+    files: &'a [CompiledFile],
+    /// the exported functions of all of the files. they keep their names, so there is only one of each
+    exported: HashMap<&'a str, Function<'a>>,
+    /// the functions of each file, by name
+    functions: Vec<HashMap<&'a str, Function<'a>>>,
+    state: State,
+    depth: usize,
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+}
+
+impl std::fmt::Debug for Interpreter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interpreter")
+            .field("state", &self.state)
+            .field("depth", &self.depth)
+            .finish_non_exhaustive()
+    }
+}
+
+/// run the program made of `files`, starting at the top level code of the main one. what it prints goes to `output`
+/// and `InputLine` reads from `input`. the error stops the program, so there is only ever one
+pub fn run(
+    files: &[CompiledFile],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), Diagnostics> {
+    let mut interpreter = Interpreter::new(files, input, output);
+    let result = interpreter.run_main();
+    // a program that printed something and then failed still shows what it printed
+    let _ = interpreter.output.flush();
+    result.map_err(|e| {
+        let mut diagnostics = Diagnostics::new(0);
+        diagnostics.push(*e);
+        diagnostics
+    })
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(
+        files: &'a [CompiledFile],
+        input: &'a mut dyn BufRead,
+        output: &'a mut dyn Write,
+    ) -> Self {
+        Interpreter::with_state(files, State::default(), input, output)
+    }
+    /// an interpreter that goes on from what another one made
+    pub fn with_state(
+        files: &'a [CompiledFile],
+        state: State,
+        input: &'a mut dyn BufRead,
+        output: &'a mut dyn Write,
+    ) -> Self {
+        let mut exported = HashMap::new();
+        let mut functions = Vec::new();
+        for (i, file) in files.iter().enumerate() {
+            let mut in_file = HashMap::new();
+            for node in &file.ast.tree {
+                if let AstNode::Func {
+                    name,
+                    args,
+                    body,
+                    export,
+                    ..
+                } = node
+                {
+                    let function = || Function {
+                        file: i,
+                        args,
+                        body,
+                    };
+                    if *export {
+                        exported.insert(name.as_str(), function());
+                    }
+                    in_file.insert(name.as_str(), function());
+                }
+            }
+            functions.push(in_file);
+        }
+        Interpreter {
+            files,
+            exported,
+            functions,
+            state,
+            depth: 0,
+            input,
+            output,
+        }
+    }
+    /// run the top level code of the main file. the error says which file it happened in
+    pub fn run_main(&mut self) -> Result<(), Box<Diagnostic>> {
+        let main = match self.files.iter().position(|f| f.main) {
+            Some(main) => main,
+            None => return Ok(()),
+        };
+        let files = self.files;
+        self.run_top_level(&files[main].ast.tree, main)
+    }
+    /// run top level code that is in the file `file`. the functions in it have to be in the file already
+    pub fn run_top_level(&mut self, tree: &[AstNode], file: usize) -> Result<(), Box<Diagnostic>> {
+        let mut globals = std::mem::take(&mut self.state.globals);
+        let mut result = Ok(());
+        for node in tree {
+            if let Err((file, e)) = self.exec(node, file, &mut globals) {
+                result = Err(Box::new(self.diagnostic(file, &e)));
+                break;
+            }
+        }
+        self.state.globals = globals;
+        result
+    }
+    /// the value of an expression at the top level of the file `file`
+    pub fn eval_top_level(&mut self, expr: &Expr, file: usize) -> Result<i64, Box<Diagnostic>> {
+        let mut globals = std::mem::take(&mut self.state.globals);
+        let result = self.eval(expr, file, &mut globals);
+        self.state.globals = globals;
+        result.map_err(|(file, e)| Box::new(self.diagnostic(file, &e)))
+    }
+    /// what the program has made, for another interpreter to go on from
+    pub fn into_state(self) -> State {
+        self.state
+    }
+    fn diagnostic(&self, file: usize, e: &RuntimeError) -> Diagnostic {
+        let file = &self.files[file];
+        e.diagnostic(&file.input).in_file(&file.path)
+    }
+    /// run the statements of a block. an error says which file it is in
+    fn exec_block(
+        &mut self,
+        body: &[AstNode],
+        file: usize,
+        frame: &mut Frame,
+    ) -> Result<Flow, (usize, RuntimeError)> {
+        for node in body {
+            match self.exec(node, file, frame)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+    fn exec(
+        &mut self,
+        node: &AstNode,
+        file: usize,
+        frame: &mut Frame,
+    ) -> Result<Flow, (usize, RuntimeError)> {
+        match node {
+            AstNode::SetOrChange {
+                sete,
+                setor,
+                type_of,
+                span,
+            } => {
+                self.exec_set_or_change(sete, setor, type_of, *span, file, frame)?;
+                Ok(Flow::Next)
+            }
+            AstNode::If { guard, body, .. } => {
+                // the assembly only goes into the body when the guard is exactly 1
+                if self.eval(guard, file, frame)? == 1 {
+                    self.exec_block(body, file, frame)
+                } else {
+                    Ok(Flow::Next)
+                }
+            }
+            AstNode::Loop { body, .. } => loop {
+                match self.exec_block(body, file, frame)? {
+                    Flow::Next => {}
+                    Flow::Break => return Ok(Flow::Next),
+                    flow => return Ok(flow),
+                }
+            },
+            AstNode::Return { val, .. } => {
+                let val = match val {
+                    Some(val) => self.eval(val, file, frame)?,
+                    None => 0,
+                };
+                Ok(Flow::Return(val))
+            }
+            AstNode::Break { .. } => Ok(Flow::Break),
+            AstNode::Call { func_call, .. } => {
+                self.eval(func_call, file, frame)?;
+                Ok(Flow::Next)
+            }
+            AstNode::Func { .. } | AstNode::Extern { .. } | AstNode::Use { .. } => Ok(Flow::Next),
+        }
+    }
+    fn exec_set_or_change(
+        &mut self,
+        sete: &str,
+        setor: &Val,
+        type_of: &TypeOfSetOrChange,
+        span: Span,
+        file: usize,
+        frame: &mut Frame,
+    ) -> Result<(), (usize, RuntimeError)> {
+        let elements = match setor {
+            Val::Expr(e) => {
+                let val = self.eval(e, file, frame)?;
+                match type_of {
+                    TypeOfSetOrChange::SetIden | TypeOfSetOrChange::ChangeIden => {
+                        frame.vars.insert(sete.to_string(), val);
+                    }
+                    TypeOfSetOrChange::ChangePtrDeref => {
+                        let addr = frame.vars[sete];
+                        self.write(addr, val, span, file)?;
+                    }
+                    TypeOfSetOrChange::ChangeArrIndex(index) => {
+                        let index = self.eval(index, file, frame)?;
+                        let addr = element_addr(frame.vars[sete], index);
+                        self.write(addr, val, span, file)?;
+                    }
+                }
+                return Ok(());
+            }
+            Val::Array(items) => items
+                .iter()
+                .map(|e| self.eval(e, file, frame))
+                .collect::<Result<Vec<i64>, _>>()?,
+        };
+        let addr = match frame.arrays.get(sete) {
+            Some(&(addr, fits)) if fits >= elements.len() => {
+                let words = std::iter::once(elements.len() as i64).chain(elements.iter().copied());
+                for (i, word) in words.enumerate() {
+                    self.write(addr + 8 * (i as i64 + 1), word, span, file)?;
+                }
+                addr
+            }
+            _ => {
+                let addr = self.state.memory.alloc_array(&elements, false);
+                frame
+                    .arrays
+                    .insert(sete.to_string(), (addr, elements.len()));
+                addr
+            }
+        };
+        frame.vars.insert(sete.to_string(), addr);
+        Ok(())
+    }
+    fn eval(
+        &mut self,
+        expr: &Expr,
+        file: usize,
+        frame: &mut Frame,
+    ) -> Result<i64, (usize, RuntimeError)> {
+        Ok(match expr {
+            // the analysis made sure it fits
+            Expr::Number(n, _) => n.parse().unwrap(),
+            Expr::Iden(name, _) => frame.vars[name],
+            Expr::BinOp { lhs, op, rhs, .. } => {
+                let lhs = self.eval(lhs, file, frame)?;
+                let rhs = self.eval(rhs, file, frame)?;
+                binop(lhs, op, rhs)
+            }
+            Expr::FuncCall {
+                func_name,
+                args,
+                external,
+                span,
+                ..
+            } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    match arg {
+                        Val::Expr(e) => values.push(self.eval(e, file, frame)?),
+                        Val::Array(_) => unreachable!(),
+                    }
+                }
+                self.call(func_name, external.unwrap_or(true), &values, *span, file)?
+            }
+            Expr::DerefPtr(name, span) => self.read(frame.vars[name], *span, file)?,
+            Expr::AccessArray(name, index, span) => {
+                let index = self.eval(index, file, frame)?;
+                self.read(element_addr(frame.vars[name], index), *span, file)?
+            }
+        })
+    }
+    /// call a function. external ones are the exported ones from the files and the ones from the standard library
+    fn call(
+        &mut self,
+        name: &str,
+        external: bool,
+        args: &[i64],
+        span: Span,
+        file: usize,
+    ) -> Result<i64, (usize, RuntimeError)> {
+        let function = if external {
+            self.exported.get(name)
+        } else {
+            self.functions[file].get(name)
+        };
+        let (callee, params, body) = match function {
+            Some(f) => (f.file, f.args, f.body),
+            None => {
+                return self
+                    .builtin(name, args, span)
+                    .map_err(|e| (file, e))?
+                    .ok_or_else(|| (file, RuntimeError::UnknownExternal(name.to_string(), span)))
+            }
+        };
+        if self.depth == MAX_DEPTH {
+            return Err((file, RuntimeError::StackOverflow(span)));
+        }
+        let mut frame = Frame::default();
+        for (param, val) in params.iter().zip(args) {
+            let name = match param {
+                crate::ast::Type::Num(name) | crate::ast::Type::ArrNum(name, _) => name,
+            };
+            frame.vars.insert(name.clone(), *val);
+        }
+        // the arrays made in the function go away when it returns, like the stack in the assembly
+        let stack_len = self.state.memory.stack.len();
+        self.depth += 1;
+        let flow = self.exec_block(body, callee, &mut frame);
+        self.depth -= 1;
+        self.state.memory.stack.truncate(stack_len);
+        match flow? {
+            Flow::Return(val) => Ok(val),
+            _ => Ok(0),
+        }
+    }
+    /// the functions from `lib.zig`. `None` if there isn't one with this name. like in `lib.zig`, they give back -1 if
+    /// writing fails
+    fn builtin(
+        &mut self,
+        name: &str,
+        args: &[i64],
+        span: Span,
+    ) -> Result<Option<i64>, RuntimeError> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
+        let n = arg(0);
+        let written = match name {
+            "PutString" => self.put_string(n, span)?,
+            "PutStringLine" => self.put_string(n, span)? && self.output.write_all(b"\n").is_ok(),
+            "InputLine" => return self.input_line(span).map(Some),
+            _ => match put_number(self.output, name, n) {
+                Some(written) => written,
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(if written { 0 } else { -1 }))
+    }
+    fn put_string(&mut self, s: i64, span: Span) -> Result<bool, RuntimeError> {
+        let len = self.read_word(s + 8, span)?;
+        for i in 0..len {
+            let c = self.read_word(s + 16 + i * 8, span)?;
+            if !put_code_point(self.output, c) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    /// read a line and give back a string with its code points. the memory for it never goes away
+    fn input_line(&mut self, span: Span) -> Result<i64, RuntimeError> {
+        // a prompt printed before this has to show up before waiting for the line
+        let _ = self.output.flush();
+        let code_points = read_line(self.input).ok_or(RuntimeError::EndOfInput(span))?;
+        Ok(self.state.memory.alloc_array(&code_points, true))
+    }
+    fn read_word(&mut self, addr: i64, span: Span) -> Result<i64, RuntimeError> {
+        self.state
+            .memory
+            .word(addr)
+            .map(|w| *w)
+            .ok_or(RuntimeError::BadAddress(addr, span))
+    }
+    fn read(&mut self, addr: i64, span: Span, file: usize) -> Result<i64, (usize, RuntimeError)> {
+        self.read_word(addr, span).map_err(|e| (file, e))
+    }
+    fn write(
+        &mut self,
+        addr: i64,
+        val: i64,
+        span: Span,
+        file: usize,
+    ) -> Result<(), (usize, RuntimeError)> {
+        match self.state.memory.word(addr) {
+            Some(word) => {
+                *word = val;
+                Ok(())
+            }
+            None => Err((file, RuntimeError::BadAddress(addr, span))),
+        }
+    }
+}
+
+/// the functions from `lib.zig` that write the number they are given. `None` if there isn't one with this name, and
+/// `Some(false)` if writing failed. `jit` has them too
+pub(crate) fn put_number(output: &mut dyn Write, name: &str, n: i64) -> Option<bool> {
+    Some(match name {
+        "PutChar" => put_code_point(output, n),
+        "PutNum" => write!(output, "{}", n).is_ok(),
+        // zig prints negative numbers with a minus in front of the digits
+        "PutNumHex" if n < 0 => write!(output, "0x-{:X}", n.unsigned_abs()).is_ok(),
+        "PutNumHex" => write!(output, "0x{:X}", n).is_ok(),
+        "PutNumBin" if n < 0 => write!(output, "-{:b}", n.unsigned_abs()).is_ok(),
+        "PutNumBin" => write!(output, "{:b}", n).is_ok(),
+        "PutNewLine" => output.write_all(b"\n").is_ok(),
+        _ => return None,
+    })
+}
+
+/// write a code point as UTF-8
+pub(crate) fn put_code_point(output: &mut dyn Write, c: i64) -> bool {
+    let c = match u32::try_from(c).ok().and_then(char::from_u32) {
+        Some(c) => c,
+        None => return false,
+    };
+    let mut buf = [0; 4];
+    output.write_all(c.encode_utf8(&mut buf).as_bytes()).is_ok()
+}
+
+/// read a line for `InputLine`, as code points. `None` if there is nothing left to read
+pub(crate) fn read_line(input: &mut dyn BufRead) -> Option<Vec<i64>> {
+    let mut line = Vec::new();
+    match input.read_until(b'\n', &mut line) {
+        Ok(0) | Err(_) => return None,
+        Ok(_) => {}
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    }
+    Some(
+        String::from_utf8_lossy(&line)
+            .chars()
+            .map(|c| c as i64)
+            .collect(),
+    )
+}
+
+/// where `array[index]` is. index 0 is the length, so the elements start at 1
+fn element_addr(array: i64, index: i64) -> i64 {
+    array.wrapping_add(index.wrapping_add(1).wrapping_mul(8))
+}
+
+/// the registers wrap around, and comparisons give 1 or 0
+fn binop(lhs: i64, op: &BinOp, rhs: i64) -> i64 {
+    match op {
+        BinOp::Add => lhs.wrapping_add(rhs),
+        BinOp::Sub => lhs.wrapping_sub(rhs),
+        BinOp::Mul => lhs.wrapping_mul(rhs),
+        BinOp::And => lhs & rhs,
+        BinOp::Or => lhs | rhs,
+        BinOp::Gt => (lhs > rhs) as i64,
+        BinOp::Lt => (lhs < rhs) as i64,
+        BinOp::Equ => (lhs == rhs) as i64,
+        BinOp::Lte => (lhs <= rhs) as i64,
+        BinOp::Gte => (lhs >= rhs) as i64,
+        BinOp::Ne => (lhs != rhs) as i64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::{compile, compile_files, Options};
+
+    /// compile and run a program with `input` as stdin. gives back what it printed
+    fn run(source: &str, input: &str) -> Result<String, Box<crate::Diagnostic>> {
+        let artifacts = compile(source, &Options::default()).unwrap();
+        let mut output = Vec::new();
+        super::run(&artifacts.files, &mut input.as_bytes(), &mut output)
+            .map_err(|e| Box::new(e.all()[0].clone()))?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn interpret_programs() {
+        let fib = "external function PutNum(n). external function PutChar(c).
+function Fib(n),
+  if n <= 1,
+      return n.
+  !
+  return Fib(n - 1) + Fib(n - 2).
+!
+call PutNum(Fib(10)).
+call PutChar(10).";
+        assert_eq!(run(fib, "").unwrap(), "55\n");
+        // index 0 is the length and the elements start at 1
+        let arrays = "external function PutStringLine(n). external function PutNum(n).
+set z to ['a','b','c'].
+change z[3] to 'a'.
+call PutStringLine(z).
+call PutNum(z[0]).";
+        assert_eq!(run(arrays, "").unwrap(), "aba\n3");
+        let loops = "external function PutNum(n).
+function Count(n) returns nothing,
+  set i to 0.
+  loop,
+    if i >= n,
+      break.
+    !
+    change i to i + 1.
+    if i = 2 or i = 3,
+      call PutNum(i).
+    !
+  !
+!
+call Count(10).
+if 5, call PutNum(5). !
+if 0 - 1 > 0 - 2 and 1, call PutNum(1). !";
+        assert_eq!(run(loops, "").unwrap(), "231");
+    }
+    #[test]
+    fn interpret_memory() {
+        // the variable holds the address of the array, which starts with a pointer to itself
+        let deref = "external function PutNum(n).
+function Peek(p), return @p.!
+set a to [7, 8].
+set p to a.
+change @p to 5.
+change p[1] to 9.
+call PutNum(a[1]).
+call PutNum(Peek(a)).
+call PutNum(Peek(3)).";
+        assert_eq!(run_output_before_error(deref, ""), "95");
+        let err = run(deref, "").unwrap_err();
+        assert_eq!(err.code, "E502");
+        assert_eq!(err.location.unwrap().line, 2);
+    }
+    fn run_output_before_error(source: &str, input: &str) -> String {
+        let artifacts = compile(source, &Options::default()).unwrap();
+        let mut output = Vec::new();
+        let _ = super::run(&artifacts.files, &mut input.as_bytes(), &mut output);
+        String::from_utf8(output).unwrap()
+    }
+    #[test]
+    fn interpret_builtins() {
+        let io = "external function InputLine(). external function PutStringLine(s).
+external function PutNumHex(n). external function PutNumBin(n). external function PutChar(c).
+set line to InputLine().
+call PutStringLine(line).
+call PutNumHex(255). call PutNumBin(0 - 5). call PutChar(233). call PutChar(0 - 1).
+change line to InputLine().";
+        assert_eq!(run_output_before_error(io, "héllo\n"), "héllo\n0xFF-101é");
+        assert_eq!(run(io, "héllo\n").unwrap_err().code, "E504");
+        let unknown = "external function Nope(). call Nope().";
+        let err = run(unknown, "").unwrap_err();
+        assert!(err.message.contains("Nope"), "{}", err.message);
+        let deep = "function F(n), return F(n + 1).! call F(0).";
+        let code = std::thread::Builder::new()
+            .stack_size(super::STACK_SIZE)
+            .spawn(move || run(deep, "").unwrap_err().code)
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(code, "E503");
+    }
+    #[test]
+    fn interpret_many_files() {
+        use std::path::PathBuf;
+        let main = "external function PutNum(n). external function AddOne(a).
+function Helper(), return 10.!
+call PutNum(AddOne(Helper())).";
+        let lib = "function Helper(), return 1.! export function AddOne(a), return a + Helper().!";
+        let roots = vec![
+            (PathBuf::from("main.ez"), main.to_string()),
+            (PathBuf::from("lib.ez"), lib.to_string()),
+        ];
+        let artifacts = compile_files(roots, &Options::default()).unwrap();
+        let mut output = Vec::new();
+        super::run(&artifacts.files, &mut "".as_bytes(), &mut output).unwrap();
+        // each file calls its own `Helper`
+        assert_eq!(output, b"11");
+    }
+}
@@ -0,0 +1,493 @@
+//! running a program inside ezc, without nasm or ld. the assembly of the files is turned into machine code by
+//! `assemble`, linked into memory that can be run, and called. the standard library is in here, written in rust, and
+//! shares its printing with the interpreter
+
+use crate::assemble::{self, Symbol};
+use crate::ast::AstNode;
+use crate::errors::{Diagnostic, Diagnostics};
+use crate::interpret;
+use crate::lexer::Span;
+use crate::CompiledFile;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::c_void;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::os::raw::c_int;
+
+/// how big the stack of the thread that runs the program should be. a program made by ld gets this much on linux
+pub const STACK_SIZE: usize = 8 << 20;
+const PAGE_SIZE: usize = 4096;
+
+/// an error from running a program with the jit
+#[derive(Debug)]
+pub enum JitError {
+    /// an external function that isn't in the standard library or in one of the files
+    UnknownExternal(String, Span),
+    /// `InputLine` when there is nothing left to read
+    EndOfInput,
+    /// the assembly of a file has something that `assemble` doesn't know. that is a bug
+    Assemble(assemble::AsmError),
+    /// the memory for the program couldn't be mapped
+    Memory(io::Error),
+}
+
+/// run the program made of `files`, starting at the top level code of the main one. the files have to be compiled
+/// with `Options::jit`. what it prints goes to `output` and `InputLine` reads from `input`. like the program made by
+/// ld, it crashes ezc if it uses memory that isn't there
+pub fn run(
+    files: &[CompiledFile],
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), Diagnostics> {
+    let result = match load(files) {
+        Ok(Some(program)) => program.run(input, output),
+        Ok(None) => Ok(()),
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| {
+        let mut diagnostics = Diagnostics::new(0);
+        diagnostics.push(*e);
+        diagnostics
+    })
+}
+
+/// a program in memory, ready to be called
+struct Program {
+    /// the code and the statics
+    memory: Mapping,
+    /// where `ezc_enter` is in the memory
+    enter: usize,
+    /// where `ezc_failed` is. the standard library sets it to stop the program
+    failed: usize,
+}
+
+/// assemble the files and link them with the standard library. `None` if no file has a `_start`
+fn load(files: &[CompiledFile]) -> Result<Option<Program>, Box<Diagnostic>> {
+    if !files.iter().any(|f| f.main) {
+        return Ok(None);
+    }
+    let mut objects = Vec::new();
+    for file in files {
+        let object = assemble::assemble(&file.asm)
+            .map_err(|e| Box::new(JitError::Assemble(e).diagnostic("").in_file(&file.path)))?;
+        objects.push(object);
+    }
+    let glue = objects.len();
+    objects.push(assemble::assemble(&glue_asm()).expect("the glue code can't be assembled"));
+    // the code of all of the objects, then their statics
+    let mut text_starts = Vec::new();
+    let mut text_len = 0;
+    for object in &objects {
+        text_len = align(text_len, 16);
+        text_starts.push(text_len);
+        text_len += object.text.len();
+    }
+    let bss_start = align(text_len, PAGE_SIZE);
+    let mut bss_starts = Vec::new();
+    let mut bss_len = 0;
+    for object in &objects {
+        bss_starts.push(bss_start + bss_len);
+        bss_len += object.bss_len;
+    }
+    let mut memory = Mapping::new(bss_start + bss_len)
+        .map_err(|e| Box::new(JitError::Memory(e).diagnostic("")))?;
+    let base = memory.ptr as usize;
+    let address = |object: usize, symbol: Symbol| match symbol {
+        Symbol::Text(offset) => base + text_starts[object] + offset,
+        Symbol::Bss(offset) => base + bss_starts[object] + offset,
+    };
+    // the exported functions of the files keep their names, so they are found before the standard library
+    let mut globals = HashMap::new();
+    for (i, object) in objects.iter().enumerate().take(glue) {
+        for name in &object.globals {
+            if let Some(&symbol) = object.symbols.get(name) {
+                globals.insert(name.as_str(), address(i, symbol));
+            }
+        }
+    }
+    for (name, _) in &STDLIB {
+        globals
+            .entry(name)
+            .or_insert_with(|| address(glue, objects[glue].symbols[*name]));
+    }
+    for (i, object) in objects.iter().enumerate() {
+        let code = &mut memory.bytes()[text_starts[i]..][..object.text.len()];
+        code.copy_from_slice(&object.text);
+        for relocation in &object.relocations {
+            let target = match object.symbols.get(&relocation.symbol) {
+                Some(&symbol) => address(i, symbol),
+                None => match globals.get(relocation.symbol.as_str()) {
+                    Some(&target) => target,
+                    None => return Err(Box::new(unknown_external(&files[i], &relocation.symbol))),
+                },
+            };
+            let from = base + text_starts[i] + relocation.end;
+            // it is all in one mapping, which is much smaller than 2GB
+            let distance = target as i64 + relocation.addend - from as i64;
+            let distance = i32::try_from(distance).expect("the program is too big to link");
+            code[relocation.at..relocation.at + 4].copy_from_slice(&distance.to_le_bytes());
+        }
+    }
+    memory
+        .make_runnable(bss_start)
+        .map_err(|e| Box::new(JitError::Memory(e).diagnostic("")))?;
+    let symbol = |name: &str| address(glue, objects[glue].symbols[name]) - base;
+    Ok(Some(Program {
+        enter: symbol("ezc_enter"),
+        failed: symbol("ezc_failed"),
+        memory,
+    }))
+}
+
+/// the error for an external function that nothing has. it points at where the file declared it
+fn unknown_external(file: &CompiledFile, name: &str) -> Diagnostic {
+    let span = file
+        .ast
+        .tree
+        .iter()
+        .find_map(|node| match node {
+            AstNode::Extern { name: n, span, .. } if n == name => Some(*span),
+            _ => None,
+        })
+        .unwrap_or_default();
+    JitError::UnknownExternal(name.to_string(), span)
+        .diagnostic(&file.input)
+        .in_file(&file.path)
+}
+
+fn align(n: usize, to: usize) -> usize {
+    n.div_ceil(to) * to
+}
+
+/// the code between rust and the program. `ezc_enter` is called from rust. it saves the registers that rust needs kept,
+/// and the stack pointer so `ezc_abort` can go back to rust from anywhere in the program. the functions of the standard
+/// library line the stack up for rust, because the program doesn't, and stop the program if they set `ezc_failed`
+fn glue_asm() -> String {
+    let mut asm = String::from(
+        "global ezc_enter
+section .text
+ezc_enter:
+push rbx
+push rbp
+push r12
+push r13
+push r14
+push r15
+mov qword [ezc_saved_rsp], rsp
+call _start
+.LEAVE
+pop r15
+pop r14
+pop r13
+pop r12
+pop rbp
+pop rbx
+ret
+ezc_abort:
+mov rsp, qword [ezc_saved_rsp]
+jmp .LEAVE
+",
+    );
+    for (name, function) in &STDLIB {
+        writeln!(
+            asm,
+            "{}:
+push rbx
+mov rbx, rsp
+and rsp, -16
+mov rax, {}
+call rax
+mov rsp, rbx
+pop rbx
+mov r11, qword [ezc_failed]
+cmp r11, 0
+jne ezc_abort
+ret",
+            name, *function as usize
+        )
+        .unwrap();
+    }
+    asm + "section .bss\nezc_saved_rsp resq 1\nezc_failed resq 1\n"
+}
+
+impl Program {
+    fn run(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Box<Diagnostic>> {
+        let mut io = Io {
+            input,
+            output,
+            strings: Vec::new(),
+            failed: None,
+            failed_flag: self.memory.ptr.wrapping_add(self.failed) as *mut i64,
+        };
+        {
+            let _running = Running::new(&mut io);
+            // the code was made from what codegen made, which keeps to the C calling convention
+            let enter: extern "sysv64" fn() =
+                unsafe { std::mem::transmute(self.memory.ptr.wrapping_add(self.enter)) };
+            enter();
+        }
+        // a program that printed something and then failed still shows what it printed
+        let _ = io.output.flush();
+        match io.failed {
+            Some(e) => Err(Box::new(e.diagnostic(""))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// what the standard library uses while a program runs
+struct Io<'a> {
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    /// the strings from `InputLine`. they are there until the program ends
+    strings: Vec<Box<[i64]>>,
+    failed: Option<JitError>,
+    failed_flag: *mut i64,
+}
+
+thread_local! {
+    /// the `Io` of the program that is running on this thread
+    static IO: Cell<*mut Io<'static>> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+/// sets `IO` while a program runs
+struct Running;
+
+impl Running {
+    fn new(io: &mut Io) -> Running {
+        IO.with(|current| current.set((io as *mut Io).cast()));
+        Running
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        IO.with(|current| current.set(std::ptr::null_mut()));
+    }
+}
+
+fn with_io<T>(f: impl FnOnce(&mut Io) -> T) -> T {
+    IO.with(|current| {
+        let io = current.get();
+        assert!(
+            !io.is_null(),
+            "the standard library was called with no program running"
+        );
+        // `Program::run` set it, and is waiting for the program that called this to end
+        f(unsafe { &mut *io })
+    })
+}
+
+impl Io<'_> {
+    /// the string is an array in the memory of the program, so it is read from there. like in `lib.zig`, a bad pointer
+    /// crashes
+    fn put_string(&mut self, s: i64) -> bool {
+        let s = s as *const i64;
+        let len = unsafe { *s.add(1) };
+        (0..len).all(|i| interpret::put_code_point(self.output, unsafe { *s.add(2 + i as usize) }))
+    }
+    fn input_line(&mut self) -> i64 {
+        // a prompt printed before this has to show up before waiting for the line
+        let _ = self.output.flush();
+        let code_points = match interpret::read_line(self.input) {
+            Some(code_points) => code_points,
+            None => {
+                self.failed = Some(JitError::EndOfInput);
+                unsafe { *self.failed_flag = 1 };
+                return 0;
+            }
+        };
+        // it starts with a pointer to itself and its length, like the arrays the program makes
+        let mut string = vec![0, code_points.len() as i64];
+        string.extend(code_points);
+        let mut string = string.into_boxed_slice();
+        string[0] = string.as_ptr() as i64;
+        let address = string[0];
+        self.strings.push(string);
+        address
+    }
+}
+
+type Function = extern "sysv64" fn(i64) -> i64;
+
+/// the functions from `lib.zig`. like there, they give back -1 if writing fails
+const STDLIB: [(&str, Function); 8] = [
+    ("PutChar", put_char),
+    ("PutNum", put_num),
+    ("PutNumHex", put_num_hex),
+    ("PutNumBin", put_num_bin),
+    ("PutNewLine", put_new_line),
+    ("PutString", put_string),
+    ("PutStringLine", put_string_line),
+    ("InputLine", input_line),
+];
+
+fn status(written: bool) -> i64 {
+    if written {
+        0
+    } else {
+        -1
+    }
+}
+
+fn put_number(name: &str, n: i64) -> i64 {
+    with_io(|io| status(interpret::put_number(io.output, name, n) == Some(true)))
+}
+
+extern "sysv64" fn put_char(c: i64) -> i64 {
+    put_number("PutChar", c)
+}
+
+extern "sysv64" fn put_num(n: i64) -> i64 {
+    put_number("PutNum", n)
+}
+
+extern "sysv64" fn put_num_hex(n: i64) -> i64 {
+    put_number("PutNumHex", n)
+}
+
+extern "sysv64" fn put_num_bin(n: i64) -> i64 {
+    put_number("PutNumBin", n)
+}
+
+extern "sysv64" fn put_new_line(_: i64) -> i64 {
+    put_number("PutNewLine", 0)
+}
+
+extern "sysv64" fn put_string(s: i64) -> i64 {
+    with_io(|io| status(io.put_string(s)))
+}
+
+extern "sysv64" fn put_string_line(s: i64) -> i64 {
+    with_io(|io| status(io.put_string(s) && io.output.write_all(b"\n").is_ok()))
+}
+
+extern "sysv64" fn input_line(_: i64) -> i64 {
+    with_io(|io| io.input_line())
+}
+
+const PROT_READ: c_int = 1;
+const PROT_WRITE: c_int = 2;
+const PROT_EXEC: c_int = 4;
+const MAP_PRIVATE: c_int = 2;
+const MAP_ANONYMOUS: c_int = 0x20;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: i64,
+    ) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// memory from mmap. the code goes at the start and the statics after it
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(len: usize) -> io::Result<Mapping> {
+        let flags = MAP_PRIVATE | MAP_ANONYMOUS;
+        let ptr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                flags,
+                -1,
+                0,
+            )
+        };
+        // MAP_FAILED
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+    fn bytes(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+    /// let the first `len` bytes be run, and not written to anymore
+    fn make_runnable(&mut self, len: usize) -> io::Result<()> {
+        if unsafe { mprotect(self.ptr as *mut c_void, len, PROT_READ | PROT_EXEC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { munmap(self.ptr as *mut c_void, self.len) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::{compile, Options};
+
+    fn run(source: &str, input: &str) -> Result<String, Box<crate::Diagnostic>> {
+        let options = Options {
+            jit: true,
+            ..Options::default()
+        };
+        let artifacts = compile(source, &options).unwrap();
+        let mut output = Vec::new();
+        match super::run(&artifacts.files, &mut input.as_bytes(), &mut output) {
+            Ok(()) => Ok(String::from_utf8(output).unwrap()),
+            Err(diagnostics) => Err(Box::new(diagnostics.all()[0].clone())),
+        }
+    }
+
+    #[test]
+    fn jit_programs() {
+        let fib = "external function PutNum(n). external function PutChar(c).
+function Fib(n),
+  if n < 2, return n. !
+  return Fib(n - 1) + Fib(n - 2).
+!
+set i to 0.
+loop,
+  if i > 10, break. !
+  call PutNum(Fib(i)). call PutChar(32).
+  change i to i + 1.
+!";
+        assert_eq!(run(fib, "").unwrap(), "0 1 1 2 3 5 8 13 21 34 55 ");
+        let arrays = "external function PutStringLine(n). external function PutNum(n).
+set s to \"héllo\".
+set a to [1, 2, 3].
+change a[2] to a[1] + a[3].
+call PutStringLine(s). call PutNum(a[2] * 1000000000000).";
+        assert_eq!(run(arrays, "").unwrap(), "héllo\n4000000000000");
+    }
+
+    #[test]
+    fn jit_builtins() {
+        let io = "external function InputLine(). external function PutStringLine(s).
+external function PutNumHex(n). external function PutNumBin(n). external function PutChar(c).
+set line to InputLine(). call PutStringLine(line). call PutNumHex(0 - 255). call PutNumBin(5).
+call PutChar(9731). call PutChar(0 - 1). call PutStringLine(InputLine()).";
+        assert_eq!(
+            run(io, "héllo\nworld\n").unwrap(),
+            "héllo\n0x-FF101☃world\n"
+        );
+        // the program stops at the call, but what it printed before stays
+        let err = run(io, "only one line\n").unwrap_err();
+        assert_eq!(err.code, "E504");
+        let unknown = "external function Nope(). call Nope().";
+        let err = run(unknown, "").unwrap_err();
+        assert_eq!(err.code, "E501");
+        assert_eq!(err.location.unwrap().column, 1);
+    }
+}
@@ -24,13 +24,23 @@ pub enum Token {
     Kreturn,
     /// External
     Kextern,
+    /// call
+    Kcall,
+    /// use
+    Kuse,
     // Iden tokens
     /// Identifier token
     Iden(String),
     /// IntLit token
     IntLit(String),
+    /// the path after `use`. it is written like a string but it is not an array
+    Path(String),
     /// EndOfLine token (.)
     EndOfLine,
+    /// `...` at the end of the args of a variadic external function
+    Ellipsis,
+    /// `'s` right after a word, like in `Math's Square(x)`
+    Possessive,
     /// EndOfFile
     Eof,
     // grouping
@@ -74,22 +84,52 @@ pub enum Token {
     AtSign,
 }
 
+/// Where something is in the source code. `start` and `end` are char indexes (not byte indexes) and `end` is exclusive.
+/// `file` says which source file the indexes are into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    /// the file that this is in
+    pub file: u32,
+    /// the first char
+    pub start: u32,
+    /// one past the last char
+    pub end: u32,
+}
+
+impl Span {
+    /// a span from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
 /// The Error type of a lex
 #[derive(Debug)]
 pub enum LexError {
     /// char not expected
-    UnexpectedChar(char, u32),
+    UnexpectedChar(char, Span),
+    /// a char literal that is not exactly one unicode code point, like `'ab'` or `''`
+    CharLitNotOneCodePoint(Span),
+}
+
+/// if the char can be in a word
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
 }
 
 /// see if a word is an iden or a kword
 /// ```rust
-/// let set = get_kword(String::from("set"));
+/// use ezc::lexer::{get_kword, Token};
+/// let set = get_kword("set");
 /// assert!(set == Token::Kset);
-/// let random = get_kword(String::from("random"));
-/// assert!(set == Token::Iden(String::from("random")));
+/// let random = get_kword("random");
+/// assert!(random == Token::Iden(String::from("random")));
 /// ```
 #[inline]
-fn get_kword(input: &str) -> Token {
+pub fn get_kword(input: &str) -> Token {
     match input {
         "Set" | "set" => Token::Kset,
         "external" | "External" => Token::Kextern,
@@ -103,11 +143,13 @@ fn get_kword(input: &str) -> Token {
         "function" | "Function" => Token::Kfunc,
         "return" | "Return" => Token::Kreturn,
         "export" | "Export" => Token::Kexport,
+        "call" | "Call" => Token::Kcall,
+        "use" | "Use" => Token::Kuse,
         _ => Token::Iden(input.to_string()),
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 enum LexerState {
     Start,
     InWord,
@@ -121,9 +163,10 @@ enum LexerState {
     InCharLitFowardSlash,
     InStrLit,
     InStrLitSawForwardSlash,
+    InPath,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 /// The thing that does the tokenizing
 pub struct Tokenizer {
     /// the state of the lexer
@@ -132,23 +175,45 @@ pub struct Tokenizer {
     intermidiate_string: String,
     /// the position that the tokenizer is at
     pos: u32,
+    /// the position that the token being lexed started at
+    token_start: u32,
+    /// the file that is being lexed. goes in the spans
+    file: u32,
+    /// the text (after `ez:`) of the `{ez: ...}` comments that tell the compiler something, like which warnings to allow
+    pub special_comments: Vec<(String, Span)>,
 }
 
 /// the type alias for a return type from lexing
-pub type Locs = Vec<u32>;
+pub type Locs = Vec<Span>;
 
 impl Tokenizer {
     /// The constructor for a tokenizer
     pub fn new() -> Tokenizer {
+        Tokenizer::new_with_file(0)
+    }
+    /// The constructor for a tokenizer that puts `file` in all of the spans
+    pub fn new_with_file(file: u32) -> Tokenizer {
         Tokenizer {
             state: LexerState::Start,
             intermidiate_string: String::new(),
             pos: 0,
+            token_start: 0,
+            file,
+            special_comments: Vec::new(),
         }
     }
-    /// the lex function
-    pub fn lex(self: &mut Self, input_string: &String) -> (Result<Vec<Token>, LexError>, Locs) {
+    /// a span from `start` up to (but not including) `end`
+    fn span(&self, start: u32, end: u32) -> Span {
+        Span {
+            file: self.file,
+            start,
+            end,
+        }
+    }
+    /// the lex function. it keeps going after an error so that all of them can be reported at once
+    pub fn lex(&mut self, input_string: &str) -> (Result<Vec<Token>, Vec<LexError>>, Locs) {
         let input: Vec<char> = input_string.chars().collect();
+        let mut errors = Vec::new();
         let mut output = Vec::new();
         let mut output_poss: Locs = Vec::new();
         let mut c: char;
@@ -161,6 +226,7 @@ impl Tokenizer {
             match self.state {
                 LexerState::Start => {
                     self.intermidiate_string = String::new();
+                    self.token_start = self.pos;
                     match c {
                         'a'..='z' | 'A'..='Z' | '_' => {
                             self.state = LexerState::InWord;
@@ -170,6 +236,12 @@ impl Tokenizer {
                             self.state = LexerState::InNum;
                             self.intermidiate_string.push(c);
                         }
+                        '.' if input.get(self.pos as usize + 1..self.pos as usize + 3)
+                            == Some(&['.', '.']) =>
+                        {
+                            self.pos += 2;
+                            self.end_token(&mut output, &mut output_poss, Token::Ellipsis)
+                        }
                         '.' => self.end_token(&mut output, &mut output_poss, Token::EndOfLine),
                         ',' => self.end_token(&mut output, &mut output_poss, Token::Comma),
                         '(' => self.end_token(&mut output, &mut output_poss, Token::Lparen),
@@ -185,58 +257,95 @@ impl Tokenizer {
                         '<' => self.state = LexerState::SawLessThan,
                         '=' => self.state = LexerState::SawEquals,
                         '{' => self.state = LexerState::InComment,
+                        '"' if output.last() == Some(&Token::Kuse) => {
+                            self.state = LexerState::InPath
+                        }
                         '"' => {
                             self.end_token(&mut output, &mut output_poss, Token::OpenBrak);
                             self.state = LexerState::InStrLit;
                         }
-                        '\'' => self.state = LexerState::InCharLit,
-                        ' ' | '\n' => {}
-                        _ => {
-                            return (Err(LexError::UnexpectedChar(c, self.pos)), output_poss);
+                        // `Math's`. a char literal can't come right after a word, so this isn't `'s'`
+                        '\'' if self.pos > 0
+                            && is_word_char(input[self.pos as usize - 1])
+                            && input.get(self.pos as usize + 1) == Some(&'s')
+                            && !input
+                                .get(self.pos as usize + 2)
+                                .is_some_and(|c| is_word_char(*c)) =>
+                        {
+                            self.pos += 1;
+                            self.end_token(&mut output, &mut output_poss, Token::Possessive)
                         }
+                        '\'' => self.state = LexerState::InCharLit,
+                        // all ascii whitespace, so tabs and CRLF line endings work
+                        ' ' | '\t' | '\n' | '\r' | '\x0B' | '\x0C' => {}
+                        // skip the char so we can keep lexing
+                        _ => errors.push(LexError::UnexpectedChar(
+                            c,
+                            self.span(self.pos, self.pos + 1),
+                        )),
                     }
                 }
                 LexerState::InComment => match c {
-                    '}' => self.state = LexerState::Start,
-                    _ => {}
+                    '}' => {
+                        let text = std::mem::take(&mut self.intermidiate_string);
+                        if let Some(rest) = text.trim_start().strip_prefix("ez:") {
+                            let span = self.span(self.token_start, self.pos + 1);
+                            self.special_comments.push((rest.trim().to_string(), span));
+                        }
+                        self.state = LexerState::Start;
+                    }
+                    _ => self.intermidiate_string.push(c),
+                },
+                LexerState::InPath => match c {
+                    '"' => {
+                        let path = std::mem::take(&mut self.intermidiate_string);
+                        self.end_token(&mut output, &mut output_poss, Token::Path(path));
+                    }
+                    _ => self.intermidiate_string.push(c),
                 },
                 LexerState::InWord => match c {
                     'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => self.intermidiate_string.push(c),
                     _ => {
+                        // put back char
+                        self.pos -= 1;
                         self.end_token(
                             &mut output,
                             &mut output_poss,
                             get_kword(&self.intermidiate_string),
                         );
-                        // put back char
-                        self.pos -= 1;
                     }
                 },
                 LexerState::InNum => match c {
                     '0'..='9' => self.intermidiate_string.push(c),
                     _ => {
+                        // put back char
+                        self.pos -= 1;
                         self.end_token(
                             &mut output,
                             &mut output_poss,
                             Token::IntLit(self.intermidiate_string.to_owned()),
                         );
-                        // put back char
-                        self.pos -= 1;
                     }
                 },
                 LexerState::InCharLit => match c {
                     '\\' => self.state = LexerState::InCharLitFowardSlash,
+                    '\'' => {
+                        errors.push(LexError::CharLitNotOneCodePoint(
+                            self.span(self.token_start, self.pos + 1),
+                        ));
+                        self.state = LexerState::Start;
+                    }
                     c => {
-                        self.end_token(
-                            &mut output,
-                            &mut output_poss,
-                            Token::IntLit((c as u8).to_string()),
-                        );
-                        match input[self.pos as usize + 1] {
-                            '\'' => {
-                                self.pos += 1;
-                            }
-                            c => return (Err(LexError::UnexpectedChar(c, self.pos)), output_poss),
+                        if let Err(e) = self.eat_char_lit_end(&input) {
+                            errors.push(e);
+                            self.state = LexerState::Start;
+                        } else {
+                            // chars are unicode code points, not bytes
+                            self.end_token(
+                                &mut output,
+                                &mut output_poss,
+                                Token::IntLit((c as u32).to_string()),
+                            );
                         }
                     }
                 },
@@ -247,10 +356,11 @@ impl Tokenizer {
                         self.state = LexerState::Start;
                     }
                     c => {
+                        // every element of a string is one unicode code point
                         self.end_token_wo_reset(
                             &mut output,
                             &mut output_poss,
-                            Token::IntLit((c as u8).to_string()),
+                            Token::IntLit((c as u32).to_string()),
                         );
                         self.end_token_wo_reset(&mut output, &mut output_poss, Token::Comma);
                     }
@@ -272,35 +382,44 @@ impl Tokenizer {
                         );
                         self.state = LexerState::InStrLit;
                     }
-                    c => return (Err(LexError::UnexpectedChar(c, self.pos)), output_poss),
+                    c => {
+                        // leave out the bad escape and keep going with the string
+                        errors.push(LexError::UnexpectedChar(
+                            c,
+                            self.span(self.pos, self.pos + 1),
+                        ));
+                        self.state = LexerState::InStrLit;
+                    }
                 },
                 LexerState::InCharLitFowardSlash => {
-                    match c {
-                        'n' => self.end_token(
-                            &mut output,
-                            &mut output_poss,
-                            Token::IntLit(String::from("10")),
-                        ),
-                        't' => self.end_token(
-                            &mut output,
-                            &mut output_poss,
-                            Token::IntLit(String::from("9")),
-                        ),
-
-                        c => return (Err(LexError::UnexpectedChar(c, self.pos)), output_poss),
-                    }
-                    match input[self.pos as usize + 1] {
-                        '\'' => {
-                            self.pos += 1;
+                    let escaped = match c {
+                        'n' => Some(Token::IntLit(String::from("10"))),
+                        't' => Some(Token::IntLit(String::from("9"))),
+                        c => {
+                            errors.push(LexError::UnexpectedChar(
+                                c,
+                                self.span(self.pos, self.pos + 1),
+                            ));
+                            None
                         }
-                        c => return (Err(LexError::UnexpectedChar(c, self.pos)), output_poss),
+                    };
+                    match (self.eat_char_lit_end(&input), escaped) {
+                        (Ok(()), Some(escaped)) => {
+                            self.end_token(&mut output, &mut output_poss, escaped)
+                        }
+                        (Err(e), _) => {
+                            errors.push(e);
+                            self.state = LexerState::Start;
+                        }
+                        (Ok(()), None) => self.state = LexerState::Start,
                     }
                 }
                 LexerState::SawGreaterThan => match c {
                     '=' => self.end_token(&mut output, &mut output_poss, Token::BoGe),
                     _ => {
-                        self.end_token(&mut output, &mut output_poss, Token::BoG);
+                        // put back char
                         self.pos -= 1;
+                        self.end_token(&mut output, &mut output_poss, Token::BoG);
                     }
                 },
                 LexerState::SawBang => match c {
@@ -308,25 +427,32 @@ impl Tokenizer {
                         self.end_token(&mut output, &mut output_poss, Token::BoNe);
                     }
                     _ => {
-                        self.end_token(&mut output, &mut output_poss, Token::ExclaimMark);
+                        // put back char
                         self.pos -= 1;
+                        self.end_token(&mut output, &mut output_poss, Token::ExclaimMark);
                     }
                 },
                 LexerState::SawLessThan => match c {
                     '=' => self.end_token(&mut output, &mut output_poss, Token::BoLe),
                     _ => {
-                        self.end_token(&mut output, &mut output_poss, Token::BoL);
+                        // put back char
                         self.pos -= 1;
+                        self.end_token(&mut output, &mut output_poss, Token::BoL);
                     }
                 },
                 LexerState::SawEquals => {
-                    self.end_token(&mut output, &mut output_poss, Token::BoE);
+                    // put back char
                     self.pos -= 1;
+                    self.end_token(&mut output, &mut output_poss, Token::BoE);
                 }
             }
             self.pos += 1;
         }
-        // clean up state
+        // clean up state. the token that is left ended at the last char
+        let end_of_input = self.pos;
+        if self.state != LexerState::Start {
+            self.pos -= 1;
+        }
         match self.state {
             LexerState::SawBang => {
                 self.end_token(&mut output, &mut output_poss, Token::ExclaimMark)
@@ -352,8 +478,14 @@ impl Tokenizer {
             LexerState::SawLessThan => self.end_token(&mut output, &mut output_poss, Token::BoL),
             _ => {}
         }
+        self.pos = end_of_input;
+        self.token_start = self.pos;
         self.end_token(&mut output, &mut output_poss, Token::Eof);
-        (Ok(output), output_poss)
+        if errors.is_empty() {
+            (Ok(output), output_poss)
+        } else {
+            (Err(errors), output_poss)
+        }
     }
     /// the function to end a token
     fn end_token(
@@ -364,7 +496,7 @@ impl Tokenizer {
     ) {
         output.push(token_type);
         self.intermidiate_string = String::from("");
-        output_poss.push(self.pos);
+        output_poss.push(self.span(self.token_start, self.pos + 1));
         self.state = LexerState::Start;
     }
     /// the function to end a token without reset
@@ -376,7 +508,30 @@ impl Tokenizer {
     ) {
         output.push(token_type);
         self.intermidiate_string = String::from("");
-        output_poss.push(self.pos);
+        output_poss.push(self.span(self.pos, self.pos + 1));
+    }
+    /// eat the closing `'` of a char literal. the char after `self.pos` has to be it.
+    /// if it isn't, the rest of the literal (up to a `'` on the same line) is skipped so lexing can go on
+    fn eat_char_lit_end(&mut self, input: &[char]) -> Result<(), LexError> {
+        match input.get(self.pos as usize + 1) {
+            Some('\'') => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => {
+                let start = self.pos;
+                if let Some(n) = input[self.pos as usize + 1..]
+                    .iter()
+                    .take_while(|c| **c != '\n')
+                    .position(|c| *c == '\'')
+                {
+                    self.pos += n as u32 + 1;
+                }
+                Err(LexError::CharLitNotOneCodePoint(
+                    self.span(self.token_start, self.pos.max(start + 1) + 1),
+                ))
+            }
+        }
     }
 }
 
@@ -393,6 +548,9 @@ mod tests {
             Tokenizer {
                 state: LexerState::Start,
                 intermidiate_string: String::from(""),
+                token_start: 11,
+                file: 0,
+                special_comments: Vec::new(),
                 pos: 11,
             }
         );
@@ -411,6 +569,29 @@ mod tests {
         assert_eq!(ts.len(), res.1.len())
     }
     #[test]
+    fn lexer_spans() {
+        let mut tokenizer = Tokenizer::new();
+        let res = tokenizer.lex(&String::from("set x to 5.\nif x >= 'é',!"));
+        let spans: Vec<(u32, u32)> = res.1.iter().map(|s| (s.start, s.end)).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0, 3),
+                (4, 5),
+                (6, 8),
+                (9, 10),
+                (10, 11),
+                (12, 14),
+                (15, 16),
+                (17, 19),
+                (20, 23),
+                (23, 24),
+                (24, 25),
+                (25, 26),
+            ]
+        );
+    }
+    #[test]
     fn lexer_char_lit() {
         let mut tokenizer = Tokenizer::new();
         let res = tokenizer.lex(&String::from("'t' '\\n'"));
@@ -420,6 +601,9 @@ mod tests {
             Tokenizer {
                 state: LexerState::Start,
                 intermidiate_string: String::from(""),
+                token_start: 8,
+                file: 0,
+                special_comments: Vec::new(),
                 pos: 8,
             }
         );
@@ -435,6 +619,81 @@ mod tests {
         assert_eq!(ts.len(), res.1.len())
     }
     #[test]
+    fn lexer_unicode_lits() {
+        let mut tokenizer = Tokenizer::new();
+        let res = tokenizer.lex(&String::from("'é' \"añ€😀\""));
+        let ts = res.0.unwrap();
+        assert_eq!(
+            ts,
+            vec![
+                Token::IntLit(String::from("233")),
+                Token::OpenBrak,
+                Token::IntLit(String::from("97")),
+                Token::Comma,
+                Token::IntLit(String::from("241")),
+                Token::Comma,
+                Token::IntLit(String::from("8364")),
+                Token::Comma,
+                Token::IntLit(String::from("128512")),
+                Token::Comma,
+                Token::CloseBrak,
+                Token::Eof,
+            ]
+        );
+        assert_eq!(ts.len(), res.1.len())
+    }
+    #[test]
+    fn lexer_bad_char_lit() {
+        for input in ["'ab'", "''", "'e\u{301}'"].iter() {
+            let mut tokenizer = Tokenizer::new();
+            match tokenizer.lex(input).0 {
+                Err(e) if matches!(e[..], [LexError::CharLitNotOneCodePoint(_)]) => {}
+                r => panic!("{:?} lexed to {:?}", input, r),
+            }
+        }
+    }
+    #[test]
+    fn lexer_keeps_going_after_errors() {
+        let mut tokenizer = Tokenizer::new();
+        let errs = tokenizer
+            .lex(&String::from("set x to $. set y to 'ab'. set z to #."))
+            .0
+            .unwrap_err();
+        match &errs[..] {
+            [LexError::UnexpectedChar('$', a), LexError::CharLitNotOneCodePoint(b), LexError::UnexpectedChar('#', c)] =>
+            {
+                assert_eq!((a.start, b.start, b.end, c.start), (9, 21, 25, 36))
+            }
+            e => panic!("{:?}", e),
+        }
+    }
+    #[test]
+    fn lexer_whitespace() {
+        let mut tokenizer = Tokenizer::new();
+        let res = tokenizer.lex(&String::from(
+            "if x >= 5,\r\n\tset y to 4.\r\n!\x0B\x0C\r\n",
+        ));
+        let ts = res.0.unwrap();
+        assert_eq!(
+            ts,
+            vec![
+                Token::Kif,
+                Token::Iden(String::from("x")),
+                Token::BoGe,
+                Token::IntLit(String::from("5")),
+                Token::Comma,
+                Token::Kset,
+                Token::Iden(String::from("y")),
+                Token::Kto,
+                Token::IntLit(String::from("4")),
+                Token::EndOfLine,
+                Token::ExclaimMark,
+                Token::Eof,
+            ]
+        );
+        assert_eq!(ts.len(), res.1.len())
+    }
+    #[test]
     fn lexer_array_lit() {
         let mut tokenizer = Tokenizer::new();
         let res = tokenizer.lex(&String::from("n[] = [1,2,3,4]"));
@@ -444,6 +703,9 @@ mod tests {
             Tokenizer {
                 state: LexerState::Start,
                 intermidiate_string: String::from(""),
+                token_start: 15,
+                file: 0,
+                special_comments: Vec::new(),
                 pos: 15,
             }
         );
@@ -479,6 +741,9 @@ mod tests {
             Tokenizer {
                 state: LexerState::Start,
                 intermidiate_string: String::from(""),
+                token_start: 13,
+                file: 0,
+                special_comments: Vec::new(),
                 pos: 13,
             }
         );
@@ -513,6 +778,9 @@ mod tests {
             Tokenizer {
                 state: LexerState::Start,
                 intermidiate_string: String::from(""),
+                token_start: 13,
+                file: 0,
+                special_comments: Vec::new(),
                 pos: 13,
             }
         );
@@ -565,7 +833,7 @@ mod tests {
         let bad_inputs = ["set x to 5.", "change y to 10."];
         for i in bad_inputs.iter() {
             let mut tokenizer = Tokenizer::new();
-            outputs.push(tokenizer.lex(&i.to_string()));
+            outputs.push(tokenizer.lex(i));
         }
         for i in outputs {
             assert_eq!(i.1.len(), i.0.unwrap().len());
@@ -713,4 +981,24 @@ if test <= 4+2,
         );
         assert_eq!(ts.len(), res.1.len())
     }
+    #[test]
+    fn lexer_possessive() {
+        let mut tokenizer = Tokenizer::new();
+        let res = tokenizer.lex(&String::from("Math's Square('s')."));
+        assert_eq!(
+            res.0.unwrap(),
+            vec![
+                Token::Iden(String::from("Math")),
+                Token::Possessive,
+                Token::Iden(String::from("Square")),
+                Token::Lparen,
+                Token::IntLit(String::from("115")),
+                Token::Rparen,
+                Token::EndOfLine,
+                Token::Eof,
+            ]
+        );
+        assert_eq!(res.1[1].start, 4);
+        assert_eq!(res.1[1].end, 6);
+    }
 }
@@ -0,0 +1,26 @@
+//! the compiler for ez. `compile` turns ez code into nasm assembly without printing anything or exiting,
+//! so it can be used from other programs. the `ezc` binary is a wrapper around it that assembles and links, or runs the
+//! program inside itself with `jit`
+
+#[warn(missing_docs)]
+#[warn(missing_crate_level_docs)]
+#[warn(missing_debug_implementations)]
+pub mod analyse;
+pub mod assemble;
+pub mod ast;
+pub mod bindgen;
+pub mod codegen;
+pub mod compile;
+pub mod errors;
+pub mod header;
+pub mod interpret;
+// the machine code and the mmap flags are for x86-64 linux. on other targets `ezc run` links an executable instead
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod jit;
+pub mod lexer;
+pub mod modules;
+pub mod parser;
+pub mod repl;
+
+pub use compile::{compile, compile_files, Artifacts, CompiledFile, Options};
+pub use errors::{Diagnostic, Diagnostics, Printer};
@@ -0,0 +1,575 @@
+//! loading the files that a program `use`s
+
+use crate::ast::{AstNode, AstRoot};
+use crate::errors::Diagnostic;
+use crate::lexer::{self, Span};
+use crate::parser;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// one source file of the program
+#[derive(Debug)]
+pub struct Module {
+    /// where the file is. the paths in `use`s are from the folder of the file they are in
+    pub path: PathBuf,
+    /// the name of the file without `.ez`, in lowercase and made unique. it goes in the mangled names and `name's Func(x)` calls it
+    pub name: String,
+    pub input: String,
+    /// the `use`s in here have been replaced with `Extern`s for the exported functions of the files
+    pub ast: AstRoot,
+    /// the `{ez: ...}` comments in the file
+    pub special_comments: Vec<(String, Span)>,
+    /// the file was given to the compiler, it isn't just used by another one
+    pub root: bool,
+}
+
+impl Module {
+    /// where the first code at the top level is, that isn't a function. this code goes in `_start`
+    pub fn top_level_code(&self) -> Option<Span> {
+        self.ast
+            .tree
+            .iter()
+            .find(|n| {
+                !matches!(
+                    n,
+                    AstNode::Func { .. } | AstNode::Extern { .. } | AstNode::Use { .. }
+                )
+            })
+            .map(|n| n.span())
+    }
+}
+
+/// Errors for loading the used files. the spans are in the file that has the `use`
+#[derive(Debug)]
+pub enum ModuleError {
+    /// a used file can't be read. has the path and why
+    CannotRead(String, String, Span),
+    /// files that use each other in a circle. has the paths, starting and ending with the same one
+    Cycle(Vec<String>, Span),
+    /// code at the top level of a used file. it would never run, since only the main file has a `_start`
+    TopLevelCode(String, Span),
+    /// a function exported by two files. exported functions aren't mangled, so they all share one namespace and `Module's Func()` can't tell them apart. has the name and the other file
+    SameExport(String, String, Span),
+    /// code at the top level of two of the files given to the compiler. there can only be one `_start`. has the other file
+    TwoStarts(String, Span),
+}
+
+impl ModuleError {
+    pub fn span(&self) -> Span {
+        match self {
+            ModuleError::CannotRead(_, _, span)
+            | ModuleError::Cycle(_, span)
+            | ModuleError::TopLevelCode(_, span)
+            | ModuleError::SameExport(_, _, span)
+            | ModuleError::TwoStarts(_, span) => *span,
+        }
+    }
+}
+
+/// loads the files
+struct Loader {
+    /// the files loaded so far. a file is only put here after all of the files it uses
+    modules: Vec<Module>,
+    /// where each file is in `modules`, by its canonical path
+    loaded: HashMap<PathBuf, usize>,
+    /// the files that had errors, by their canonical paths. they aren't loaded again, so their errors are only reported once
+    failed: HashSet<PathBuf>,
+    /// the files being loaded right now, each one used by the one before it. for finding cycles
+    stack: Vec<(PathBuf, String)>,
+    /// the errors, with the files they are in
+    errors: Vec<Diagnostic>,
+    /// the number for the spans of the next file
+    next_file: u32,
+}
+
+/// load the files given to the compiler and all of the files they use, and the files those use, and so on.
+/// gives back the files with the used ones before the ones that use them. a file is only loaded once, even if it is given and also used.
+/// the errors all say which file they are in
+pub fn load(roots: Vec<(PathBuf, String)>) -> Result<Vec<Module>, Vec<Diagnostic>> {
+    let mut loader = Loader {
+        modules: Vec::new(),
+        loaded: HashMap::new(),
+        failed: HashSet::new(),
+        stack: Vec::new(),
+        errors: Vec::new(),
+        next_file: 0,
+    };
+    for (path, input) in roots {
+        // if a given file can't be canonicalized it still gets compiled, it just can't be part of a cycle
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        match loader.loaded.get(&canonical) {
+            // an earlier file uses it
+            Some(&index) => loader.modules[index].root = true,
+            None if loader.failed.contains(&canonical) => {}
+            None => {
+                loader.load_file(&path, canonical, input);
+            }
+        }
+    }
+    if loader.errors.is_empty() {
+        loader.check_exports();
+        loader.check_starts();
+    }
+    if loader.errors.is_empty() {
+        Ok(loader.modules)
+    } else {
+        Err(loader.errors)
+    }
+}
+
+impl Loader {
+    /// make sure only one of the files has code at the top level
+    fn check_starts(&mut self) {
+        let mut start: Option<&Path> = None;
+        for module in &self.modules {
+            let span = match module.top_level_code() {
+                Some(span) => span,
+                None => continue,
+            };
+            match start {
+                Some(other) => {
+                    let e = ModuleError::TwoStarts(other.display().to_string(), span);
+                    self.errors
+                        .push(e.diagnostic(&module.input).in_file(&module.path));
+                }
+                None => start = Some(&module.path),
+            }
+        }
+    }
+    /// make sure no two files export a function with the same name
+    fn check_exports(&mut self) {
+        let mut exported: HashMap<&str, &Path> = HashMap::new();
+        for module in &self.modules {
+            for node in &module.ast.tree {
+                if let AstNode::Func {
+                    name,
+                    export: true,
+                    span,
+                    ..
+                } = node
+                {
+                    // a file exporting it twice is found by the analysis
+                    match exported.insert(name, &module.path) {
+                        Some(other) if other != module.path => {
+                            let e = ModuleError::SameExport(
+                                name.clone(),
+                                other.display().to_string(),
+                                *span,
+                            );
+                            self.errors
+                                .push(e.diagnostic(&module.input).in_file(&module.path));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    /// the name for a module at `path`, from the name of the file
+    fn module_name(&self, path: &Path) -> String {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name: String = stem
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let taken = |n: &str| self.modules.iter().any(|m| m.name == n);
+        if !taken(&name) {
+            return name;
+        }
+        (2..)
+            .map(|i| format!("{}_{}", name, i))
+            .find(|n| !taken(n))
+            .unwrap()
+    }
+    /// lex and parse a file, load the files it uses and put it in `self.modules`. gives back where it is
+    fn load_file(&mut self, path: &Path, canonical: PathBuf, input: String) -> Option<usize> {
+        let is_root = self.stack.is_empty();
+        let name = path.display().to_string();
+        let mut tokenizer = lexer::Tokenizer::new_with_file(self.next_file);
+        self.next_file += 1;
+        let (tokens, locs) = tokenizer.lex(&input);
+        let tokens = match tokens {
+            Ok(tokens) => tokens,
+            Err(errs) => {
+                let errs = errs.iter().map(|e| e.diagnostic(&input).in_file(path));
+                self.errors.extend(errs);
+                self.failed.insert(canonical);
+                return None;
+            }
+        };
+        let mut ast = match parser::parse(tokens, locs) {
+            Ok(ast) => ast,
+            Err(errs) => {
+                let errs = errs.iter().map(|e| e.diagnostic(&input).in_file(path));
+                self.errors.extend(errs);
+                self.failed.insert(canonical);
+                return None;
+            }
+        };
+        let mut errors = Vec::new();
+        self.stack.push((canonical.clone(), name.clone()));
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        // the files this one uses, so using one twice doesn't declare its functions twice
+        let mut used = HashSet::new();
+        // the externs for the used functions go before everything else, like the parser puts them,
+        // so functions can call them
+        let mut externs = Vec::new();
+        let mut tree = Vec::new();
+        for node in std::mem::take(&mut ast.tree) {
+            let (use_path, span) = match node {
+                AstNode::Use { path, span } => (path, span),
+                node => {
+                    tree.push(node);
+                    continue;
+                }
+            };
+            let used_path = dir.join(&use_path);
+            let used_canonical = match used_path.canonicalize() {
+                Ok(p) => p,
+                Err(e) => {
+                    errors.push(ModuleError::CannotRead(use_path, e.to_string(), span));
+                    continue;
+                }
+            };
+            if let Some(start) = self.stack.iter().position(|f| f.0 == used_canonical) {
+                let mut cycle: Vec<String> =
+                    self.stack[start..].iter().map(|f| f.1.clone()).collect();
+                cycle.push(used_path.display().to_string());
+                errors.push(ModuleError::Cycle(cycle, span));
+                continue;
+            }
+            let index = match self.loaded.get(&used_canonical) {
+                Some(&index) => index,
+                // its errors are already there
+                None if self.failed.contains(&used_canonical) => continue,
+                None => {
+                    let used_input = match fs::read_to_string(&used_path) {
+                        Ok(i) => i,
+                        Err(e) => {
+                            errors.push(ModuleError::CannotRead(use_path, e.to_string(), span));
+                            continue;
+                        }
+                    };
+                    match self.load_file(&used_path, used_canonical, used_input) {
+                        Some(index) => index,
+                        // the errors are already there
+                        None => continue,
+                    }
+                }
+            };
+            if !used.insert(index) {
+                continue;
+            }
+            // exported functions aren't mangled, so they can be called like external ones
+            for node in &self.modules[index].ast.tree {
+                if let AstNode::Func {
+                    name,
+                    args,
+                    export: true,
+                    returns_nothing,
+                    ..
+                } = node
+                {
+                    externs.push(AstNode::Extern {
+                        module: Some(self.modules[index].name.clone()),
+                        name: name.clone(),
+                        args: args.clone(),
+                        variadic: false,
+                        returns_nothing: *returns_nothing,
+                        span,
+                    });
+                }
+            }
+        }
+        externs.append(&mut tree);
+        ast.tree = externs;
+        self.stack.pop();
+        let module = Module {
+            name: self.module_name(path),
+            path: path.to_path_buf(),
+            input,
+            ast,
+            special_comments: tokenizer.special_comments,
+            root: is_root,
+        };
+        if !is_root {
+            if let Some(span) = module.top_level_code() {
+                errors.insert(0, ModuleError::TopLevelCode(name.clone(), span));
+            }
+        }
+        let input = module.input.as_str();
+        if !errors.is_empty() {
+            let errs = errors.iter().map(|e| e.diagnostic(input).in_file(path));
+            self.errors.extend(errs);
+            self.failed.insert(canonical);
+            return None;
+        }
+        self.loaded.insert(canonical, self.modules.len());
+        self.modules.push(module);
+        Some(self.modules.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// make a folder with these files in it
+    fn files(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("ezc_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (file, contents) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+    #[test]
+    fn modules_load_uses() {
+        use crate::ast::AstNode;
+        use crate::modules;
+        let main = "use \"lib/math.ez\". use \"lib/math.ez\". use \"io.ez\".
+set x to Square(AddOne(2)).";
+        let dir = files(
+            "uses",
+            &[
+                ("main.ez", main),
+                (
+                    "lib/math.ez",
+                    "use \"../io.ez\". export function Square(x), return x * x.!
+export function AddOne(x), return x + 1.! function Hidden(), return 0.!",
+                ),
+                ("io.ez", "export function Show(x) returns nothing, return.!"),
+            ],
+        );
+        let modules = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap();
+        let names: Vec<String> = modules
+            .iter()
+            .map(|m| m.path.strip_prefix(&dir).unwrap().display().to_string())
+            .collect();
+        assert_eq!(names, vec!["lib/../io.ez", "lib/math.ez", "main.ez"]);
+        let externs: Vec<(&str, bool)> = modules[2]
+            .ast
+            .tree
+            .iter()
+            .filter_map(|n| match n {
+                AstNode::Extern {
+                    name,
+                    returns_nothing,
+                    ..
+                } => Some((name.as_str(), *returns_nothing)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            externs,
+            vec![("Square", false), ("AddOne", false), ("Show", true)]
+        );
+        // every file has its own number in the spans
+        assert_eq!(modules[1].ast.tree[0].span().file, 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn modules_errors() {
+        use crate::modules;
+        let main = "use \"a.ez\". use \"missing.ez\".";
+        let dir = files(
+            "errors",
+            &[
+                ("main.ez", main),
+                ("a.ez", "use \"b.ez\"."),
+                ("b.ez", "use \"a.ez\". set x to 1."),
+            ],
+        );
+        let errs = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap_err();
+        let a = dir.join("a.ez").display().to_string();
+        let b = dir.join("b.ez").display().to_string();
+        assert_eq!(errs.len(), 3, "{:?}", errs);
+        assert_eq!(errs[0].file.as_ref(), Some(&b));
+        assert_eq!(errs[0].code, "E303");
+        assert!(errs[0].message.contains("only have functions"));
+        assert!(errs[1]
+            .message
+            .contains(&format!("{} -> {} -> {}", a, b, a)));
+        assert!(errs[2].message.contains("missing.ez"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn modules_broken_used_twice() {
+        use crate::modules;
+        // a broken file is only loaded once, so its errors are only reported once
+        for (broken, code) in [
+            ("export function F(), return 1.! set x to 1.", "E303"),
+            ("export function F(), return $.!", "E001"),
+            ("export function F(), return 1.", "E101"),
+        ] {
+            let main = "use \"a.ez\". use \"b.ez\". use \"math.ez\".";
+            let dir = files(
+                "broken_used_twice",
+                &[
+                    ("main.ez", main),
+                    ("a.ez", "use \"math.ez\". export function A(), return F().!"),
+                    ("b.ez", "use \"math.ez\". export function B(), return F().!"),
+                    ("math.ez", broken),
+                ],
+            );
+            let errs = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap_err();
+            let math = dir.join("math.ez").display().to_string();
+            assert_eq!(errs.len(), 1, "{}: {:?}", broken, errs);
+            assert_eq!(errs[0].file.as_ref(), Some(&math));
+            assert_eq!(errs[0].code, code, "{}: {:?}", broken, errs);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+    #[test]
+    fn modules_names() {
+        use crate::analyse;
+        use crate::ast::{AstNode, Expr};
+        use crate::modules;
+        // `G` in this file hides the one from b/util.ez, which can still be called with its module
+        let main = "use \"a/util.ez\". use \"b/Util.ez\".
+function G(x), return x.!
+set x to Util's F(1) + F(2) + util_2's G(3) + G(4).";
+        let lib = "export function Helper(), return 0.!";
+        let dir = files(
+            "names",
+            &[
+                ("main.ez", main),
+                (
+                    "a/util.ez",
+                    "export function F(x), return x.! function H(), return 0.!",
+                ),
+                (
+                    "b/Util.ez",
+                    "export function G(x), return x.! function H(), return 0.!",
+                ),
+            ],
+        );
+        let mut modules = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap();
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["util", "util_2", "main"]);
+        let main = modules.last_mut().unwrap();
+        analyse::analize(&mut main.ast).unwrap();
+        let mut calls = Vec::new();
+        let mut exprs: Vec<&Expr> = main
+            .ast
+            .tree
+            .iter()
+            .filter_map(|n| match n {
+                AstNode::SetOrChange {
+                    setor: crate::ast::Val::Expr(e),
+                    ..
+                } => Some(e),
+                _ => None,
+            })
+            .collect();
+        while let Some(e) = exprs.pop() {
+            match e {
+                Expr::BinOp { lhs, rhs, .. } => exprs.extend([&**lhs, &**rhs]),
+                Expr::FuncCall {
+                    func_name,
+                    external,
+                    ..
+                } => calls.push((func_name.as_str(), external.unwrap())),
+                _ => {}
+            }
+        }
+        calls.sort();
+        assert_eq!(
+            calls,
+            vec![("F", true), ("F", true), ("G", false), ("G", true)]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // exported functions keep their names, so two files can't both have one
+        let main = "use \"a.ez\". use \"b.ez\".";
+        let dir = files(
+            "same_export",
+            &[("main.ez", main), ("a.ez", lib), ("b.ez", lib)],
+        );
+        let errs = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap_err();
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        let b = dir.join("b.ez").display().to_string();
+        assert_eq!(errs[0].file.as_ref(), Some(&b));
+        assert!(errs[0].message.contains("Helper"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn modules_calls_in_functions() {
+        use crate::analyse;
+        use crate::ast::AstNode;
+        use crate::modules;
+        // used functions can be called from function bodies, in the main file and in used files
+        let main = "use \"lib.ez\". use \"lib2.ez\". function G(), return AddOne(1).!
+set x to G() + AddTwo(1).";
+        let dir = files(
+            "calls_in_functions",
+            &[
+                ("main.ez", main),
+                ("lib.ez", "export function AddOne(n), return n + 1.!"),
+                (
+                    "lib2.ez",
+                    "use \"io.ez\". export function AddTwo(n), return n + Two().!",
+                ),
+                ("io.ez", "export function Two(), return 2.!"),
+            ],
+        );
+        let mut modules = modules::load(vec![(dir.join("main.ez"), main.to_string())]).unwrap();
+        for module in &mut modules {
+            // the externs are before the functions that call them
+            let first_func = module
+                .ast
+                .tree
+                .iter()
+                .position(|n| matches!(n, AstNode::Func { .. }));
+            let last_extern = module
+                .ast
+                .tree
+                .iter()
+                .rposition(|n| matches!(n, AstNode::Extern { .. }));
+            if let (Some(func), Some(ext)) = (first_func, last_extern) {
+                assert!(ext < func, "{}: {:?}", module.name, module.ast.tree);
+            }
+            analyse::analize(&mut module.ast).unwrap();
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn modules_many_roots() {
+        use crate::modules;
+        let main = "use \"lib.ez\". set x to F().";
+        let lib = "export function F(), return 1.!";
+        let other = "export function G(), return F().! use \"lib.ez\".";
+        let dir = files(
+            "roots",
+            &[("main.ez", main), ("lib.ez", lib), ("other.ez", other)],
+        );
+        let read = |file: &str| {
+            let path = dir.join(file);
+            let input = std::fs::read_to_string(&path).unwrap();
+            (path, input)
+        };
+        // lib.ez is given and also used, but it is only loaded once
+        let modules =
+            modules::load(vec![read("main.ez"), read("lib.ez"), read("other.ez")]).unwrap();
+        let loaded: Vec<(&str, bool)> = modules.iter().map(|m| (m.name.as_str(), m.root)).collect();
+        assert_eq!(loaded, vec![("lib", true), ("main", true), ("other", true)]);
+        assert!(modules[1].top_level_code().is_some());
+        assert!(modules[0].top_level_code().is_none());
+
+        // only one of them can have the `_start`
+        std::fs::write(dir.join("other.ez"), "set y to 2.").unwrap();
+        let errs = modules::load(vec![read("main.ez"), read("other.ez")]).unwrap_err();
+        assert_eq!(errs.len(), 1, "{:?}", errs);
+        let other = dir.join("other.ez").display().to_string();
+        assert_eq!(errs[0].file.as_ref(), Some(&other));
+        assert!(errs[0].message.contains("main.ez"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,11 +1,11 @@
 //! the parser module
 
 use crate::ast::*;
-use crate::lexer::{Locs, Token};
+use crate::lexer::{Locs, Span, Token};
 use std::cmp::Ordering;
 
 /// AstRoot <- Vec<Ast>
-pub fn parse(input: Vec<Token>, locs_input: Vec<u32>) -> Result<AstRoot, ParserError> {
+pub fn parse(input: Vec<Token>, locs_input: Locs) -> Result<AstRoot, Vec<ParserError>> {
     let mut tree = Parser::new(input, locs_input).parse(true)?;
     // sort it so that funcs are on top of vec so that codegen is MUCH easier
     tree.sort_by(|a, b| match (a, b) {
@@ -25,6 +25,17 @@ pub fn parse(input: Vec<Token>, locs_input: Vec<u32>) -> Result<AstRoot, ParserE
     })
 }
 
+/// Expr EndOfLine?. one expression on its own, like the ones typed into the repl
+pub fn parse_expression(input: Vec<Token>, locs_input: Locs) -> Result<Expr, ParserError> {
+    let mut parser = Parser::new(input, locs_input);
+    let expr = parser.parse_expr()?;
+    if parser.cur_tok() == Token::EndOfLine {
+        parser.next();
+    }
+    parser.expect_eat_token(Token::Eof)?;
+    Ok(expr)
+}
+
 /// the parser
 #[derive(Debug)]
 struct Parser {
@@ -34,6 +45,8 @@ struct Parser {
     pos_input: usize,
     /// the debug info of the locations of the tokens
     locs_input: Locs,
+    /// the errors found so far
+    errors: Vec<ParserError>,
 }
 
 /// an error in the parsing
@@ -42,7 +55,7 @@ pub enum ParserError {
     ExectedOneFoundAnother {
         expected: Token,
         found: Token,
-        pos: u32,
+        span: Span,
     },
 }
 
@@ -56,6 +69,7 @@ impl Parser {
             input,
             pos_input: 0,
             locs_input,
+            errors: Vec::new(),
         }
     }
 
@@ -81,9 +95,17 @@ impl Parser {
         ParserError::ExectedOneFoundAnother {
             expected,
             found,
-            pos: self.locs_input[self.pos_input],
+            span: self.cur_span(),
         }
     }
+    /// the span of the current token
+    fn cur_span(&self) -> Span {
+        self.locs_input[self.pos_input]
+    }
+    /// a span from `start` to the end of the last token that was eaten
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.locs_input[self.pos_input - 1])
+    }
     // /// Peek one token ahead without eating it. may need in future
     fn peek(self: &mut Self) -> Token {
         self.input[self.pos_input + 1].clone()
@@ -105,35 +127,119 @@ impl Parser {
         }
         Err(self.expected_token_err(token, self.cur_tok()))
     }
-    /// The function that does the parsing
-    fn parse(self: &mut Self, toplevel: bool) -> Result<Vec<AstNode>, ParserError> {
+    /// The function that does the parsing. all of the errors in the input are returned, not just the first one
+    fn parse(&mut self, toplevel: bool) -> Result<Vec<AstNode>, Vec<ParserError>> {
+        let tree = self.parse_block(toplevel);
+        if self.errors.is_empty() {
+            Ok(tree)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+    /// parse statements until the end of the block. a statement with an error is skipped and the error is saved in `self.errors`
+    fn parse_block(&mut self, toplevel: bool) -> Vec<AstNode> {
         let mut tree = Vec::new();
         while self.cur_tok() != Token::Eof {
+            if self.cur_tok() == Token::ExclaimMark && !toplevel {
+                break;
+            }
+            if let Err(e) = self.parse_stmt(&mut tree, toplevel) {
+                self.errors.push(e);
+                self.synchronize(toplevel);
+            }
+        }
+        tree
+    }
+    /// parse one statement into `tree`
+    fn parse_stmt(&mut self, tree: &mut Vec<AstNode>, toplevel: bool) -> Result<(), ParserError> {
+        match self.cur_tok() {
+            Token::Kset => self.parse_set_stmt(tree)?,
+            Token::Kchange => self.parse_change_stmt(tree)?,
+            Token::Kloop => self.parse_loop_stmt(tree)?,
+            Token::Kif => self.parse_if_stmt(tree)?,
+            Token::Kcall => self.parse_call_stmt(tree)?,
+            Token::Iden(_) if matches!(self.peek(), Token::Lparen | Token::Possessive) => {
+                self.parse_call_stmt(tree)?
+            }
+            Token::Kfunc if toplevel => self.parse_func(tree)?,
+            Token::Kexport if toplevel => self.parse_exported_func(tree)?,
+            Token::Kextern if toplevel => self.parse_extern(tree)?,
+            Token::Kuse if toplevel => {
+                let start = self.cur_span();
+                self.expect_eat_token(Token::Kuse)?;
+                let path = match self.cur_tok() {
+                    Token::Path(p) => {
+                        self.next();
+                        p
+                    }
+                    t => {
+                        return Err(self.expected_token_err(Token::Path(String::from("lib.ez")), t))
+                    }
+                };
+                self.expect_eat_token(Token::EndOfLine)?;
+                tree.push(AstNode::Use {
+                    path,
+                    span: self.span_from(start),
+                });
+            }
+            Token::Kreturn if !toplevel => {
+                let start = self.cur_span();
+                self.expect_eat_token(Token::Kreturn)?;
+                let e = if self.cur_tok() == Token::EndOfLine {
+                    None
+                } else {
+                    Some(self.parse_expr()?)
+                };
+                self.expect_eat_token(Token::EndOfLine)?;
+                tree.push(AstNode::Return {
+                    val: e,
+                    span: self.span_from(start),
+                });
+            }
+            Token::Kbreak => {
+                let start = self.cur_span();
+                self.expect_eat_token(Token::Kbreak)?;
+                self.expect_eat_token(Token::EndOfLine)?;
+                tree.push(AstNode::Break {
+                    span: self.span_from(start),
+                });
+            }
+            t => return Err(self.expected_token_err(Token::Eof, t)),
+        }
+        Ok(())
+    }
+    /// after an error, skip to the end of the broken statement so parsing can go on from the next one.
+    /// blocks (`,` ... `!`) opened while skipping are skipped whole. commas in parens and array literals don't open blocks
+    fn synchronize(&mut self, toplevel: bool) {
+        let mut blocks = 0;
+        let mut nesting = 0;
+        loop {
             match self.cur_tok() {
-                Token::Kset => self.parse_set_stmt(&mut tree)?,
-                Token::Kchange => self.parse_change_stmt(&mut tree)?,
-                Token::Kloop => self.parse_loop_stmt(&mut tree)?,
-                Token::Kif => self.parse_if_stmt(&mut tree)?,
-                Token::ExclaimMark if !toplevel => break,
-                Token::Kfunc if toplevel => self.parse_func(&mut tree)?,
-                Token::Kexport if toplevel => self.parse_exported_func(&mut tree)?,
-                Token::Kextern if toplevel => self.parse_extern(&mut tree)?,
-                Token::Kreturn if !toplevel => {
-                    self.expect_eat_token(Token::Kreturn)?;
-                    let e = self.parse_expr()?;
-                    self.expect_eat_token(Token::EndOfLine)?;
-                    tree.push(AstNode::Return { val: e });
+                Token::Eof => return,
+                Token::Lparen | Token::OpenBrak => nesting += 1,
+                Token::Rparen | Token::CloseBrak if nesting > 0 => nesting -= 1,
+                Token::Comma if nesting == 0 => blocks += 1,
+                Token::EndOfLine if blocks == 0 => {
+                    self.pos_input += 1;
+                    return;
                 }
-                Token::Kbreak => {
-                    self.expect_eat_token(Token::Kbreak)?;
-                    self.expect_eat_token(Token::EndOfLine)?;
-                    tree.push(AstNode::Break);
+                Token::ExclaimMark if blocks > 0 => {
+                    blocks -= 1;
+                    if blocks == 0 {
+                        self.pos_input += 1;
+                        return;
+                    }
                 }
-                Token::Eof => break,
-                t => return Err(self.expected_token_err(Token::Eof, t)),
+                // the end of the block we are in. leave it for the block to eat
+                Token::ExclaimMark if !toplevel => return,
+                Token::ExclaimMark => {
+                    self.pos_input += 1;
+                    return;
+                }
+                _ => {}
             }
+            self.pos_input += 1;
         }
-        Ok(tree)
     }
     /// Iden <- String
     fn parse_iden(&mut self) -> Result<String, ParserError> {
@@ -152,8 +258,9 @@ impl Parser {
     fn parse_expr_iden(&mut self) -> Result<Expr, ParserError> {
         match self.cur_tok() {
             Token::Iden(s) => {
+                let span = self.cur_span();
                 self.pos_input += 1;
-                Ok(Expr::Iden(s))
+                Ok(Expr::Iden(s, span))
             }
             t => Err(self.expected_token_err(Token::Iden(String::from("")), t)),
         }
@@ -162,8 +269,9 @@ impl Parser {
     fn parse_expr_number(&mut self) -> Result<Expr, ParserError> {
         match self.cur_tok() {
             Token::IntLit(s) => {
+                let span = self.cur_span();
                 self.pos_input += 1;
-                Ok(Expr::Number(s))
+                Ok(Expr::Number(s, span))
             }
             t => Err(self.expected_token_err(Token::IntLit(String::from("")), t)),
         }
@@ -192,10 +300,12 @@ impl Parser {
                 rhs = self.parse_bin_op_rhs(pres + 1, &rhs)?;
                 // then loop around
             }
+            let span = lhs.span().to(rhs.span());
             lhs = Expr::BinOp {
                 op: convert_tok_to_ast_binop(bin_op),
                 lhs: Box::new(lhs),
                 rhs: Box::new(rhs),
+                span,
             };
         }
     }
@@ -221,14 +331,20 @@ impl Parser {
     }
     /// Extern <- Kextern Fnproto.
     fn parse_extern(&mut self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         self.expect_eat_token(Token::Kextern)?;
         self.expect_eat_token(Token::Kfunc)?;
-        let (func_name, items_in_func) = self.parse_func_proto()?;
+        let (func_name, items_in_func, variadic) = self.parse_func_proto(true)?;
+        let returns_nothing = self.parse_returns_nothing()?;
+        self.expect_eat_token(Token::EndOfLine)?;
         tree.push(AstNode::Extern {
+            module: None,
             name: func_name,
             args: items_in_func,
+            variadic,
+            returns_nothing,
+            span: self.span_from(start),
         });
-        self.expect_eat_token(Token::EndOfLine)?;
         Ok(())
     }
     /// Type <- Iden
@@ -250,16 +366,34 @@ impl Parser {
         Ok(Type::Num(self.parse_iden()?))
         // }
     }
-    /// FnProto <- Iden Lparen (Iden ,)* Rparen
-    fn parse_func_proto(&mut self) -> Result<(String, Vec<Type>), ParserError> {
+    /// ReturnsNothing <- ("returns" "nothing")?. they are not keywords so they can still be used as names
+    fn parse_returns_nothing(&mut self) -> Result<bool, ParserError> {
+        if self.cur_tok() != Token::Iden(String::from("returns")) {
+            return Ok(false);
+        }
+        self.next();
+        self.expect_eat_token(Token::Iden(String::from("nothing")))?;
+        Ok(true)
+    }
+    /// FnProto <- Iden Lparen (Iden ,)* Ellipsis? Rparen. the ellipsis is only allowed when `variadic_ok`, for external functions
+    /// gives back the name, the args and if it is variadic
+    fn parse_func_proto(
+        &mut self,
+        variadic_ok: bool,
+    ) -> Result<(String, Vec<Type>, bool), ParserError> {
         let func_name = self.parse_iden()?;
         let mut items_in_func = Vec::new();
         self.expect_eat_token(Token::Lparen)?;
         if self.cur_tok() == Token::Rparen {
             self.expect_eat_token(Token::Rparen)?;
-            return Ok((func_name, items_in_func));
+            return Ok((func_name, items_in_func, false));
         }
-        while let Token::Iden(_) = self.cur_tok() {
+        loop {
+            if variadic_ok && self.cur_tok() == Token::Ellipsis {
+                self.next();
+                self.expect_eat_token(Token::Rparen)?;
+                return Ok((func_name, items_in_func, true));
+            }
             items_in_func.push(self.parse_type()?);
             match self.cur_tok() {
                 Token::Comma => self.expect_eat_token(Token::Comma)?,
@@ -270,26 +404,30 @@ impl Parser {
                 t => return Err(self.expected_token_err(Token::Rparen, t)),
             }
         }
-        Ok((func_name, items_in_func))
+        Ok((func_name, items_in_func, false))
     }
     /// Expr <- Number | Iden | ParenExpr | Expr BinOp Expr (parsing an expression but not top level) | AtSign Iden | Iden OpenBrak Expr CloseBrak
     fn parse_expr_primary(&mut self) -> Result<Expr, ParserError> {
         match self.cur_tok() {
             Token::IntLit(_) => self.parse_expr_number(),
-            Token::Iden(_) if self.peek() == Token::Lparen => self.parse_expr_funcall(),
+            Token::Iden(_) if matches!(self.peek(), Token::Lparen | Token::Possessive) => {
+                self.parse_expr_funcall()
+            }
             Token::Iden(a) if self.peek() == Token::OpenBrak => {
+                let start = self.cur_span();
                 // eat the iden
                 self.next();
                 // i am superstious
                 self.expect_eat_token(Token::OpenBrak)?;
-                let r = Expr::AccessArray(a, Box::new(self.parse_expr()?));
+                let index = self.parse_expr()?;
                 self.expect_eat_token(Token::CloseBrak)?;
-                Ok(r)
+                Ok(Expr::AccessArray(a, Box::new(index), self.span_from(start)))
             }
             Token::AtSign => {
+                let start = self.cur_span();
                 self.next();
                 let i = self.parse_iden()?;
-                Ok(Expr::DerefPtr(i))
+                Ok(Expr::DerefPtr(i, self.span_from(start)))
             }
             Token::Iden(_) => self.parse_expr_iden(),
             Token::Lparen => self.parse_expr_paren(),
@@ -298,17 +436,26 @@ impl Parser {
             }
         }
     }
-    /// Expr <- Iden Lparen ParenExpr,* Rparen
+    /// Expr <- (Iden Possessive)? Iden Lparen ParenExpr,* Rparen
     fn parse_expr_funcall(&mut self) -> Result<Expr, ParserError> {
-        let func_name = self.parse_iden()?;
+        let start = self.cur_span();
+        let mut func_name = self.parse_iden()?;
+        let mut module = None;
+        if self.cur_tok() == Token::Possessive {
+            self.next();
+            module = Some(func_name);
+            func_name = self.parse_iden()?;
+        }
         self.expect_eat_token(Token::Lparen)?;
         let mut args = Vec::new();
         if self.cur_tok() == Token::Rparen {
             self.expect_eat_token(Token::Rparen)?;
             return Ok(Expr::FuncCall {
+                module,
                 func_name,
                 args,
                 external: None,
+                span: self.span_from(start),
             });
         }
         while let Token::Iden(_) | Token::IntLit(_) | Token::AtSign = self.cur_tok() {
@@ -323,9 +470,11 @@ impl Parser {
             }
         }
         Ok(Expr::FuncCall {
+            module,
             func_name,
             args,
             external: None,
+            span: self.span_from(start),
         })
     }
     /// Setor <- Expr | ArrLit
@@ -355,48 +504,61 @@ impl Parser {
     //
     /// Function <- FnProto OpenBlock Ast CloseBlock
     fn parse_func(&mut self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         self.expect_eat_token(Token::Kfunc)?;
-        let (name, args) = self.parse_func_proto()?;
+        let (name, args, _) = self.parse_func_proto(false)?;
+        let returns_nothing = self.parse_returns_nothing()?;
         self.expect_eat_token(Token::Comma)?;
-        let body = self.parse(false)?;
+        let body = self.parse_block(false);
         self.expect_eat_token(Token::ExclaimMark)?;
         tree.push(AstNode::Func {
             name,
             args,
             body,
             export: false,
+            returns_nothing,
             vars_declared: None,
+            span: self.span_from(start),
         });
         Ok(())
     }
     /// ExportedFunc <- Kexport Function
     fn parse_exported_func(&mut self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         self.expect_eat_token(Token::Kexport)?;
         self.expect_eat_token(Token::Kfunc)?;
-        let (name, args) = self.parse_func_proto()?;
+        let (name, args, _) = self.parse_func_proto(false)?;
+        let returns_nothing = self.parse_returns_nothing()?;
         self.expect_eat_token(Token::Comma)?;
-        let body = self.parse(false)?;
+        let body = self.parse_block(false);
         self.expect_eat_token(Token::ExclaimMark)?;
         tree.push(AstNode::Func {
             name,
             args,
             body,
             export: true,
+            returns_nothing,
             vars_declared: None,
+            span: self.span_from(start),
         });
         Ok(())
     }
     /// LoopNode <- Kloop OpenBlock Ast CloseBlock
     fn parse_loop_stmt(self: &mut Self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         self.expect_eat_token(Token::Kloop)?;
         self.expect_eat_token(Token::Comma)?;
-        let body: Vec<AstNode> = self.parse(false)?;
+        let body: Vec<AstNode> = self.parse_block(false);
         self.expect_eat_token(Token::ExclaimMark)?;
-        tree.push(AstNode::Loop { body });
+        tree.push(AstNode::Loop {
+            body,
+            span: self.span_from(start),
+        });
         Ok(())
     }
     /// IfNode <- Kif Expr OpenBlock Ast CloseBlock
     fn parse_if_stmt(self: &mut Self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         // Kif
         self.expect_eat_token(Token::Kif)?;
         // Expr
@@ -404,18 +566,34 @@ impl Parser {
         // OpenBlock
         self.expect_eat_token(Token::Comma)?;
         // Ast
-        let body: Vec<AstNode> = self.parse(false)?;
+        let body: Vec<AstNode> = self.parse_block(false);
         // CloseBlock
         self.expect_eat_token(Token::ExclaimMark)?;
         tree.push(AstNode::If {
             guard,
             body,
             vars_declared: None,
+            span: self.span_from(start),
+        });
+        Ok(())
+    }
+    /// CallNode <- Kcall? FuncCall EndOfLine
+    fn parse_call_stmt(&mut self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
+        if self.cur_tok() == Token::Kcall {
+            self.next();
+        }
+        let func_call = self.parse_expr_funcall()?;
+        self.expect_eat_token(Token::EndOfLine)?;
+        tree.push(AstNode::Call {
+            func_call,
+            span: self.span_from(start),
         });
         Ok(())
     }
     /// SetNode <- Kset KIden Kto Expr EndOfLine
     fn parse_set_stmt(self: &mut Self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         // Kset
         self.expect_eat_token(Token::Kset)?;
         // Iden
@@ -423,18 +601,19 @@ impl Parser {
         self.expect_eat_token(Token::Kto)?;
         // Expr
         let setor = self.parse_val()?;
-        let node = AstNode::SetOrChange {
+        // EndOfLine
+        self.expect_eat_token(Token::EndOfLine)?;
+        tree.push(AstNode::SetOrChange {
             sete,
             type_of: TypeOfSetOrChange::SetIden,
             setor,
-        };
-        // EndOfLine
-        self.expect_eat_token(Token::EndOfLine)?;
-        tree.push(node);
+            span: self.span_from(start),
+        });
         Ok(())
     }
     /// ChangeNode <- Kchange SpecialSete Kto Expr EndOfLine
     fn parse_change_stmt(self: &mut Self, tree: &mut Vec<AstNode>) -> Result<(), ParserError> {
+        let start = self.cur_span();
         // Kset
         self.expect_eat_token(Token::Kchange)?;
         // Iden
@@ -443,14 +622,14 @@ impl Parser {
         self.expect_eat_token(Token::Kto)?;
         // Expr
         let setor = self.parse_val()?;
-        let node = AstNode::SetOrChange {
+        // EndOfLine
+        self.expect_eat_token(Token::EndOfLine)?;
+        tree.push(AstNode::SetOrChange {
             sete: sete.0,
             type_of: sete.1,
             setor,
-        };
-        // EndOfLine
-        self.expect_eat_token(Token::EndOfLine)?;
-        tree.push(node);
+            span: self.span_from(start),
+        });
         Ok(())
     }
     /// SpecialSete <- KIden | AtSign KIden | KIden OpenBrak [ Expr ] CloseBlock
@@ -479,6 +658,79 @@ mod tests {
     use super::*;
     use crate::ast::{AstNode, Expr, Val};
     use crate::lexer;
+    use crate::lexer::Span;
+
+    /// the tree with every span cleared, for comparing with trees written out by hand
+    fn no_spans(mut tree: Vec<AstNode>) -> Vec<AstNode> {
+        tree.iter_mut().for_each(clear_node);
+        tree
+    }
+    fn clear_node(node: &mut AstNode) {
+        match node {
+            AstNode::SetOrChange {
+                setor,
+                type_of,
+                span,
+                ..
+            } => {
+                match setor {
+                    Val::Expr(e) => clear_expr(e),
+                    Val::Array(es) => es.iter_mut().for_each(clear_expr),
+                }
+                if let crate::ast::TypeOfSetOrChange::ChangeArrIndex(e) = type_of {
+                    clear_expr(e);
+                }
+                *span = Span::default();
+            }
+            AstNode::If {
+                guard, body, span, ..
+            } => {
+                clear_expr(guard);
+                body.iter_mut().for_each(clear_node);
+                *span = Span::default();
+            }
+            AstNode::Loop { body, span } | AstNode::Func { body, span, .. } => {
+                body.iter_mut().for_each(clear_node);
+                *span = Span::default();
+            }
+            AstNode::Return { val, span } => {
+                val.iter_mut().for_each(clear_expr);
+                *span = Span::default();
+            }
+            AstNode::Call { func_call, span } => {
+                clear_expr(func_call);
+                *span = Span::default();
+            }
+            AstNode::Break { span } | AstNode::Extern { span, .. } | AstNode::Use { span, .. } => {
+                *span = Span::default()
+            }
+        }
+    }
+    fn clear_expr(expr: &mut Expr) {
+        match expr {
+            Expr::Number(_, span) | Expr::Iden(_, span) | Expr::DerefPtr(_, span) => {
+                *span = Span::default()
+            }
+            Expr::BinOp { lhs, rhs, span, .. } => {
+                clear_expr(lhs);
+                clear_expr(rhs);
+                *span = Span::default();
+            }
+            Expr::FuncCall { args, span, .. } => {
+                for arg in args {
+                    match arg {
+                        Val::Expr(e) => clear_expr(e),
+                        Val::Array(es) => es.iter_mut().for_each(clear_expr),
+                    }
+                }
+                *span = Span::default();
+            }
+            Expr::AccessArray(_, index, span) => {
+                clear_expr(index);
+                *span = Span::default();
+            }
+        }
+    }
     #[test]
     fn parser_set() {
         let mut tokenizer = lexer::Tokenizer::new();
@@ -486,23 +738,29 @@ mod tests {
             "Set x to 10. set y to 5 . set  xarst to 555134234523452345  \n.\n\n",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("10")))
+                    setor: Val::Expr(Expr::Number(String::from("10"), Span::default()))
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("y"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("5")))
+                    setor: Val::Expr(Expr::Number(String::from("5"), Span::default()))
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("xarst"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("555134234523452345")))
+                    setor: Val::Expr(Expr::Number(
+                        String::from("555134234523452345"),
+                        Span::default()
+                    ))
                 }
             ],
             ast
@@ -513,25 +771,31 @@ mod tests {
         let mut tokenizer = lexer::Tokenizer::new();
         let output = tokenizer.lex(&String::from("set p to fib(a,b). set z to lib()."));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("p"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::FuncCall {
+                        module: None,
+                        span: Span::default(),
                         func_name: String::from("fib"),
                         args: vec![
-                            Val::Expr(Expr::Iden(String::from("a"))),
-                            Val::Expr(Expr::Iden(String::from("b")))
+                            Val::Expr(Expr::Iden(String::from("a"), Span::default())),
+                            Val::Expr(Expr::Iden(String::from("b"), Span::default()))
                         ],
                         external: None,
                     })
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("z"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::FuncCall {
+                        module: None,
+                        span: Span::default(),
                         func_name: String::from("lib"),
                         args: vec![],
                         external: None,
@@ -542,25 +806,146 @@ mod tests {
         );
     }
     #[test]
+    fn parser_call_stmt() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from("call PutChar(72). PutChar(x)."));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let ast = parser.parse(true).unwrap();
+        let call = |arg| AstNode::Call {
+            span: Span::default(),
+            func_call: Expr::FuncCall {
+                module: None,
+                span: Span::default(),
+                func_name: String::from("PutChar"),
+                args: vec![Val::Expr(arg)],
+                external: None,
+            },
+        };
+        assert_eq!(
+            vec![
+                call(Expr::Number(String::from("72"), Span::default())),
+                call(Expr::Iden(String::from("x"), Span::default())),
+            ],
+            no_spans(ast.clone())
+        );
+        assert_eq!((ast[1].span().start, ast[1].span().end), (18, 29));
+    }
+    #[test]
+    fn parser_returns_nothing() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from(
+            "external function PutChar(c) returns nothing. function F() returns nothing, return.!",
+        ));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let ast = no_spans(parser.parse(true).unwrap());
+        match &ast[..] {
+            [AstNode::Extern {
+                returns_nothing: true,
+                ..
+            }, AstNode::Func {
+                returns_nothing: true,
+                body,
+                ..
+            }] => assert_eq!(
+                body,
+                &vec![AstNode::Return {
+                    val: None,
+                    span: Span::default()
+                }]
+            ),
+            a => panic!("{:?}", a),
+        }
+    }
+    #[test]
+    fn parser_variadic_extern() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from(
+            "external function printf(fmt, ...). external function F(...). external function G(a).",
+        ));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let ast = parser.parse(true).unwrap();
+        match &ast[..] {
+            [AstNode::Extern {
+                args: printf_args,
+                variadic: true,
+                ..
+            }, AstNode::Extern {
+                args: f_args,
+                variadic: true,
+                ..
+            }, AstNode::Extern {
+                variadic: false, ..
+            }] => {
+                assert_eq!(printf_args, &vec![Type::Num(String::from("fmt"))]);
+                assert!(f_args.is_empty());
+            }
+            a => panic!("{:?}", a),
+        }
+        // only external functions can be variadic, and the `...` has to be last
+        for input in &[
+            "function F(a, ...), return 0.!",
+            "external function F(..., a).",
+        ] {
+            let output = lexer::Tokenizer::new().lex(&String::from(*input));
+            assert!(Parser::new(output.0.unwrap(), output.1)
+                .parse(true)
+                .is_err());
+        }
+    }
+    #[test]
+    fn parser_qualified_call() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from("Math's Square(1). set x to math's Id(2)."));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let ast = parser.parse(true).unwrap();
+        match &ast[..] {
+            [AstNode::Call {
+                func_call:
+                    Expr::FuncCall {
+                        module: Some(a),
+                        func_name: f,
+                        ..
+                    },
+                ..
+            }, AstNode::SetOrChange {
+                setor:
+                    Val::Expr(Expr::FuncCall {
+                        module: Some(b),
+                        func_name: g,
+                        ..
+                    }),
+                ..
+            }] => {
+                assert_eq!((a.as_str(), f.as_str()), ("Math", "Square"));
+                assert_eq!((b.as_str(), g.as_str()), ("math", "Id"));
+            }
+            a => panic!("{:?}", a),
+        }
+    }
+    #[test]
     fn parser_loop() {
         let mut tokenizer = lexer::Tokenizer::new();
         let output = tokenizer.lex(&String::from("Set x to 1. loop, change x to x+1.!"));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("1")))
+                    setor: Val::Expr(Expr::Number(String::from("1"), Span::default()))
                 },
                 AstNode::Loop {
+                    span: Span::default(),
                     body: vec![AstNode::SetOrChange {
+                        span: Span::default(),
                         sete: String::from("x"),
                         type_of: crate::ast::TypeOfSetOrChange::ChangeIden,
                         setor: Val::Expr(Expr::BinOp {
-                            lhs: Box::new(Expr::Iden(String::from("x"))),
-                            rhs: Box::new(Expr::Number(String::from("1"))),
+                            span: Span::default(),
+                            lhs: Box::new(Expr::Iden(String::from("x"), Span::default())),
+                            rhs: Box::new(Expr::Number(String::from("1"), Span::default())),
                             op: BinOp::Add
                         })
                     }]
@@ -576,23 +961,26 @@ mod tests {
             "Set x to 10. set y to 5 . change  x to y  \n.\n\n",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("10")))
+                    setor: Val::Expr(Expr::Number(String::from("10"), Span::default()))
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("y"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("5")))
+                    setor: Val::Expr(Expr::Number(String::from("5"), Span::default()))
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::ChangeIden,
-                    setor: Val::Expr(Expr::Iden(String::from("y")))
+                    setor: Val::Expr(Expr::Iden(String::from("y"), Span::default()))
                 }
             ],
             ast
@@ -603,18 +991,21 @@ mod tests {
         let mut tokenizer = lexer::Tokenizer::new();
         let output = tokenizer.lex(&String::from("Set x to [1,2,3, PutRust(10)]. "));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![AstNode::SetOrChange {
+                span: Span::default(),
                 sete: String::from("x"),
                 type_of: crate::ast::TypeOfSetOrChange::SetIden,
                 setor: Val::Array(vec![
-                    Expr::Number(String::from("1")),
-                    Expr::Number(String::from("2")),
-                    Expr::Number(String::from("3")),
+                    Expr::Number(String::from("1"), Span::default()),
+                    Expr::Number(String::from("2"), Span::default()),
+                    Expr::Number(String::from("3"), Span::default()),
                     Expr::FuncCall {
+                        module: None,
+                        span: Span::default(),
                         func_name: String::from("PutRust"),
-                        args: vec![Val::Expr(Expr::Number(String::from("10")))],
+                        args: vec![Val::Expr(Expr::Number(String::from("10"), Span::default()))],
                         external: None
                     }
                 ])
@@ -627,22 +1018,29 @@ mod tests {
         let mut tokenizer = lexer::Tokenizer::new();
         let output = tokenizer.lex(&String::from("Set x to [1,2]. set z to People(@x)."));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Array(vec![
-                        Expr::Number(String::from("1")),
-                        Expr::Number(String::from("2")),
+                        Expr::Number(String::from("1"), Span::default()),
+                        Expr::Number(String::from("2"), Span::default()),
                     ])
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("z"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::FuncCall {
-                        args: vec![Val::Expr(Expr::DerefPtr(String::from("x"))),],
+                        module: None,
+                        span: Span::default(),
+                        args: vec![Val::Expr(Expr::DerefPtr(
+                            String::from("x"),
+                            Span::default()
+                        )),],
                         func_name: String::from("People"),
                         external: None
                     })
@@ -656,23 +1054,26 @@ mod tests {
         let mut tokenizer = lexer::Tokenizer::new();
         let output = tokenizer.lex(&String::from("Set x to [1,2]. set z to x[1]."));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Array(vec![
-                        Expr::Number(String::from("1")),
-                        Expr::Number(String::from("2")),
+                        Expr::Number(String::from("1"), Span::default()),
+                        Expr::Number(String::from("2"), Span::default()),
                     ])
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("z"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::AccessArray(
                         String::from("x"),
-                        Box::new(Expr::Number(String::from("1")))
+                        Box::new(Expr::Number(String::from("1"), Span::default())),
+                        Span::default()
                     ))
                 }
             ],
@@ -690,23 +1091,27 @@ mod tests {
 ",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![AstNode::Func {
+                span: Span::default(),
                 args: vec![
                     Type::Num(String::from("y")),
                     Type::Num(String::from("z")),
                     Type::Num(String::from("b"))
                 ],
                 export: false,
+                returns_nothing: false,
                 body: vec![
                     AstNode::SetOrChange {
+                        span: Span::default(),
                         sete: String::from("y"),
-                        setor: Val::Expr(Expr::Number(String::from("4")),),
+                        setor: Val::Expr(Expr::Number(String::from("4"), Span::default()),),
                         type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     },
                     AstNode::Return {
-                        val: Expr::Iden(String::from("y"))
+                        span: Span::default(),
+                        val: Some(Expr::Iden(String::from("y"), Span::default()))
                     }
                 ],
                 name: String::from("test"),
@@ -726,23 +1131,27 @@ mod tests {
 ",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![AstNode::Func {
+                span: Span::default(),
                 args: vec![
                     Type::Num(String::from("y")),
                     Type::Num(String::from("z")),
                     Type::Num(String::from("b"))
                 ],
                 export: true,
+                returns_nothing: false,
                 body: vec![
                     AstNode::SetOrChange {
+                        span: Span::default(),
                         sete: String::from("y"),
-                        setor: Val::Expr(Expr::Number(String::from("4")),),
+                        setor: Val::Expr(Expr::Number(String::from("4"), Span::default()),),
                         type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     },
                     AstNode::Return {
-                        val: Expr::Iden(String::from("y"))
+                        span: Span::default(),
+                        val: Some(Expr::Iden(String::from("y"), Span::default()))
                     }
                 ],
                 name: String::from("test"),
@@ -758,39 +1167,47 @@ mod tests {
             "Set x to (5 + 10).set y to 32. if x > 10, if y > 4, change x to 5.!! ",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::BinOp {
-                        lhs: Box::new(Expr::Number(String::from("5"))),
+                        span: Span::default(),
+                        lhs: Box::new(Expr::Number(String::from("5"), Span::default())),
                         op: BinOp::Add,
-                        rhs: Box::new(Expr::Number(String::from("10")))
+                        rhs: Box::new(Expr::Number(String::from("10"), Span::default()))
                     })
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("y"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
-                    setor: Val::Expr(Expr::Number(String::from("32")),),
+                    setor: Val::Expr(Expr::Number(String::from("32"), Span::default()),),
                 },
                 AstNode::If {
+                    span: Span::default(),
                     guard: Expr::BinOp {
-                        lhs: Box::new(Expr::Iden(String::from("x"))),
+                        span: Span::default(),
+                        lhs: Box::new(Expr::Iden(String::from("x"), Span::default())),
                         op: BinOp::Gt,
-                        rhs: Box::new(Expr::Number(String::from("10")))
+                        rhs: Box::new(Expr::Number(String::from("10"), Span::default()))
                     },
                     body: vec![AstNode::If {
+                        span: Span::default(),
                         guard: Expr::BinOp {
-                            lhs: Box::new(Expr::Iden(String::from("y"))),
+                            span: Span::default(),
+                            lhs: Box::new(Expr::Iden(String::from("y"), Span::default())),
                             op: BinOp::Gt,
-                            rhs: Box::new(Expr::Number(String::from("4")))
+                            rhs: Box::new(Expr::Number(String::from("4"), Span::default()))
                         },
                         body: vec![AstNode::SetOrChange {
+                            span: Span::default(),
                             sete: String::from("x"),
                             type_of: crate::ast::TypeOfSetOrChange::ChangeIden,
-                            setor: Val::Expr(Expr::Number(String::from("5")))
+                            setor: Val::Expr(Expr::Number(String::from("5"), Span::default()))
                         }],
                         vars_declared: None
                     }],
@@ -807,38 +1224,45 @@ mod tests {
             "Set x to (5 + 10). change y to (1-x)+x . set  xarst to y+x  \n.\n\n",
         ));
         let mut parser = Parser::new(output.0.unwrap(), output.1);
-        let ast = parser.parse(true).unwrap();
+        let ast = no_spans(parser.parse(true).unwrap());
         assert_eq!(
             vec![
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("x"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::BinOp {
-                        lhs: Box::new(Expr::Number(String::from("5"))),
+                        span: Span::default(),
+                        lhs: Box::new(Expr::Number(String::from("5"), Span::default())),
                         op: BinOp::Add,
-                        rhs: Box::new(Expr::Number(String::from("10")))
+                        rhs: Box::new(Expr::Number(String::from("10"), Span::default()))
                     })
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("y"),
                     type_of: crate::ast::TypeOfSetOrChange::ChangeIden,
                     setor: Val::Expr(Expr::BinOp {
+                        span: Span::default(),
                         lhs: Box::new(Expr::BinOp {
-                            lhs: Box::new(Expr::Number(String::from("1"))),
+                            span: Span::default(),
+                            lhs: Box::new(Expr::Number(String::from("1"), Span::default())),
                             op: BinOp::Sub,
-                            rhs: Box::new(Expr::Iden(String::from("x")))
+                            rhs: Box::new(Expr::Iden(String::from("x"), Span::default()))
                         }),
                         op: BinOp::Add,
-                        rhs: Box::new(Expr::Iden(String::from("x")))
+                        rhs: Box::new(Expr::Iden(String::from("x"), Span::default()))
                     })
                 },
                 AstNode::SetOrChange {
+                    span: Span::default(),
                     sete: String::from("xarst"),
                     type_of: crate::ast::TypeOfSetOrChange::SetIden,
                     setor: Val::Expr(Expr::BinOp {
-                        lhs: Box::new(Expr::Iden(String::from("y"))),
+                        span: Span::default(),
+                        lhs: Box::new(Expr::Iden(String::from("y"), Span::default())),
                         op: BinOp::Add,
-                        rhs: Box::new(Expr::Iden(String::from("x")))
+                        rhs: Box::new(Expr::Iden(String::from("x"), Span::default()))
                     })
                 }
             ],
@@ -846,6 +1270,57 @@ mod tests {
         );
     }
     #[test]
+    fn parser_spans() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from("set x to 1 + y.\nloop, break.!"));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let ast = parser.parse(true).unwrap();
+        match &ast[0] {
+            AstNode::SetOrChange {
+                span,
+                setor: Val::Expr(e),
+                ..
+            } => {
+                assert_eq!((span.start, span.end), (0, 15));
+                assert_eq!((e.span().start, e.span().end), (9, 14));
+            }
+            n => panic!("{:?}", n),
+        }
+        match &ast[1] {
+            AstNode::Loop { span, body } => {
+                assert_eq!((span.start, span.end), (16, 29));
+                assert_eq!((body[0].span().start, body[0].span().end), (22, 28));
+            }
+            n => panic!("{:?}", n),
+        }
+    }
+    #[test]
+    fn parser_recovers_after_errors() {
+        let mut tokenizer = lexer::Tokenizer::new();
+        let output = tokenizer.lex(&String::from(
+            "set x to . set y to 1. loop, set to 2. if f(1, 2) 3, break.! break. ! set 5 to 4. set z to 3.",
+        ));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        let errs = parser.parse(true).unwrap_err();
+        let found: Vec<Token> = errs
+            .into_iter()
+            .map(|ParserError::ExectedOneFoundAnother { found, .. }| found)
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                Token::EndOfLine,
+                Token::Kto,
+                Token::IntLit(String::from("3")),
+                Token::IntLit(String::from("5")),
+            ]
+        );
+        // the good statements still got parsed
+        let output = lexer::Tokenizer::new().lex(&String::from("set x to . set y to 1."));
+        let mut parser = Parser::new(output.0.unwrap(), output.1);
+        assert_eq!(parser.parse_block(true).len(), 1);
+    }
+    #[test]
     #[should_panic]
     fn parser_bad_stuff() {
         let mut tokenizer = lexer::Tokenizer::new();
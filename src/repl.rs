@@ -0,0 +1,351 @@
+//! `ezc repl`: type in ez a statement (or a whole block) at a time and see what it does. the code is lexed, parsed
+//! and analysed one input at a time and run with the interpreter, so variables and functions stay from one input to
+//! the next. an expression on its own prints its value
+
+use crate::analyse::Session;
+use crate::ast::{AstNode, AstRoot, Expr};
+use crate::errors::{Diagnostic, Diagnostics, ErrorFormat, Printer, WarningOptions};
+use crate::interpret::{Interpreter, State};
+use crate::lexer::{Token, Tokenizer};
+use crate::parser;
+use crate::CompiledFile;
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+/// the name of the "file" in the errors
+const REPL_FILE: &str = "<repl>";
+
+/// the things that an input with an error has to be taken back out of
+#[derive(Clone)]
+struct Known {
+    tokenizer: Tokenizer,
+    analysis: Session,
+    /// the functions that give back nothing worth printing: the external ones and the ones that `returns nothing`
+    quiet: HashSet<String>,
+}
+
+/// everything typed in so far
+pub struct Repl {
+    known: Known,
+    /// the inputs that worked, one after the other, with their functions. the spans of the functions from earlier
+    /// inputs point into this, so the errors in them can still show their code
+    file: CompiledFile,
+    state: State,
+}
+
+impl std::fmt::Debug for Repl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Repl")
+            .field("input", &self.file.input)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Repl::new()
+    }
+}
+
+impl Repl {
+    /// a repl that nothing has been typed into
+    pub fn new() -> Self {
+        Repl {
+            known: Known {
+                tokenizer: Tokenizer::new(),
+                analysis: Session::new(),
+                quiet: HashSet::new(),
+            },
+            file: CompiledFile {
+                path: PathBuf::from(REPL_FILE),
+                input: String::new(),
+                name: String::from("repl"),
+                root: true,
+                main: true,
+                ast: AstRoot {
+                    static_vars: None,
+                    tree: Vec::new(),
+                },
+                asm: String::new(),
+                header: None,
+            },
+            state: State::default(),
+        }
+    }
+    /// run one input. what it prints goes to `output` and `InputLine` reads from `input`. gives back the warnings.
+    /// an input with an error is forgotten, but what it did before a runtime error stays done
+    pub fn eval(
+        &mut self,
+        text: &str,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<Vec<Diagnostic>, Diagnostics> {
+        let known = self.known.clone();
+        let (input_len, tree_len) = (self.file.input.len(), self.file.ast.tree.len());
+        let result = self.eval_input(text, input, output);
+        let _ = output.flush();
+        if result.is_err() {
+            self.known = known;
+            self.file.input.truncate(input_len);
+            self.file.ast.tree.truncate(tree_len);
+        }
+        result
+    }
+    fn eval_input(
+        &mut self,
+        text: &str,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> Result<Vec<Diagnostic>, Diagnostics> {
+        self.file.input.push_str(text);
+        // the tokenizer goes on from where the last input stopped, so the spans are from the start of the history
+        let (tokens, locs) = match self.known.tokenizer.lex(&self.file.input) {
+            (Ok(tokens), locs) => (tokens, locs),
+            (Err(errs), _) => {
+                return Err(self.errors(errs.iter().map(|e| e.diagnostic(&self.file.input))))
+            }
+        };
+        if let Ok(mut expr) = parser::parse_expression(tokens.clone(), locs.clone()) {
+            let warnings = self
+                .known
+                .analysis
+                .analyze_expr(&mut expr)
+                .map_err(|errs| self.errors(errs.iter().map(|e| e.diagnostic(&self.file.input))))?;
+            let value = self.run(input, output, |i| i.eval_top_level(&expr, 0))?;
+            if !self.is_quiet(&expr) {
+                let _ = writeln!(output, "{}", value);
+            }
+            return Ok(self.warnings(warnings));
+        }
+        let mut tree = parser::parse(tokens, locs)
+            .map_err(|errs| self.errors(errs.iter().map(|e| e.diagnostic(&self.file.input))))?
+            .tree;
+        if let Some(node) = tree.iter().find(|n| matches!(n, AstNode::Use { .. })) {
+            let d = Diagnostic::at(
+                crate::errors::Severity::Error,
+                "E405",
+                String::from("`use` doesn't work in the repl\nHint: declare the functions you need with `external function`, or type them in"),
+                &self.file.input,
+                node.span(),
+            );
+            return Err(self.errors(std::iter::once(d)));
+        }
+        let warnings = self
+            .known
+            .analysis
+            .analyze(&mut tree)
+            .map_err(|errs| self.errors(errs.iter().map(|e| e.diagnostic(&self.file.input))))?;
+        // the functions are kept so later inputs can call them. the rest is run now and then forgotten
+        let (functions, statements): (Vec<AstNode>, Vec<AstNode>) = tree
+            .into_iter()
+            .partition(|n| matches!(n, AstNode::Func { .. } | AstNode::Extern { .. }));
+        for function in &functions {
+            match function {
+                AstNode::Extern { name, .. }
+                | AstNode::Func {
+                    name,
+                    returns_nothing: true,
+                    ..
+                } => {
+                    self.known.quiet.insert(name.clone());
+                }
+                _ => {}
+            }
+        }
+        self.file.ast.tree.extend(functions);
+        self.run(input, output, |i| i.run_top_level(&statements, 0))?;
+        Ok(self.warnings(warnings))
+    }
+    /// run something with an interpreter that goes on from what the inputs before made
+    fn run<T>(
+        &mut self,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+        f: impl FnOnce(&mut Interpreter) -> Result<T, Box<Diagnostic>>,
+    ) -> Result<T, Diagnostics> {
+        let state = std::mem::take(&mut self.state);
+        let mut interpreter =
+            Interpreter::with_state(std::slice::from_ref(&self.file), state, input, output);
+        let result = f(&mut interpreter);
+        self.state = interpreter.into_state();
+        result.map_err(|e| self.errors(std::iter::once(*e)))
+    }
+    /// a call on its own to a function that gives back nothing worth printing
+    fn is_quiet(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::FuncCall { func_name, .. } => self.known.quiet.contains(func_name),
+            _ => false,
+        }
+    }
+    fn errors(&self, errors: impl Iterator<Item = Diagnostic>) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new(0);
+        for e in errors {
+            diagnostics.push(e.in_file(&self.file.path));
+        }
+        diagnostics
+    }
+    fn warnings(&self, warnings: Vec<crate::errors::Warning>) -> Vec<Diagnostic> {
+        let options = WarningOptions::default();
+        warnings
+            .iter()
+            .filter(|w| options.is_on(w.kind))
+            .map(|w| {
+                w.diagnostic(&self.file.input, false)
+                    .in_file(&self.file.path)
+            })
+            .collect()
+    }
+}
+
+/// does `text` have a whole input, or is a block still open. a block is opened by `if`, `loop` and `function`
+/// (but not `external function`) and closed by `!`
+pub fn is_complete(text: &str) -> bool {
+    let tokens = match Tokenizer::new().lex(text).0 {
+        Ok(tokens) => tokens,
+        // the errors get shown when it is run
+        Err(_) => return true,
+    };
+    let mut open = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Kif | Token::Kloop => open += 1,
+            Token::Kfunc if i == 0 || tokens[i - 1] != Token::Kextern => open += 1,
+            Token::ExclaimMark => open -= 1,
+            _ => {}
+        }
+    }
+    open <= 0
+}
+
+/// the loop of `ezc repl`. it reads from `input` until it ends. `prompts` is for when a person is typing
+pub fn run(input: &mut dyn BufRead, output: &mut dyn Write, printer: &Printer, prompts: bool) {
+    let mut repl = Repl::new();
+    let mut printer = printer.clone();
+    // every error is in the repl, so it doesn't have to say so
+    if printer.format == ErrorFormat::Human {
+        printer.main_file = Some(String::from(REPL_FILE));
+    }
+    let mut text = String::new();
+    loop {
+        if prompts {
+            let _ = write!(output, "{}", if text.is_empty() { "> " } else { ". " });
+            let _ = output.flush();
+        }
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        text += &line;
+        if !is_complete(&text) {
+            continue;
+        }
+        let whole = std::mem::take(&mut text);
+        if whole.trim().is_empty() {
+            continue;
+        }
+        match repl.eval(&whole, input, output) {
+            Ok(warnings) => warnings.iter().for_each(|w| printer.print(w)),
+            Err(errors) => errors.print(&printer),
+        }
+    }
+    // a block that was never closed
+    if !text.trim().is_empty() {
+        if let Err(errors) = repl.eval(&text, input, output) {
+            errors.print(&printer);
+        }
+    }
+    if prompts {
+        let _ = writeln!(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// type the inputs into a repl. gives back what was printed and the codes of the errors
+    fn session(inputs: &[&str]) -> (String, Vec<&'static str>) {
+        let mut repl = Repl::new();
+        let mut output = Vec::new();
+        let mut codes = Vec::new();
+        for text in inputs {
+            if let Err(errors) = repl.eval(text, &mut "".as_bytes(), &mut output) {
+                codes.extend(errors.all().iter().map(|e| e.code));
+            }
+        }
+        (String::from_utf8(output).unwrap(), codes)
+    }
+
+    #[test]
+    fn repl_keeps_vars_and_functions() {
+        let (output, codes) = session(&[
+            "external function PutNum(n).\n",
+            "set x to 5.\n",
+            "x * 2\n",
+            "function Twice(n),\n  return n * 2.\n!\n",
+            "Twice(x) + 1.\n",
+            "change x to Twice(x).\n",
+            "set a to [1, 2, 3].\n",
+            "a[0] + a[3]\n",
+            // calls to external functions and ones that return nothing don't print what they give back
+            "PutNum(x).\n",
+            "function Say(n) returns nothing,\n  call PutNum(n).\n!\n",
+            "Say(7)\n",
+        ]);
+        assert_eq!(output, "10\n11\n6\n107");
+        assert!(codes.is_empty(), "{:?}", codes);
+    }
+
+    #[test]
+    fn repl_forgets_inputs_with_errors() {
+        let (output, codes) = session(&[
+            // the second set is an error, so the first one is forgotten too
+            "set x to 1. set x to 2.\n",
+            "set x to 3.\n",
+            "x\n",
+            "y + 1\n",
+            "set q to @x.\n",
+            "x +\n",
+            "use \"lib.ez\".\n",
+            "x\n",
+        ]);
+        assert_eq!(output, "3\n3\n");
+        assert_eq!(codes, vec!["E201", "E202", "E215", "E101", "E405"]);
+    }
+
+    #[test]
+    fn repl_runtime_errors() {
+        let mut repl = Repl::new();
+        let mut output = Vec::new();
+        let mut eval = |text: &str| repl.eval(text, &mut "".as_bytes(), &mut output);
+        eval("function Peek(p),\n  return @p.\n!\n").unwrap();
+        eval("set a to [4].\n").unwrap();
+        let errors = eval("Peek(3)\n").unwrap_err();
+        // it is in the function, which was typed in before
+        let error = &errors.all()[0];
+        assert_eq!(error.code, "E502");
+        assert_eq!(error.location.as_ref().unwrap().line, 2);
+        // the repl goes on after it
+        eval("a[1]\n").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "4\n");
+    }
+
+    #[test]
+    fn repl_blocks() {
+        assert!(is_complete("set x to 5.\n"));
+        assert!(!is_complete("if x = 5,\n"));
+        assert!(!is_complete("function F(n),\n  if n,\n  !\n"));
+        assert!(is_complete("function F(n),\n  if n,\n  !\n!\n"));
+        assert!(is_complete("external function PutNum(n).\n"));
+        let mut output = Vec::new();
+        run(
+            &mut "external function PutNum(n).\nset i to 0.\nloop,\n  change i to i + 1.\n  if i > 3,\n    break.\n  !\n  call PutNum(i).\n!\ni\n".as_bytes(),
+            &mut output,
+            &Printer::default(),
+            false,
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "1234\n");
+    }
+}
@@ -1,12 +1,12 @@
+#include <inttypes.h>
 #include <stdio.h>
 
-extern unsigned long long Fib_rec();
-extern unsigned long long Factorial();
+#include "lib.h"
 
 int main() {
   for (int i = 1; i <= 30; i++) {
-    printf("fac(%d)=%lld ", i, Factorial(i));
-    printf("fib(%d)=%lld \n", i, Fib_rec(i));
+    printf("fac(%d)=%" PRId64 " ", i, Factorial(i));
+    printf("fib(%d)=%" PRId64 " \n", i, Fib_rec(i));
   }
   return 0;
 }
@@ -0,0 +1,255 @@
+//! tests for the command line: where `-o` and `--emit` put what they make, the errors for options that don't go
+//! together, and that the temporary files are removed. making a program needs nasm, ld and the standard library, so
+//! the tests that link are left out when they can't be found, like in `differential.rs`
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+mod common;
+use common::{scratch_dir, stdlib};
+
+/// run ezc with `args` in `dir`
+fn ezc(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ezc"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("could not run ezc")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+const MAIN: &str = "set x to 1.\nset y to x + 2.\nset z to y.\n";
+const LIB_A: &str = "export function F(), return 1.!";
+const LIB_B: &str = "export function G(), return 2.!";
+
+#[test]
+fn cmdline_emit_tokens() {
+    let dir = scratch_dir(
+        "cmdline-tokens",
+        &[("main.ez", "set x to 1.\nset y to x + 2.")],
+    );
+    let output = ezc(&dir, &["--emit=tokens", "main.ez"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let lines: Vec<String> = stdout(&output).lines().map(String::from).collect();
+    // one token a line, with the line and column it starts at
+    assert_eq!(
+        lines,
+        vec![
+            "1:1 Kset",
+            "1:5 Iden(\"x\")",
+            "1:7 Kto",
+            "1:10 IntLit(\"1\")",
+            "1:11 EndOfLine",
+            "2:1 Kset",
+            "2:5 Iden(\"y\")",
+            "2:7 Kto",
+            "2:10 Iden(\"x\")",
+            "2:12 BoPlus",
+            "2:14 IntLit(\"2\")",
+            "2:15 EndOfLine",
+            "2:16 Eof",
+        ]
+    );
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cmdline_emit_text_stages() {
+    let dir = scratch_dir(
+        "cmdline-stages",
+        &[("main.ez", MAIN), ("a.ez", LIB_A), ("b.ez", LIB_B)],
+    );
+    // what each stage starts with
+    let stages = [
+        ("tokens", "1:1 Kset\n"),
+        ("ast", "AstRoot {\n    static_vars: None,"),
+        ("analysed-ast", "AstRoot {\n    static_vars: Some("),
+        ("asm", "global _start\n"),
+    ];
+    for (stage, start) in stages {
+        let emit = format!("--emit={}", stage);
+        let printed = ezc(&dir, &[&emit, "main.ez"]);
+        assert!(printed.status.success(), "{}: {}", stage, stderr(&printed));
+        assert!(
+            stdout(&printed).starts_with(start),
+            "{}: {}",
+            stage,
+            stdout(&printed)
+        );
+
+        // with -o it goes to the file instead, and nothing is printed
+        let out = format!("{}.out", stage);
+        let written = ezc(&dir, &[&emit, "-o", &out, "main.ez"]);
+        assert!(written.status.success(), "{}: {}", stage, stderr(&written));
+        assert_eq!(stdout(&written), "", "{}", stage);
+        assert_eq!(
+            fs::read_to_string(dir.join(&out)).unwrap(),
+            stdout(&printed),
+            "{}",
+            stage
+        );
+
+        // with more than one file, each one starts with its name, even when they go to one -o file
+        let many = ezc(&dir, &[&emit, "-o", &out, "a.ez", "b.ez"]);
+        assert!(many.status.success(), "{}: {}", stage, stderr(&many));
+        let text = fs::read_to_string(dir.join(&out)).unwrap();
+        let a = text.find("==> a.ez <==\n");
+        let b = text.find("==> b.ez <==\n");
+        assert!(
+            a == Some(0) && b > a,
+            "{}: the files aren't named:\n{}",
+            stage,
+            text
+        );
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cmdline_errors() {
+    let dir = scratch_dir(
+        "cmdline-errors",
+        &[("main.ez", MAIN), ("a.ez", LIB_A), ("b.ez", LIB_B)],
+    );
+    let expect_error = |args: &[&str], message: &str| {
+        let output = ezc(&dir, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(
+            stderr(&output).contains(message),
+            "{:?}: {}",
+            args,
+            stderr(&output)
+        );
+    };
+    expect_error(
+        &["--emit=bytecode", "main.ez"],
+        "There is no stage called `bytecode`",
+    );
+    // the objects are what is made without linking, and -o can only name one
+    expect_error(
+        &["-nolink", "-o", "x.o", "a.ez", "b.ez"],
+        "-o can only be used with -lib or -nolink when there is one file to compile",
+    );
+    expect_error(
+        &["--emit=obj", "-o", "x.o", "a.ez", "b.ez"],
+        "-o can only be used with -lib or -nolink when there is one file to compile",
+    );
+    expect_error(
+        &["run", "--emit=asm", "main.ez"],
+        "ezc run makes a program and runs it",
+    );
+    expect_error(&["-o"], "Need another option after -o");
+    assert!(!dir.join("x.o").exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cmdline_error_limit() {
+    let dir = scratch_dir(
+        "cmdline-error_limit",
+        &[
+            ("two.ez", "set x to a. set y to b."),
+            ("three.ez", "set x to a. set y to b. set z to c."),
+        ],
+    );
+    // the summary isn't labelled like an error, so it doesn't look like another one
+    let output = ezc(&dir, &["-error-limit", "1", "two.ez"]);
+    let lines: Vec<String> = stderr(&output).lines().map(String::from).collect();
+    assert_eq!(lines.iter().filter(|l| l.starts_with("ERROR")).count(), 1);
+    assert!(lines.contains(&"1 more error not shown (see -error-limit)".to_string()));
+    assert!(lines.contains(&"2 errors found".to_string()));
+    let output = ezc(&dir, &["-error-limit", "1", "three.ez"]);
+    assert!(stderr(&output).contains("\n2 more errors not shown (see -error-limit)\n"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cmdline_temp_dir_removed() {
+    // the C file doesn't compile, so the build fails after the assembly is already in the temporary folder. without
+    // nasm it fails a step before that
+    let dir = scratch_dir(
+        "cmdline-temp_dir",
+        &[("main.ez", MAIN), ("broken.c", "int main( {")],
+    );
+    let tmp = env::temp_dir();
+    let stdlib = stdlib().map(|p| p.display().to_string());
+    let mut runs = vec![
+        vec!["main.ez", "broken.c"],
+        vec!["main.ez", "missing.ez"],
+        vec!["run", "main.ez", "-stdlib-path", "missing.a"],
+    ];
+    if let Some(stdlib) = &stdlib {
+        runs.push(vec!["main.ez", "broken.c", "-stdlib-path", stdlib]);
+    }
+    for args in runs {
+        let child = Command::new(env!("CARGO_BIN_EXE_ezc"))
+            .current_dir(&dir)
+            .args(&args)
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .expect("could not run ezc");
+        let pid = child.id();
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        let prefix = format!("ezc-{}", pid);
+        let left: Vec<String> = fs::read_dir(&tmp)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name == &prefix || name.starts_with(&format!("{}-", prefix)))
+            .collect();
+        assert!(left.is_empty(), "{:?} left {:?}", args, left);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn cmdline_output() {
+    let stdlib = match stdlib() {
+        Some(stdlib) => stdlib,
+        None => return,
+    };
+    let stdlib = stdlib.to_str().unwrap();
+    let dir = scratch_dir(
+        "cmdline-output",
+        &[
+            (
+                "main.ez",
+                "external function PutChar(c) returns nothing. call PutChar(72).",
+            ),
+            ("a.ez", LIB_A),
+        ],
+    );
+    // the program goes where -o says, not to a.out
+    let output = ezc(&dir, &["-o", "hello", "-stdlib-path", stdlib, "main.ez"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!dir.join("a.out").exists());
+    let ran = Command::new(dir.join("hello")).output().unwrap();
+    assert_eq!(stdout(&ran), "H");
+
+    // `ezc run -o` keeps the program it ran
+    let output = ezc(
+        &dir,
+        &["run", "-o", "kept", "-stdlib-path", stdlib, "main.ez"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "H");
+    assert!(dir.join("kept").exists());
+
+    // with -nolink or --emit=obj it names the object
+    for (flag, object) in [("-nolink", "nolink.o"), ("--emit=obj", "emit.o")] {
+        let output = ezc(&dir, &[flag, "-o", object, "a.ez"]);
+        assert!(output.status.success(), "{}: {}", flag, stderr(&output));
+        assert!(dir.join(object).exists(), "{}", flag);
+        assert!(!dir.join("a.ez.o").exists(), "{}", flag);
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,34 @@
+//! helpers for the tests that run the ezc binary
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// the standard library to link the native code with, if it and nasm are there. it is in
+/// `lib/zig-cache/lib/libstd.a` or in `EZC_STDLIB_PATH`
+pub fn stdlib() -> Option<PathBuf> {
+    let path = match env::var_os("EZC_STDLIB_PATH") {
+        Some(p) => PathBuf::from(p),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("lib/zig-cache/lib/libstd.a"),
+    };
+    let has_nasm = Command::new("nasm").arg("-v").output().is_ok();
+    if path.exists() && has_nasm {
+        Some(path)
+    } else {
+        eprintln!("nasm or the standard library is missing, so nothing is linked");
+        None
+    }
+}
+
+/// a folder of its own for each test with `files` in it, because they run at the same time. `name` has to be
+/// different for every test in every file
+pub fn scratch_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = env::temp_dir().join(format!("ezc-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, contents) in files {
+        fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
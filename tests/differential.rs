@@ -0,0 +1,395 @@
+//! differential testing: every program is run inside ezc, as native code and with the interpreter (`ezc run`,
+//! `ezc run -stdlib-path ...` and `ezc run --interpret`), and what they print and their exit status have to be the
+//! same. the programs are the ones in this folder and random ones. the native side needs nasm, ld and the standard
+//! library. it looks for it in `lib/zig-cache/lib/libstd.a` or in `EZC_STDLIB_PATH`, and is left out when it can't
+//! be found
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+mod common;
+use common::{scratch_dir, stdlib};
+
+/// how many random programs get run. `EZC_DIFFERENTIAL_PROGRAMS` can ask for more
+const RANDOM_PROGRAMS: u64 = 200;
+
+/// what a program did
+struct Outcome {
+    stdout: String,
+    status: Option<i32>,
+    /// the errors from ezc or the interpreter. it isn't compared, it only helps to see what went wrong
+    stderr: String,
+}
+
+impl fmt::Debug for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "status {:?}, printed {:?}", self.status, self.stdout)?;
+        if self.status != Some(0) {
+            write!(f, "\n{}", self.stderr.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl Outcome {
+    fn same_as(&self, other: &Outcome) -> bool {
+        self.stdout == other.stdout && self.status == other.status
+    }
+}
+
+/// run `ezc run file` with `args` in `dir`
+fn ezc_run(dir: &Path, file: &str, args: &[&str]) -> Outcome {
+    let output = Command::new(env!("CARGO_BIN_EXE_ezc"))
+        .current_dir(dir)
+        .arg("run")
+        .arg(file)
+        .args(args)
+        .output()
+        .expect("could not run ezc");
+    Outcome {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        status: output.status.code(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    }
+}
+
+/// run `file` in `dir` every way and say what was different
+fn compare(dir: &Path, file: &str, stdlib: Option<&Path>) -> Result<Outcome, String> {
+    let interpreted = ezc_run(dir, file, &["--interpret"]);
+    let mut others = Vec::new();
+    // on other targets `ezc run` links an executable, which is the native run below
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        others.push(("jit", ezc_run(dir, file, &[])));
+    }
+    if let Some(stdlib) = stdlib {
+        let native = ezc_run(dir, file, &["-stdlib-path", stdlib.to_str().unwrap()]);
+        others.push(("native", native));
+    }
+    for (way, outcome) in others {
+        if !outcome.same_as(&interpreted) {
+            return Err(format!(
+                "{:<12} {:?}\ninterpreted: {:?}",
+                format!("{}:", way),
+                outcome,
+                interpreted
+            ));
+        }
+    }
+    Ok(interpreted)
+}
+
+#[test]
+fn differential_test_programs() {
+    let stdlib = stdlib();
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let mut programs: Vec<(PathBuf, String, String)> = fs::read_dir(&tests)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "ez"))
+        .map(|p| {
+            let name = p.file_name().unwrap().to_string_lossy().into_owned();
+            (tests.clone(), name.clone(), name)
+        })
+        .collect();
+    // cinterface starts at a C main, which the interpreter can't run
+    programs.push((tests.join("module"), "main.ez".into(), "module".into()));
+    programs.sort();
+    let mut failed = Vec::new();
+    for (dir, file, intended) in programs {
+        let intended =
+            fs::read_to_string(tests.join("intended").join(intended + ".output")).unwrap();
+        match compare(&dir, &file, stdlib.as_deref()) {
+            Ok(outcome) if outcome.stdout == intended && outcome.status == Some(0) => {}
+            Ok(outcome) => failed.push(format!(
+                "{}: intended {:?}, got {:?}",
+                file, intended, outcome
+            )),
+            Err(e) => failed.push(format!("{}:\n{}", file, e)),
+        }
+    }
+    assert!(failed.is_empty(), "\n{}", failed.join("\n"));
+}
+
+#[test]
+fn differential_random_programs() {
+    let stdlib = stdlib();
+    let dir = scratch_dir("differential-random", &[]);
+    let programs = env::var("EZC_DIFFERENTIAL_PROGRAMS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(RANDOM_PROGRAMS);
+    for seed in 1..=programs {
+        let source = Generator::new(seed).program();
+        fs::write(dir.join("random.ez"), &source).unwrap();
+        match compare(&dir, "random.ez", stdlib.as_deref()) {
+            Ok(outcome) if outcome.status == Some(0) => {}
+            Ok(outcome) => panic!("seed {} did not run: {:?}\n{}", seed, outcome, source),
+            Err(e) => panic!("seed {} is different:\n{}\n{}", seed, e, source),
+        }
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+/// the variables that code can use
+#[derive(Clone, Default)]
+struct Scope {
+    numbers: Vec<String>,
+    /// with how many elements they have
+    arrays: Vec<(String, usize)>,
+    /// the functions it can call, with how many args they take and if they return nothing
+    functions: Vec<(String, usize, bool)>,
+    in_function: bool,
+    returns_nothing: bool,
+    in_loop: bool,
+}
+
+/// makes random programs that the analysis allows and that always stop: functions only call the ones before
+/// them, loops count to a small number and array indexes are always in bounds
+struct Generator {
+    state: u64,
+    names: usize,
+    out: String,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Generator {
+            // xorshift can't start at 0
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+            names: 0,
+            out: String::new(),
+        }
+    }
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+    /// a number in `0..n`
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+    fn name(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{}{}", prefix, self.names)
+    }
+    fn line(&mut self, indent: usize, text: &str) {
+        self.out += &"    ".repeat(indent);
+        self.out += text;
+        self.out.push('\n');
+    }
+
+    fn program(mut self) -> String {
+        self.line(0, "external function PutNum(n).");
+        self.line(0, "external function PutChar(c).");
+        let mut functions = Vec::new();
+        for _ in 0..self.below(4) {
+            let function = self.function(&functions);
+            functions.push(function);
+        }
+        let mut scope = Scope {
+            functions,
+            ..Scope::default()
+        };
+        self.block(&mut scope, 0, 12);
+        self.out
+    }
+
+    fn function(&mut self, functions: &[(String, usize, bool)]) -> (String, usize, bool) {
+        let name = self.name("F");
+        let args: Vec<String> = (0..self.below(4)).map(|_| self.name("a")).collect();
+        let returns_nothing = self.chance(30);
+        self.line(
+            0,
+            &format!(
+                "function {}({}){},",
+                name,
+                args.join(", "),
+                if returns_nothing {
+                    " returns nothing"
+                } else {
+                    ""
+                }
+            ),
+        );
+        let mut scope = Scope {
+            numbers: args.clone(),
+            functions: functions.to_vec(),
+            in_function: true,
+            returns_nothing,
+            ..Scope::default()
+        };
+        self.block(&mut scope, 1, 6);
+        if !returns_nothing {
+            let e = self.expr(&scope, 2);
+            self.line(1, &format!("return {}.", e));
+        }
+        self.line(0, "!");
+        (name, args.len(), returns_nothing)
+    }
+
+    /// `count` statements. what they set stays in `scope`
+    fn block(&mut self, scope: &mut Scope, indent: usize, count: u64) {
+        for _ in 0..=self.below(count) {
+            self.statement(scope, indent);
+        }
+    }
+
+    fn statement(&mut self, scope: &mut Scope, indent: usize) {
+        match self.below(11) {
+            0 | 1 => {
+                let e = self.arg(scope, 3);
+                self.line(indent, &format!("call PutNum({}).", e));
+                self.line(indent, "call PutChar(32).");
+            }
+            2 if !scope.in_loop => {
+                let name = self.name("v");
+                let e = self.expr(scope, 3);
+                self.line(indent, &format!("set {} to {}.", name, e));
+                scope.numbers.push(name);
+            }
+            3 if !scope.in_loop => {
+                let name = self.name("x");
+                let len = 1 + self.below(5) as usize;
+                let elements: Vec<String> = (0..len).map(|_| self.expr(scope, 1)).collect();
+                self.line(
+                    indent,
+                    &format!("set {} to [{}].", name, elements.join(", ")),
+                );
+                scope.arrays.push((name, len));
+            }
+            4 if !scope.numbers.is_empty() => {
+                let name = self.pick(&scope.numbers);
+                let e = self.expr(scope, 3);
+                self.line(indent, &format!("change {} to {}.", name, e));
+            }
+            5 if !scope.arrays.is_empty() => {
+                let (name, len) = self.pick(&scope.arrays);
+                let index = 1 + self.below(len as u64);
+                let e = self.expr(scope, 3);
+                self.line(indent, &format!("change {}[{}] to {}.", name, index, e));
+            }
+            6 if indent < 4 => {
+                let e = self.condition(scope);
+                self.line(indent, &format!("if {},", e));
+                self.block(&mut scope.clone(), indent + 1, 4);
+                self.line(indent, "!");
+            }
+            7 if indent < 4 && !scope.in_loop => {
+                let counter = self.name("c");
+                let times = self.below(5);
+                self.line(indent, &format!("set {} to 0.", counter));
+                self.line(indent, "loop,");
+                self.line(indent + 1, &format!("if {} >= {},", counter, times));
+                self.line(indent + 2, "break.");
+                self.line(indent + 1, "!");
+                self.line(
+                    indent + 1,
+                    &format!("change {} to {} + 1.", counter, counter),
+                );
+                let mut inner = Scope {
+                    in_loop: true,
+                    ..scope.clone()
+                };
+                self.block(&mut inner, indent + 1, 4);
+                self.line(indent, "!");
+                scope.numbers.push(counter);
+            }
+            8 => {
+                let void: Vec<(String, usize, bool)> =
+                    scope.functions.iter().filter(|f| f.2).cloned().collect();
+                if !void.is_empty() {
+                    let (name, args, _) = self.pick(&void);
+                    let args = self.args(scope, args, 2);
+                    self.line(indent, &format!("call {}({}).", name, args));
+                }
+            }
+            9 if scope.in_function && indent > 1 && self.chance(30) => {
+                if scope.returns_nothing {
+                    self.line(indent, "return.");
+                } else {
+                    let e = self.expr(scope, 2);
+                    self.line(indent, &format!("return {}.", e));
+                }
+            }
+            10 if scope.in_loop && self.chance(30) => self.line(indent, "break."),
+            _ => {}
+        }
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize].clone()
+    }
+
+    /// a comparison most of the time, so both sides of the `if` run. `if` only runs its body for a 1
+    fn condition(&mut self, scope: &Scope) -> String {
+        let lhs = self.expr(scope, 1);
+        let rhs = self.expr(scope, 1);
+        if self.chance(80) {
+            let op = self.pick(&["<", ">", "<=", ">=", "=", "!="]);
+            format!("{} {} {}", lhs, op, rhs)
+        } else {
+            lhs
+        }
+    }
+
+    fn args(&mut self, scope: &Scope, count: usize, depth: u32) -> String {
+        let args: Vec<String> = (0..count).map(|_| self.arg(scope, depth)).collect();
+        args.join(", ")
+    }
+
+    fn arg(&mut self, scope: &Scope, depth: u32) -> String {
+        let e = self.expr(scope, depth);
+        // the parser only takes an arg that starts with a name, a number or `@`
+        if e.starts_with('(') {
+            format!("0 + {}", e)
+        } else {
+            e
+        }
+    }
+
+    fn expr(&mut self, scope: &Scope, depth: u32) -> String {
+        let choice = if depth == 0 {
+            self.below(4)
+        } else {
+            self.below(7)
+        };
+        match choice {
+            1 if !scope.numbers.is_empty() => self.pick(&scope.numbers),
+            2 if !scope.arrays.is_empty() => {
+                let (name, len) = self.pick(&scope.arrays);
+                // the 0th one is the length
+                format!("{}[{}]", name, self.below(len as u64 + 1))
+            }
+            3 => format!("{}", self.below(2) * 1_000_000_000_000 + self.below(100)),
+            4 | 5 => {
+                let lhs = self.expr(scope, depth - 1);
+                let rhs = self.expr(scope, depth - 1);
+                let op = self.pick(&["+", "-", "*", "<", ">", "<=", ">=", "=", "!=", "and", "or"]);
+                if self.chance(50) {
+                    format!("({} {} {})", lhs, op, rhs)
+                } else {
+                    format!("{} {} {}", lhs, op, rhs)
+                }
+            }
+            6 => {
+                let returning: Vec<(String, usize, bool)> =
+                    scope.functions.iter().filter(|f| !f.2).cloned().collect();
+                if returning.is_empty() {
+                    return format!("{}", self.below(10));
+                }
+                let (name, args, _) = self.pick(&returning);
+                let args = self.args(scope, args, depth - 1);
+                format!("{}({})", name, args)
+            }
+            _ => format!("{}", self.below(10)),
+        }
+    }
+}
@@ -8,13 +8,15 @@ for file in `ls *.ez`; do
 done
 
 cd module
-make
-./out > ../intended/module.output
+cargo run -q -- main.ez -stdlib-path ../../lib/zig-cache/lib/libstd.a
+./a.out > ../intended/module.output
+rm a.out
 echo "generated intended/module.output"
 cd ..
 
 cd cinterface
-make
-./out > ../intended/cinterface.output
+cargo run -q -- lib.ez main.c --emit-header
+./a.out > ../intended/cinterface.output
+rm a.out lib.h
 echo "generated intended/cinterface.output"
 cd ..
@@ -24,8 +24,11 @@ done
 
 for dir in cinterface module; do
   cd $dir
-  make
-  ./out > ../tmp.out
+  case $dir in
+    cinterface) cargo run -q -- lib.ez main.c --emit-header;;
+    module) cargo run -q -- main.ez -stdlib-path ../../lib/zig-cache/lib/libstd.a;;
+  esac
+  ./a.out > ../tmp.out
   case $? in
     0) echo "\"$dir\" PASSED RUNNING";;
     *)
@@ -42,8 +45,9 @@ for dir in cinterface module; do
       exit 1
       ;;
     esac
-    rm a.out
+    rm -f a.out $dir/a.out
     rm tmp.out
 done
+rm -f cinterface/lib.h
 
 echo "ALL TESTS PASSED"